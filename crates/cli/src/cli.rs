@@ -1,45 +1,69 @@
-use std::path::PathBuf;
+use std::process::ExitCode;
 
-use clap::command;
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
 
-use core::db::Database;
-use core::diagnostics::Diagnostics;
-use core::queries::{parse_file, read_file, Path};
+use core::gen::Registry;
+
+use crate::commands::{check, doc, explain, export, gen, lsp};
+use crate::report::{ColorChoice, MessageFormat, Reporter};
 
 #[derive(Parser)]
 #[command(bin_name = "haml", author = "Harrison Turton", version)]
 struct Args {
-    path: PathBuf,
-
-    #[arg(short, long)]
-    import_root: Option<String>,
-}
+    #[command(subcommand)]
+    command: Command,
 
-pub fn main() {
-    let Args { path, .. } = Args::parse();
+    /// How to write out diagnostics
+    #[arg(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
 
-    let db = Database::default();
-    let path = Path::new(&db, path);
+    /// When to color diagnostics
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+}
 
-    let file = match read_file(&db, path) {
-        Some(file) => file,
-        None => {
-            println!("Could not read file");
-            return;
-        }
-    };
+#[derive(Subcommand)]
+enum Command {
+    /// Report the diagnostics for a set of files
+    Check(check::CheckArgs),
+    /// Write reference pages for every declaration in a spec
+    Doc(doc::DocArgs),
+    /// Describe a diagnostic code, like `E0002`, or a lint at length
+    Explain(explain::ExplainArgs),
+    /// Check a config file and write out its data as JSON, YAML or TOML
+    Export(export::ExportArgs),
+    /// Generate code or schemas from a spec
+    Gen(gen::GenArgs),
+    /// Run the language server
+    Lsp(server::Args),
+}
 
-    let _ast = match parse_file(&db, file) {
-        Some(ast) => ast,
-        None => {
-            let parse_file_errs = parse_file::accumulated::<Diagnostics>(&db, file);
-            for err in parse_file_errs {
-                println!("{}", err.to_user_message(&db, &file));
-            }
-            return;
-        }
+/// Run the command line with the code generators in `registry`
+pub fn run(registry: Registry) -> ExitCode {
+    let Args {
+        command,
+        message_format,
+        color,
+    } = Args::parse();
+    // The log is written to stdout, where the other commands write their output
+    if message_format == MessageFormat::Sarif && !matches!(command, Command::Check(_)) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "`--message-format sarif` can only be used with `haml check`",
+            )
+            .exit();
+    }
+    let reporter = Reporter::new(message_format, color);
+    let code = match command {
+        Command::Check(args) => check::run(args, &reporter),
+        Command::Doc(args) => doc::run(args, &reporter),
+        Command::Explain(args) => explain::run(args),
+        Command::Export(args) => export::run(args, &reporter),
+        Command::Gen(args) => gen::run(args, &registry, &reporter),
+        Command::Lsp(args) => lsp::run(args),
     };
-
-    println!("Parsed file successfully");
+    reporter.finish();
+    code
 }
@@ -0,0 +1,183 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path as FsPath, PathBuf};
+use std::process::ExitCode;
+
+use clap::Args;
+
+use crate::report::Reporter;
+use core::db::Database;
+use core::diagnostics::{
+    apply_fixes, diagnostics_for, diagnostics_for_workspace, Diagnostic, Fixed, Level,
+};
+use core::lint::Config;
+use core::queries::{read_file, Path, TrackedConfig};
+
+/// Files with these endings are picked up when a directory is checked
+const EXTENSIONS: [&str; 2] = [".haml", ".haml.spec"];
+
+/// Fixes can uncover more problems, like a closed string revealing a missing
+/// brace after it, so they're applied until none are left, up to this many
+/// times
+const MAX_FIX_PASSES: usize = 8;
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Files, directories or glob patterns to check
+    #[arg(required = true)]
+    paths: Vec<String>,
+
+    #[arg(short, long)]
+    import_root: Option<String>,
+
+    /// Apply the fixes that diagnostics suggest, where they're certain, then
+    /// report what's left
+    #[arg(long)]
+    fix: bool,
+
+    /// Fail if there are any warnings, by reporting them as errors
+    #[arg(long)]
+    deny_warnings: bool,
+}
+
+pub fn run(args: CheckArgs, reporter: &Reporter) -> ExitCode {
+    let files = match expand_paths(&args.paths) {
+        Ok(files) => files,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let db = Database::default();
+    let mut errors = 0;
+    let mut checked = vec![];
+    for path in files {
+        // Lints are configured by the project each file is in
+        let config = match Config::find(&path) {
+            Ok(config) => config,
+            Err(message) => {
+                eprintln!("{message}");
+                errors += 1;
+                continue;
+            }
+        };
+        if args.fix {
+            if let Err(message) = fix(&db, &path, &config) {
+                eprintln!("{message}");
+                errors += 1;
+            }
+        }
+        match read_file(&db, Path::new(&db, path.clone())) {
+            Some(file) => checked.push((file, config)),
+            None => {
+                eprintln!("Could not read file {}", path.display());
+                errors += 1;
+            }
+        }
+    }
+
+    let files: Vec<_> = checked
+        .iter()
+        .map(|(file, config)| (*file, config))
+        .collect();
+    for (file, mut diagnostics) in diagnostics_for_workspace(&db, &files) {
+        if args.deny_warnings {
+            deny_warnings(&mut diagnostics);
+        }
+        errors += reporter.report(&db, file, &diagnostics);
+    }
+
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn deny_warnings(diagnostics: &mut [Diagnostic]) {
+    for diagnostic in diagnostics {
+        if diagnostic.level == Level::Warning {
+            diagnostic.level = Level::Error;
+            diagnostic
+                .notes
+                .push("warnings are denied by `--deny-warnings`".to_string());
+        }
+    }
+}
+
+/// Apply fixes to `path`, and the files it imports, until none are left.
+/// Every pass reads the files again, so it sees the previous pass's fixes.
+fn fix(db: &Database, path: &FsPath, config: &Config) -> Result<(), String> {
+    let config = TrackedConfig::new(db, config.clone());
+    for _ in 0..MAX_FIX_PASSES {
+        let Some(file) = read_file(db, Path::new(db, path.to_path_buf())) else {
+            return Ok(());
+        };
+        let diagnostics: Vec<_> = diagnostics_for(db, file, config)
+            .into_iter()
+            .flat_map(|(_, diagnostics)| diagnostics)
+            .collect();
+        let fixed = apply_fixes(db, &diagnostics);
+        if fixed.is_empty() {
+            return Ok(());
+        }
+        for Fixed { file, text, fixes } in fixed {
+            let path = file.path(db);
+            fs::write(path, text)
+                .map_err(|err| format!("Could not write {}: {err}", path.display()))?;
+            let plural = if fixes == 1 { "" } else { "es" };
+            eprintln!("Fixed {} ({fixes} fix{plural})", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Turn the paths given on the command line into a sorted list of files.
+/// Directories are searched recursively, and anything that doesn't exist is
+/// treated as a glob pattern.
+fn expand_paths(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = BTreeSet::new();
+    for pattern in paths {
+        let path = FsPath::new(pattern);
+        if path.is_dir() {
+            collect_dir(path, &mut files)
+                .map_err(|err| format!("Could not read directory {pattern}: {err}"))?;
+            continue;
+        }
+        if path.exists() {
+            files.insert(path.to_path_buf());
+            continue;
+        }
+
+        let matches =
+            glob::glob(pattern).map_err(|err| format!("Invalid pattern {pattern}: {err}"))?;
+        let before = files.len();
+        for path in matches.flatten() {
+            if path.is_file() {
+                files.insert(path);
+            }
+        }
+        if files.len() == before {
+            return Err(format!("No files match {pattern}"));
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+fn collect_dir(dir: &FsPath, files: &mut BTreeSet<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_dir(&path, files)?;
+        } else if is_haml_file(&path) {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_haml_file(path: &FsPath) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, ValueEnum};
+
+use codegen::doc;
+use core::db::Database;
+use core::queries::build_symbol_table;
+
+use super::{load_checked, write_files};
+use crate::report::Reporter;
+
+#[derive(Args)]
+pub struct DocArgs {
+    /// The spec to document
+    path: PathBuf,
+
+    /// The format of the pages
+    #[arg(long, value_enum, default_value_t = Format::Html)]
+    format: Format,
+
+    /// The directory to write the pages into
+    #[arg(short, long, default_value = "doc")]
+    out_dir: PathBuf,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Static pages with a stylesheet and search, that open straight from disk
+    Html,
+    /// Markdown pages, to publish with another site generator
+    Markdown,
+}
+
+pub fn run(args: DocArgs, reporter: &Reporter) -> ExitCode {
+    let db = Database::default();
+    let Some(file) = load_checked(&db, &args.path, reporter) else {
+        return ExitCode::FAILURE;
+    };
+    let symbols = build_symbol_table(&db, file).unwrap_or_default();
+    let format = match args.format {
+        Format::Html => doc::Format::Html,
+        Format::Markdown => doc::Format::Markdown,
+    };
+    write_files(Some(args.out_dir), doc::generate(&db, &symbols, format))
+}
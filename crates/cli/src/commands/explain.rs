@@ -0,0 +1,40 @@
+use std::process::ExitCode;
+
+use clap::Args;
+
+use core::diagnostics::codes::{self, CODES};
+use core::lint::{self, LINTS};
+
+#[derive(Args)]
+pub struct ExplainArgs {
+    /// The code or lint to explain. Without one, every code and lint is listed.
+    code: Option<String>,
+}
+
+pub fn run(args: ExplainArgs) -> ExitCode {
+    let Some(name) = args.code else {
+        for code in CODES {
+            println!("{}  {}", code.name, code.title);
+        }
+        println!("\nLints:");
+        let width = LINTS.iter().map(|lint| lint.name.len()).max().unwrap_or(0);
+        for lint in LINTS {
+            println!("{:width$}  {} ({})", lint.name, lint.title, lint.default);
+        }
+        return ExitCode::SUCCESS;
+    };
+    let code = codes::find(&name).or_else(|| lint::find(&name).map(|lint| lint.code()));
+    match code {
+        Some(code) => {
+            println!("{}: {}\n", code.name, code.title);
+            print!("{}", code.explanation);
+            ExitCode::SUCCESS
+        }
+        None => {
+            eprintln!(
+                "`{name}` is not a diagnostic code or lint. Run `haml explain` to list them."
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, ValueEnum};
+use serde_json::{Map, Number, Value as Json};
+
+use core::db::Database;
+use core::queries::{check_file, SourceFile};
+use core::sema::{Instance, Value};
+
+use super::{load_checked, write_output};
+use crate::report::Reporter;
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// The config file to export
+    path: PathBuf,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// How references to other instances are written
+    #[arg(long, value_enum, default_value_t = Refs::Inline)]
+    refs: Refs,
+
+    /// Write to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Refs {
+    /// Replace each reference with a copy of the instance it refers to
+    Inline,
+    /// Write each reference as `{"$ref": "#/<name>"}`, and include the
+    /// instances they refer to at the top level
+    Ref,
+}
+
+pub fn run(args: ExportArgs, reporter: &Reporter) -> ExitCode {
+    let db = Database::default();
+    let Some(file) = load_checked(&db, &args.path, reporter) else {
+        return ExitCode::FAILURE;
+    };
+    let instances = check_file(&db, file).unwrap_or_default();
+    let text = match export(&instances, file, args.refs, args.format) {
+        Ok(text) => text,
+        Err(message) => {
+            eprintln!("Could not export {}: {message}", args.path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    write_output(args.output.as_deref(), &text)
+}
+
+/// Write the instances declared in `file` as a document in `format`
+pub(crate) fn export(
+    instances: &[Instance],
+    file: SourceFile,
+    refs: Refs,
+    format: Format,
+) -> Result<String, String> {
+    let exported = instances
+        .iter()
+        .filter(|instance| instance.span.file == file)
+        .map(|instance| instance.name.as_str());
+    let document = Exporter::new(instances, refs).document(exported)?;
+    match format {
+        Format::Json => serde_json::to_string_pretty(&document)
+            .map(|json| json + "\n")
+            .map_err(|err| err.to_string()),
+        Format::Yaml => serde_yaml::to_string(&document).map_err(|err| err.to_string()),
+        Format::Toml => toml::to_string(&document).map_err(|err| err.to_string()),
+    }
+}
+
+/// Turns checked instances into a document keyed by instance name. Keys are
+/// sorted, so the output only changes when the config does.
+struct Exporter<'a> {
+    instances: HashMap<&'a str, &'a Instance>,
+    refs: Refs,
+}
+
+impl<'a> Exporter<'a> {
+    fn new(instances: &'a [Instance], refs: Refs) -> Exporter<'a> {
+        let instances = instances
+            .iter()
+            .map(|instance| (instance.name.as_str(), instance))
+            .collect();
+        Exporter { instances, refs }
+    }
+
+    fn document<'n>(&self, names: impl Iterator<Item = &'n str>) -> Result<Json, String> {
+        let mut document = Map::new();
+        let mut pending: Vec<String> = names.map(str::to_string).collect();
+        while let Some(name) = pending.pop() {
+            if document.contains_key(&name) {
+                continue;
+            }
+            let mut referenced = BTreeSet::new();
+            let value = self.instance(&name, &mut vec![], &mut referenced)?;
+            document.insert(name, value);
+            // Instances that are referred to are exported too, so that every
+            // `$ref` points into the document
+            pending.extend(referenced);
+        }
+        Ok(Json::Object(document))
+    }
+
+    fn instance(
+        &self,
+        name: &str,
+        stack: &mut Vec<String>,
+        referenced: &mut BTreeSet<String>,
+    ) -> Result<Json, String> {
+        let instance = self.instances[name];
+        stack.push(name.to_string());
+        let value = self.value(&instance.value, stack, referenced);
+        stack.pop();
+        value
+    }
+
+    fn value(
+        &self,
+        value: &Value,
+        stack: &mut Vec<String>,
+        referenced: &mut BTreeSet<String>,
+    ) -> Result<Json, String> {
+        let json = match value {
+            Value::String(value) | Value::Type(value) => Json::String(value.clone()),
+            Value::Int(value) => Json::from(*value),
+            Value::Uint(value) => Json::from(*value),
+            Value::Float(value) => Number::from_f64(*value).map_or(Json::Null, Json::Number),
+            Value::Object(fields) => {
+                let mut object = Map::new();
+                for (key, value) in fields {
+                    object.insert(key.clone(), self.value(value, stack, referenced)?);
+                }
+                Json::Object(object)
+            }
+            Value::Ref(name) if self.refs == Refs::Ref => {
+                referenced.insert(name.clone());
+                let mut object = Map::new();
+                object.insert("$ref".to_string(), Json::String(format!("#/{name}")));
+                Json::Object(object)
+            }
+            Value::Ref(name) => {
+                if stack.contains(name) {
+                    return Err(format!(
+                        "cannot inline `{name}` because it refers to itself, use `--refs ref` instead"
+                    ));
+                }
+                self.instance(name, stack, referenced)?
+            }
+        };
+        Ok(json)
+    }
+}
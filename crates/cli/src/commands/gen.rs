@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::path::{Path as FsPath, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+
+use codegen::plugin::Plugin;
+use core::db::Database;
+use core::gen::{Generator, Model, Options, OutputFile, Registry};
+use core::queries::SourceFile;
+
+use super::{load_checked, write_files, write_output};
+use crate::report::Reporter;
+
+#[derive(Args)]
+#[command(arg_required_else_help = true)]
+pub struct GenArgs {
+    #[command(subcommand)]
+    backend: Option<Backend>,
+
+    /// Run an external code generator instead of a backend, with the same
+    /// arguments: `haml gen --plugin <executable> <path> [--opt key=value]...
+    /// [-o file | --out-dir dir]`. It reads the spec's model as JSON from stdin, and writes
+    /// the files it generates as JSON to stdout.
+    #[arg(long, value_name = "EXECUTABLE")]
+    plugin: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Backend {
+    /// List the backends in the registry. Any of them can be run with
+    /// `haml gen <backend> <path> [--opt key=value]... [-o file | --out-dir
+    /// dir]`.
+    List,
+    #[command(external_subcommand)]
+    Registered(Vec<String>),
+}
+
+// The arguments of a backend selected by name from the registry, or of a
+// plugin
+#[derive(Parser)]
+pub(crate) struct RegisteredArgs {
+    /// The spec to generate from
+    path: PathBuf,
+
+    /// An option for the backend, like `--opt root=Folder`, or `--opt
+    /// mapping=api.toml` for `openapi`
+    #[arg(long = "opt", value_parser = parse_option)]
+    options: Vec<(String, String)>,
+
+    /// Write the generated file here instead of to stdout, for backends that
+    /// generate a single file
+    #[arg(short, long, conflicts_with = "out_dir")]
+    output: Option<PathBuf>,
+
+    /// Write the files into this directory instead of to stdout
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+}
+
+fn parse_option(option: &str) -> Result<(String, String), String> {
+    match option.split_once('=') {
+        Some((key, value)) => Ok((key.to_string(), value.to_string())),
+        None => Err(format!("expected `key=value`, found `{option}`")),
+    }
+}
+
+pub fn run(args: GenArgs, registry: &Registry, reporter: &Reporter) -> ExitCode {
+    match (args.plugin, args.backend) {
+        // Everything after `--plugin` is the plugin's arguments, which clap
+        // takes as the name of a backend
+        (Some(program), Some(Backend::Registered(args))) => {
+            let bin_name = format!("haml gen --plugin {}", program.display());
+            let args = RegisteredArgs::parse_from(std::iter::once(bin_name).chain(args));
+            plugin(Plugin::new(program), args, reporter)
+        }
+        (Some(_), _) => {
+            eprintln!("`--plugin` needs a spec to generate from");
+            ExitCode::FAILURE
+        }
+        (None, Some(Backend::List)) => list(registry),
+        (None, Some(Backend::Registered(args))) => registered(registry, args, reporter),
+        // Clap prints the help without a backend
+        (None, None) => ExitCode::FAILURE,
+    }
+}
+
+fn list(registry: &Registry) -> ExitCode {
+    let width = registry
+        .iter()
+        .map(|generator| generator.name().len())
+        .max()
+        .unwrap_or(0);
+    for generator in registry.iter() {
+        println!("{:width$}  {}", generator.name(), generator.description());
+    }
+    ExitCode::SUCCESS
+}
+
+fn registered(registry: &Registry, args: Vec<String>, reporter: &Reporter) -> ExitCode {
+    let name = &args[0];
+    let Some(generator) = registry.get(name) else {
+        eprintln!("There is no backend named `{name}`, see `haml gen list`");
+        return ExitCode::FAILURE;
+    };
+    let bin_name = format!("haml gen {name}");
+    let args = RegisteredArgs::parse_from(std::iter::once(bin_name).chain(args[1..].to_vec()));
+    let db = Database::default();
+    match generate(&db, generator, &args, reporter) {
+        Some(files) => write_generated(args, files),
+        None => ExitCode::FAILURE,
+    }
+}
+
+/// Generate files from the spec at `args.path` with `generator`. Returns
+/// nothing if the spec or the generator reported errors.
+pub(crate) fn generate(
+    db: &Database,
+    generator: &dyn Generator,
+    args: &RegisteredArgs,
+    reporter: &Reporter,
+) -> Option<Vec<OutputFile>> {
+    let (file, model) = load_model(db, &args.path, reporter)?;
+    let options: Options = args.options.iter().cloned().collect();
+    match generator.generate(db, &model, &options) {
+        Ok(files) => Some(files),
+        Err(diagnostics) => {
+            reporter.report(db, file, &diagnostics);
+            None
+        }
+    }
+}
+
+fn plugin(plugin: Plugin, args: RegisteredArgs, reporter: &Reporter) -> ExitCode {
+    let db = Database::default();
+    let Some((file, model)) = load_model(&db, &args.path, reporter) else {
+        return ExitCode::FAILURE;
+    };
+    let options: Options = args.options.iter().cloned().collect();
+    let output = match plugin.run(&db, &model, &options) {
+        Ok(output) => output,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+    reporter.report(&db, file, &output.diagnostics);
+    if output.has_errors() {
+        return ExitCode::FAILURE;
+    }
+    write_generated(args, output.files)
+}
+
+// Write the files to the output file or directory the arguments name
+fn write_generated(args: RegisteredArgs, files: Vec<OutputFile>) -> ExitCode {
+    let Some(output) = args.output else {
+        return write_files(args.out_dir, file_map(files));
+    };
+    match &files[..] {
+        [file] => write_output(Some(&output), &file.contents),
+        files => {
+            eprintln!(
+                "`--output` needs a single file, but {} were generated, use `--out-dir`",
+                files.len()
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn load_model(db: &Database, path: &FsPath, reporter: &Reporter) -> Option<(SourceFile, Model)> {
+    let file = load_checked(db, path, reporter)?;
+    match Model::new(db, file) {
+        Ok(model) => Some((file, model)),
+        Err(diagnostics) => {
+            reporter.report(db, file, &diagnostics);
+            None
+        }
+    }
+}
+
+fn file_map(files: Vec<OutputFile>) -> BTreeMap<String, String> {
+    files
+        .into_iter()
+        .map(|file| (file.path, file.contents))
+        .collect()
+}
@@ -0,0 +1,11 @@
+use std::process::ExitCode;
+
+pub fn run(args: server::Args) -> ExitCode {
+    match server::run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Language server failed: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path as FsPath, PathBuf};
+use std::process::ExitCode;
+
+use core::db::Database;
+use core::diagnostics::diagnostics_for;
+use core::lint::Config;
+use core::queries::{check_file, read_file, Path, SourceFile, TrackedConfig};
+
+use crate::report::Reporter;
+
+pub mod check;
+pub mod doc;
+pub mod explain;
+pub mod export;
+pub mod gen;
+pub mod lsp;
+#[cfg(test)]
+mod test;
+
+/// Read, type-check and lint a file, reporting any diagnostics like `haml
+/// check` does. Returns the file only if it, and everything it imports, is free
+/// of errors.
+fn load_checked(db: &Database, path: &FsPath, reporter: &Reporter) -> Option<SourceFile> {
+    let config = match Config::find(path) {
+        Ok(config) => TrackedConfig::new(db, config),
+        Err(message) => {
+            eprintln!("{message}");
+            return None;
+        }
+    };
+    let Some(file) = read_file(db, Path::new(db, path.to_path_buf())) else {
+        eprintln!("Could not read file {}", path.display());
+        return None;
+    };
+    let checked = check_file(db, file);
+    let mut errors = 0;
+    for (file, diagnostics) in diagnostics_for(db, file, config) {
+        errors += reporter.report(db, file, &diagnostics);
+    }
+    (checked.is_some() && errors == 0).then_some(file)
+}
+
+/// Write generated text to `output`, or to stdout if there isn't one
+fn write_output(output: Option<&FsPath>, text: &str) -> ExitCode {
+    let Some(output) = output else {
+        print!("{text}");
+        return ExitCode::SUCCESS;
+    };
+    match fs::write(output, text) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Could not write {}: {err}", output.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Write generated files by their path into `out_dir`. Without a directory to
+/// write to, files are printed one after another, each after a comment with
+/// its path. A single file is printed as it is, so it can be piped.
+fn write_files(out_dir: Option<PathBuf>, files: BTreeMap<String, String>) -> ExitCode {
+    let Some(out_dir) = out_dir else {
+        if files.len() == 1 {
+            let text = files.into_values().next().unwrap_or_default();
+            return write_output(None, &text);
+        }
+        for (path, text) in files {
+            println!("// {path}");
+            print!("{text}");
+        }
+        return ExitCode::SUCCESS;
+    };
+    for (path, text) in files {
+        let path = out_dir.join(path);
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if write_output(Some(&path), &text) == ExitCode::FAILURE {
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
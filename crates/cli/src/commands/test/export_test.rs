@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use core::db::Database;
+use core::queries::{check_file, SourceFile};
+
+use crate::commands::export::{export, Format, Refs};
+
+const SPEC: &str = r#"
+constructor service {
+    name: string;
+    port?: uint32;
+    upstream?: Service;
+}
+"#;
+
+// Check `text` and export its instances
+fn exported(text: &str, refs: Refs, format: Format) -> Result<String, String> {
+    let db = Database::default();
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), format!("{SPEC}{text}"));
+    let instances = check_file(&db, file).expect("the config checks");
+    export(&instances, file, refs, format)
+}
+
+const CHAIN: &str = r#"
+service Cache {
+    name: "cache",
+    port: 6379,
+}
+
+service Api {
+    name: "api",
+    upstream: Cache,
+}
+"#;
+
+#[test]
+fn test_inlines_references_and_leaves_out_missing_optional_fields() {
+    let json = exported(CHAIN, Refs::Inline, Format::Json).unwrap();
+    assert_eq!(
+        json,
+        r#"{
+  "Api": {
+    "name": "api",
+    "upstream": {
+      "name": "cache",
+      "port": 6379
+    }
+  },
+  "Cache": {
+    "name": "cache",
+    "port": 6379
+  }
+}
+"#
+    );
+}
+
+#[test]
+fn test_writes_references_as_refs() {
+    let json = exported(CHAIN, Refs::Ref, Format::Json).unwrap();
+    assert_eq!(
+        json,
+        r##"{
+  "Api": {
+    "name": "api",
+    "upstream": {
+      "$ref": "#/Cache"
+    }
+  },
+  "Cache": {
+    "name": "cache",
+    "port": 6379
+  }
+}
+"##
+    );
+}
+
+#[test]
+fn test_cycles_can_only_be_exported_as_refs() {
+    let cycle = r#"
+        service Ping {
+            name: "ping",
+            upstream: Pong,
+        }
+
+        service Pong {
+            name: "pong",
+            upstream: Ping,
+        }
+    "#;
+    let err = exported(cycle, Refs::Inline, Format::Json).unwrap_err();
+    assert!(err.contains("refers to itself"), "{err}");
+
+    let json = exported(cycle, Refs::Ref, Format::Json).unwrap();
+    assert!(json.contains(r##""$ref": "#/Ping""##), "{json}");
+    assert!(json.contains(r##""$ref": "#/Pong""##), "{json}");
+}
+
+#[test]
+fn test_exports_yaml() {
+    let yaml = exported(CHAIN, Refs::Ref, Format::Yaml).unwrap();
+    assert_eq!(
+        yaml,
+        "Api:\n  name: api\n  upstream:\n    $ref: '#/Cache'\nCache:\n  name: cache\n  port: 6379\n"
+    );
+}
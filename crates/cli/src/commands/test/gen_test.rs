@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Parser;
+use core::db::Database;
+use serde_json::Value as Json;
+
+use crate::commands::gen::{generate, RegisteredArgs};
+use crate::report::{ColorChoice, MessageFormat, Reporter};
+
+// An API described by constructors other than the example's `api` and
+// `endpoint`, so it needs a mapping
+const SPEC: &str = r#"
+constructor service {
+    title: string;
+    routes: map<string, Route>;
+}
+
+constructor route {
+    op: string;
+    verb?: string;
+    request: struct;
+    response: struct;
+}
+
+struct Ping {}
+struct Pong { at: string; }
+
+route PingRoute {
+    op: "ping",
+    verb: "post",
+    request: Ping,
+    response: Pong,
+}
+
+service Health {
+    title: "Health",
+    routes: { "/ping": PingRoute },
+}
+"#;
+
+const MAPPING: &str = r#"
+api = "service"
+title = "title"
+endpoints = "routes"
+method = "verb"
+operation_id = "op"
+"#;
+
+// A fresh directory with the spec and its mapping
+fn project(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("haml-gen-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("api.haml"), SPEC).unwrap();
+    fs::write(dir.join("mapping.toml"), MAPPING).unwrap();
+    dir
+}
+
+// Run `haml gen <backend>` with `args`, returning the files it generated
+fn gen(backend: &str, args: &[String]) -> Option<Vec<(String, String)>> {
+    let registry = codegen::registry();
+    let generator = registry.get(backend).unwrap();
+    let bin_name = format!("haml gen {backend}");
+    let args = RegisteredArgs::parse_from(std::iter::once(&bin_name).chain(args));
+    let reporter = Reporter::new(MessageFormat::Short, ColorChoice::Never);
+    let files = generate(&Database::default(), generator, &args, &reporter)?;
+    Some(
+        files
+            .into_iter()
+            .map(|file| (file.path, file.contents))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_openapi_reads_its_mapping_from_a_file() {
+    let dir = project("mapping");
+    let spec = dir.join("api.haml").display().to_string();
+    let mapping = format!("mapping={}", dir.join("mapping.toml").display());
+
+    let files = gen(
+        "openapi",
+        &[spec.clone(), "--opt".to_string(), mapping.clone()],
+    )
+    .unwrap();
+    assert_eq!(files.len(), 1);
+    let document: Json = serde_json::from_str(&files[0].1).unwrap();
+    assert_eq!(document["info"]["title"], "Health");
+    assert_eq!(document["paths"]["/ping"]["post"]["operationId"], "ping");
+
+    // Options take precedence over the file
+    let operation_id = "operation_id=verb".to_string();
+    let args = [
+        spec.clone(),
+        "--opt".to_string(),
+        mapping,
+        "--opt".to_string(),
+        operation_id,
+    ];
+    let files = gen("openapi", &args).unwrap();
+    let document: Json = serde_json::from_str(&files[0].1).unwrap();
+    assert_eq!(document["paths"]["/ping"]["post"]["operationId"], "post");
+
+    // Without the mapping, there's no `api` instance to build from
+    assert!(gen("openapi", &[spec]).is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
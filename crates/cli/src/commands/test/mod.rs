@@ -0,0 +1,2 @@
+pub mod export_test;
+pub mod gen_test;
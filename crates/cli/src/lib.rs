@@ -0,0 +1,17 @@
+//! The `haml` command line. A crate with code generators of its own can build
+//! a `haml` that includes them by registering them on top of the built-in
+//! ones and calling [`run`]:
+//!
+//! ```ignore
+//! fn main() -> std::process::ExitCode {
+//!     let mut registry = codegen::registry();
+//!     registry.register(my_backend::Kotlin);
+//!     cli::run(registry)
+//! }
+//! ```
+
+mod cli;
+mod commands;
+mod report;
+
+pub use cli::run;
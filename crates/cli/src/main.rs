@@ -1,5 +1,5 @@
-mod cli;
+use std::process::ExitCode;
 
-fn main() {
-    cli::main()
+fn main() -> ExitCode {
+    cli::run(codegen::registry())
 }
@@ -0,0 +1,161 @@
+//! Writing out diagnostics in the format chosen with `--message-format`, in
+//! color if `--color` says so
+
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::io;
+
+use clap::ValueEnum;
+use is_terminal::IsTerminal;
+
+use core::db::Database;
+use core::diagnostics::{Diagnostic, Level, RenderOptions, SarifLog};
+use core::queries::SourceFile;
+
+/// The width diagnostics are trimmed to when `COLUMNS` doesn't say
+const DEFAULT_WIDTH: usize = 140;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    /// Rendered for people, with the code each diagnostic points at
+    #[default]
+    Human,
+    /// A JSON object per diagnostic, one per line
+    Json,
+    /// A line per diagnostic, like `file:line:column: error: message`
+    Short,
+    /// A SARIF 2.1.0 log of every diagnostic, written to stdout once
+    /// `haml check` has finished
+    Sarif,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color diagnostics when stderr is a terminal. `NO_COLOR` turns color off,
+    /// and `CLICOLOR_FORCE` turns it on when writing elsewhere.
+    #[default]
+    Auto,
+    /// Always color diagnostics
+    Always,
+    /// Never color diagnostics
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto if env_flag("NO_COLOR") => false,
+            ColorChoice::Auto if env_flag("CLICOLOR_FORCE") => true,
+            ColorChoice::Auto => io::stderr().is_terminal(),
+        }
+    }
+}
+
+// Whether an environment variable is set to something other than "" or "0"
+fn env_flag(name: &str) -> bool {
+    env::var_os(name)
+        .filter(|value| !value.is_empty() && value != "0")
+        .is_some()
+}
+
+pub struct Reporter {
+    format: MessageFormat,
+    options: RenderOptions,
+    sarif: RefCell<SarifLog>,
+    errors: Cell<usize>,
+    warnings: Cell<usize>,
+}
+
+impl Reporter {
+    pub fn new(format: MessageFormat, color: ColorChoice) -> Reporter {
+        let width = env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(DEFAULT_WIDTH);
+        Reporter {
+            format,
+            options: RenderOptions {
+                color: color.enabled(),
+                max_width: Some(width),
+            },
+            sarif: RefCell::default(),
+            errors: Cell::new(0),
+            warnings: Cell::new(0),
+        }
+    }
+
+    /// Write diagnostics found while checking `file` to stderr, so they don't
+    /// mix with what a command writes to stdout, and return how many of them
+    /// were errors. Diagnostics can come from the files it imports, so
+    /// each one is rendered against the file its span is in, and the ones in
+    /// the same file are printed together.
+    pub fn report(&self, db: &Database, file: SourceFile, diagnostics: &[Diagnostic]) -> usize {
+        let file_of = |diagnostic: &Diagnostic| diagnostic.span.map_or(file, |span| span.file);
+        let mut files = vec![];
+        for diagnostic in diagnostics {
+            if !files.contains(&file_of(diagnostic)) {
+                files.push(file_of(diagnostic));
+            }
+        }
+        let mut diagnostics: Vec<_> = diagnostics.iter().collect();
+        diagnostics.sort_by_key(|diagnostic| files.iter().position(|&f| f == file_of(diagnostic)));
+
+        let mut errors = 0;
+        for diagnostic in diagnostics {
+            match diagnostic.level {
+                Level::Error => errors += 1,
+                Level::Warning => self.warnings.set(self.warnings.get() + 1),
+                Level::Info => {}
+            }
+            let file = file_of(diagnostic);
+            let message = match self.format {
+                // A blank line keeps diagnostics apart, like rustc
+                MessageFormat::Human => {
+                    format!("{}\n", diagnostic.render(db, &file, &self.options))
+                }
+                MessageFormat::Json => diagnostic.to_json(db, &file).to_string(),
+                MessageFormat::Short => diagnostic.to_short_message(db, &file),
+                MessageFormat::Sarif => {
+                    self.sarif.borrow_mut().add(db, file, diagnostic);
+                    continue;
+                }
+            };
+            eprintln!("{message}");
+        }
+        self.errors.set(self.errors.get() + errors);
+        errors
+    }
+
+    /// Write out anything that's only written once the command has finished:
+    /// the SARIF log, or how many errors and warnings there were
+    pub fn finish(&self) {
+        match self.format {
+            MessageFormat::Sarif => {
+                let log = self.sarif.borrow().to_json();
+                println!("{}", serde_json::to_string_pretty(&log).unwrap_or_default());
+            }
+            MessageFormat::Human => {
+                if let Some(summary) = summary(self.errors.get(), self.warnings.get()) {
+                    eprintln!("{summary}");
+                }
+            }
+            MessageFormat::Json | MessageFormat::Short => {}
+        }
+    }
+}
+
+// Like `3 errors, 2 warnings emitted`
+fn summary(errors: usize, warnings: usize) -> Option<String> {
+    let count = |count: usize, noun: &str| match count {
+        0 => None,
+        1 => Some(format!("1 {noun}")),
+        count => Some(format!("{count} {noun}s")),
+    };
+    let counts: Vec<_> = [count(errors, "error"), count(warnings, "warning")]
+        .into_iter()
+        .flatten()
+        .collect();
+    (!counts.is_empty()).then(|| format!("{} emitted", counts.join(", ")))
+}
@@ -0,0 +1,77 @@
+//! Helpers for generating Rust from a spec in a `build.rs` script, so the
+//! generated types are rebuilt whenever the spec changes.
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     // spec/api.haml.spec starts with `package api;`
+//!     codegen::build::compile("spec/api.haml.spec").unwrap();
+//! }
+//!
+//! // src/lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/api.rs"));
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::{Path as FsPath, PathBuf};
+
+use core::db::Database;
+use core::diagnostics::{diagnostics_for, Level};
+use core::lint::Config;
+use core::queries::{
+    build_symbol_table, check_file, read_file, resolve_imports, Path, TrackedConfig,
+};
+
+use crate::{module_name, rust};
+
+/// Generate Rust for `spec` into `$OUT_DIR/<name>.rs`, where `<name>` is the
+/// package of the spec joined with `_`, or its file name up to the first `.`
+/// without one, as `haml gen rust` names it. Returns the generated file.
+pub fn compile(spec: impl AsRef<FsPath>) -> Result<PathBuf, String> {
+    let out_dir = env::var_os("OUT_DIR").ok_or("OUT_DIR is not set, is this a build script?")?;
+    compile_to(spec, out_dir)
+}
+
+/// Like `compile`, but into `out_dir` rather than `$OUT_DIR`
+pub fn compile_to(
+    spec: impl AsRef<FsPath>,
+    out_dir: impl AsRef<FsPath>,
+) -> Result<PathBuf, String> {
+    let spec = spec.as_ref();
+    let db = Database::default();
+    let Some(file) = read_file(&db, Path::new(&db, spec.to_path_buf())) else {
+        return Err(format!("Could not read file {}", spec.display()));
+    };
+
+    // Cargo reruns the build script if the spec or anything it imports changes
+    for file in resolve_imports(&db, file) {
+        println!("cargo:rerun-if-changed={}", file.path(&db).display());
+    }
+
+    let config = TrackedConfig::new(&db, Config::find(spec)?);
+    let checked = check_file(&db, file);
+    let errors: Vec<_> = diagnostics_for(&db, file, config)
+        .into_iter()
+        .flat_map(|(_, diagnostics)| diagnostics)
+        .filter(|diagnostic| matches!(diagnostic.level, Level::Error))
+        .map(|diagnostic| {
+            let file = diagnostic.span.map_or(file, |span| span.file);
+            diagnostic.to_user_message(&db, &file)
+        })
+        .collect();
+    if checked.is_none() || !errors.is_empty() {
+        return Err(format!(
+            "Could not generate Rust for {}\n{}",
+            spec.display(),
+            errors.join("\n\n")
+        ));
+    }
+
+    let symbols = build_symbol_table(&db, file).unwrap_or_default();
+    let name = module_name(&db, file).join("_");
+    let output = out_dir.as_ref().join(format!("{name}.rs"));
+    fs::write(&output, rust::generate(&db, &symbols))
+        .map_err(|err| format!("Could not write {}: {err}", output.display()))?;
+    Ok(output)
+}
@@ -0,0 +1,822 @@
+//! Reference documentation for the declarations of a spec, as static HTML or
+//! Markdown pages with a search index.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use serde_json::json;
+
+use core::ast::node::{
+    AliasDecl, Annotation, AnnotationFieldDecl, AnnotationFieldValue, BlockDecl, DocComment,
+    FieldDecl, FieldType,
+};
+use core::sema::{type_name, Symbol, SymbolTable};
+use core::Db;
+
+use crate::module_name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+/// Render a page for every struct, constructor and annotation in `symbols`
+/// into `types/`, an index page, and a search index. Returns the text of each
+/// file by its path.
+pub fn generate(db: &dyn Db, symbols: &SymbolTable, format: Format) -> BTreeMap<String, String> {
+    let pages = Pages::new(db, symbols).pages;
+    let extension = match format {
+        Format::Html => "html",
+        Format::Markdown => "md",
+    };
+
+    let mut files = BTreeMap::new();
+    for page in &pages {
+        let text = match format {
+            Format::Html => html::page(page, &pages),
+            Format::Markdown => markdown::page(page),
+        };
+        files.insert(format!("types/{}.{extension}", page.file), text);
+    }
+    let index = match format {
+        Format::Html => html::index(&pages),
+        Format::Markdown => markdown::index(&pages),
+    };
+    files.insert(format!("index.{extension}"), index);
+
+    let search = search_index(&pages, extension);
+    files.insert(
+        "search.json".to_string(),
+        serde_json::to_string_pretty(&search).unwrap_or_default() + "\n",
+    );
+    if format == Format::Html {
+        files.insert("search.js".to_string(), html::search_script(&search));
+        files.insert("style.css".to_string(), html::STYLE.to_string());
+    }
+    files
+}
+
+/// Everything shown on the page of one declaration
+#[derive(Debug)]
+struct Page {
+    name: String,
+    // The name of the page's file in `types/`, without the extension
+    file: String,
+    kind: &'static str,
+    package: String,
+    docs: Option<String>,
+    annotations: Vec<String>,
+    sections: Vec<Section>,
+    used_by: Vec<Link>,
+}
+
+#[derive(Debug)]
+struct Section {
+    title: &'static str,
+    note: Option<String>,
+    rows: Vec<Row>,
+}
+
+/// A field, union variant or annotation field
+#[derive(Debug)]
+struct Row {
+    name: String,
+    typ: Vec<TypePart>,
+    optional: bool,
+    docs: Option<String>,
+    annotations: Vec<String>,
+}
+
+#[derive(Debug)]
+enum TypePart {
+    Text(String),
+    /// A type with its own page
+    Link(Link),
+}
+
+/// The name of a page and of its file
+#[derive(Debug, Clone)]
+struct Link {
+    name: String,
+    file: String,
+}
+
+struct Pages<'db> {
+    db: &'db dyn Db,
+    symbols: &'db SymbolTable,
+    pages: Vec<Page>,
+    // File names by page name
+    files: BTreeMap<String, String>,
+    // Page names by the names of the pages that refer to them
+    used_by: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl<'db> Pages<'db> {
+    fn new(db: &'db dyn Db, symbols: &'db SymbolTable) -> Pages<'db> {
+        let mut pages = Pages {
+            db,
+            symbols,
+            pages: vec![],
+            files: file_names(symbols),
+            used_by: BTreeMap::new(),
+        };
+        for (name, symbol) in symbols.iter() {
+            if let Some(page) = pages.page(name, symbol) {
+                pages.pages.push(page);
+            }
+        }
+        for page in &mut pages.pages {
+            if let Some(users) = pages.used_by.get(&page.name) {
+                page.used_by = users.iter().map(|user| link(&pages.files, user)).collect();
+            }
+        }
+        pages
+    }
+
+    fn page(&mut self, name: &str, symbol: &Symbol) -> Option<Page> {
+        let (kind, docs, annotations) = match symbol {
+            Symbol::Struct(decl) => ("struct", &decl.docs, &decl.annotations),
+            Symbol::Constructor(decl) => ("constructor", &decl.docs, &decl.annotations),
+            Symbol::Annotation(decl) => ("annotation", &decl.docs, &decl.annotations),
+            Symbol::Instance(_) => return None,
+        };
+        let sections = match symbol {
+            Symbol::Struct(decl) => self.block(name, &decl.content),
+            Symbol::Constructor(decl) => self.block(name, &decl.content),
+            Symbol::Annotation(decl) => vec![self.annotation_fields(&decl.fields)],
+            Symbol::Instance(_) => vec![],
+        };
+        let mut page = Page {
+            name: name.to_string(),
+            file: link(&self.files, name).file,
+            kind,
+            package: module_name(self.db, symbol.name().span.file).join("."),
+            docs: self.docs(docs),
+            annotations: self.annotations(name, annotations),
+            sections,
+            used_by: vec![],
+        };
+        if let Symbol::Constructor(_) = symbol {
+            let note = format!("Used as a type with the name `{}`.", type_name(name));
+            page.docs = Some(match page.docs {
+                Some(docs) => format!("{docs}\n\n{note}"),
+                None => note,
+            });
+        }
+        Some(page)
+    }
+
+    fn block(&mut self, owner: &str, block: &BlockDecl) -> Vec<Section> {
+        match block {
+            BlockDecl::FieldSet(fields) => vec![Section {
+                title: "Fields",
+                note: None,
+                rows: self.fields(owner, &fields.fields),
+            }],
+            BlockDecl::Repeatable(fields) => vec![Section {
+                title: "Fields",
+                note: Some("Repeatable: a map of names to entries with these fields.".to_string()),
+                rows: self.fields(owner, &fields.fields),
+            }],
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => {
+                let mut typ = vec![TypePart::Text("map<".to_string())];
+                typ.extend(self.field_type(owner, &map.key));
+                typ.push(TypePart::Text(", ".to_string()));
+                typ.extend(self.field_type(owner, &map.value));
+                typ.push(TypePart::Text(">".to_string()));
+                vec![Section {
+                    title: "Alias",
+                    note: None,
+                    rows: vec![Row {
+                        name: String::new(),
+                        typ,
+                        optional: false,
+                        docs: None,
+                        annotations: vec![],
+                    }],
+                }]
+            }
+            BlockDecl::Union(union) => {
+                let note = match union.discriminator {
+                    Some(discriminator) => format!(
+                        "One of these variants, chosen by the `{}` field.",
+                        discriminator.text(self.db)
+                    ),
+                    None => "One of these variants, as an object with a single field.".to_string(),
+                };
+                vec![Section {
+                    title: "Variants",
+                    note: Some(note),
+                    rows: self.fields(owner, &union.variants.fields),
+                }]
+            }
+        }
+    }
+
+    fn fields(&mut self, owner: &str, fields: &[FieldDecl]) -> Vec<Row> {
+        fields
+            .iter()
+            .map(|field| Row {
+                name: field.name.text(self.db).to_string(),
+                typ: self.field_type(owner, &field.typ),
+                optional: field.optional,
+                docs: self.docs(&field.docs),
+                annotations: self.annotations(owner, &field.annotations),
+            })
+            .collect()
+    }
+
+    fn annotation_fields(&self, fields: &[AnnotationFieldDecl]) -> Section {
+        let rows = fields
+            .iter()
+            .map(|field| {
+                let typ = match field.value {
+                    AnnotationFieldValue::String(_) => "string",
+                    AnnotationFieldValue::Uint32(_) => "uint32",
+                    AnnotationFieldValue::Uint64(_) => "uint64",
+                    AnnotationFieldValue::Int32(_) => "int32",
+                    AnnotationFieldValue::Int64(_) => "int64",
+                    AnnotationFieldValue::Float32(_) => "float32",
+                    AnnotationFieldValue::Float64(_) => "float64",
+                };
+                Row {
+                    name: field.name.text(self.db).to_string(),
+                    typ: vec![TypePart::Text(typ.to_string())],
+                    optional: field.optional,
+                    docs: None,
+                    annotations: vec![],
+                }
+            })
+            .collect();
+        Section {
+            title: "Fields",
+            note: None,
+            rows,
+        }
+    }
+
+    fn field_type(&mut self, owner: &str, typ: &FieldType) -> Vec<TypePart> {
+        match typ {
+            FieldType::Map(map) => {
+                let mut parts = vec![TypePart::Text("map<".to_string())];
+                parts.extend(self.field_type(owner, &map.key));
+                parts.push(TypePart::Text(", ".to_string()));
+                parts.extend(self.field_type(owner, &map.value));
+                parts.push(TypePart::Text(">".to_string()));
+                parts
+            }
+            FieldType::Ident(token) => {
+                let name = token.text(self.db);
+                match self.symbols.resolve_type(self.db, name) {
+                    Some(symbol) => {
+                        let page = symbol.name().text(self.db).to_string();
+                        self.used_by
+                            .entry(page.clone())
+                            .or_default()
+                            .insert(owner.to_string());
+                        vec![TypePart::Link(link(&self.files, &page))]
+                    }
+                    None => vec![TypePart::Text(name.to_string())],
+                }
+            }
+            typ => vec![TypePart::Text(typ.token().text(self.db).to_string())],
+        }
+    }
+
+    // Annotations as they're written, like `@pack(index = 1)`. Annotations
+    // with a declaration are used by `owner`.
+    fn annotations(&mut self, owner: &str, annotations: &[Annotation]) -> Vec<String> {
+        annotations
+            .iter()
+            .map(|annotation| {
+                let name = annotation.name.text(self.db);
+                if let Some(Symbol::Annotation(_)) = self.symbols.get(name) {
+                    self.used_by
+                        .entry(name.to_string())
+                        .or_default()
+                        .insert(owner.to_string());
+                }
+                if annotation.args.is_empty() {
+                    return format!("@{name}");
+                }
+                let args: Vec<_> = annotation
+                    .args
+                    .iter()
+                    .map(|arg| {
+                        let key = arg.key.text(self.db);
+                        // Arguments without a value are written as they were
+                        if arg.value.token() == arg.key {
+                            return key.to_string();
+                        }
+                        let value = arg.value.token().text(self.db);
+                        format!("{key} = {value}")
+                    })
+                    .collect();
+                format!("@{name}({})", args.join(", "))
+            })
+            .collect()
+    }
+
+    fn docs(&self, docs: &DocComment) -> Option<String> {
+        docs.text(self.db)
+    }
+}
+
+// Pages are named after their declaration, but names that only differ in case
+// get a number from the second on, as they would overwrite each other on file
+// systems that ignore case
+fn file_names(symbols: &SymbolTable) -> BTreeMap<String, String> {
+    let mut files = BTreeMap::new();
+    let mut taken: BTreeMap<String, usize> = BTreeMap::new();
+    for (name, symbol) in symbols.iter() {
+        if let Symbol::Instance(_) = symbol {
+            continue;
+        }
+        let count = taken.entry(name.to_lowercase()).or_default();
+        *count += 1;
+        let file = match *count {
+            1 => name.to_string(),
+            count => format!("{name}-{count}"),
+        };
+        files.insert(name.to_string(), file);
+    }
+    files
+}
+
+fn link(files: &BTreeMap<String, String>, name: &str) -> Link {
+    Link {
+        name: name.to_string(),
+        file: files.get(name).cloned().unwrap_or_else(|| name.to_string()),
+    }
+}
+
+fn type_text(typ: &[TypePart]) -> String {
+    typ.iter()
+        .map(|part| match part {
+            TypePart::Text(text) => text.as_str(),
+            TypePart::Link(link) => link.name.as_str(),
+        })
+        .collect()
+}
+
+// One entry per page, field and annotation, so a search for any of them finds
+// the page
+fn search_index(pages: &[Page], extension: &str) -> serde_json::Value {
+    let mut entries = vec![];
+    for page in pages {
+        let url = format!("types/{}.{extension}", page.file);
+        entries.push(json!({
+            "name": page.name,
+            "kind": page.kind,
+            "package": page.package,
+            "url": url,
+            "text": page.docs.clone().unwrap_or_default(),
+        }));
+        for row in page.sections.iter().flat_map(|section| &section.rows) {
+            if row.name.is_empty() {
+                continue;
+            }
+            let text = [
+                row.docs.clone().unwrap_or_default(),
+                row.annotations.join(" "),
+            ];
+            entries.push(json!({
+                "name": format!("{}.{}", page.name, row.name),
+                "kind": "field",
+                "package": page.package,
+                "url": format!("{url}#{}", row.name),
+                "type": type_text(&row.typ),
+                "text": text.join(" ").trim(),
+            }));
+        }
+    }
+    json!(entries)
+}
+
+mod html {
+    use super::*;
+
+    pub const STYLE: &str = "\
+body { margin: 0; font-family: -apple-system, BlinkMacSystemFont, \"Segoe UI\", Helvetica, Arial, sans-serif; color: #27262b; line-height: 1.5; }
+a { color: #7253ed; text-decoration: none; }
+a:hover { text-decoration: underline; }
+code { font-family: \"SFMono-Regular\", Menlo, Consolas, monospace; font-size: 0.9em; background: #f5f6fa; padding: 0.1em 0.3em; border-radius: 4px; }
+.side { position: fixed; top: 0; bottom: 0; width: 16rem; overflow-y: auto; padding: 1.5rem; background: #f5f6fa; border-right: 1px solid #eeebee; box-sizing: border-box; }
+.side h1 { font-size: 1.25rem; margin-top: 0; }
+.side ul { list-style: none; padding-left: 0; }
+.side h2 { font-size: 0.75rem; text-transform: uppercase; color: #5c5962; }
+.main { margin-left: 16rem; padding: 1.5rem 3rem; max-width: 50rem; }
+.kind { color: #5c5962; font-weight: normal; }
+.annotation { color: #5c5962; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.5rem; border-bottom: 1px solid #eeebee; vertical-align: top; }
+#search { width: 100%; padding: 0.4rem; box-sizing: border-box; border: 1px solid #eeebee; border-radius: 4px; }
+#results { list-style: none; padding-left: 0; }
+";
+
+    // Result links are relative to the root, where the script is, rather than
+    // to the page that searches
+    const SEARCH: &str = "\
+const HAML_ROOT = document.currentScript.src;
+
+function search(query) {
+  const results = document.getElementById('results');
+  results.innerHTML = '';
+  query = query.trim().toLowerCase();
+  if (!query) return;
+  for (const entry of HAML_SEARCH_INDEX) {
+    const haystack = (entry.name + ' ' + entry.text).toLowerCase();
+    if (!haystack.includes(query)) continue;
+    const item = document.createElement('li');
+    const link = document.createElement('a');
+    link.href = new URL(entry.url, HAML_ROOT);
+    link.textContent = entry.name;
+    item.appendChild(link);
+    item.appendChild(document.createTextNode(' ' + entry.kind));
+    results.appendChild(item);
+  }
+}
+";
+
+    // The index is a script rather than a fetched file so search works when
+    // the pages are opened straight from disk
+    pub fn search_script(index: &serde_json::Value) -> String {
+        format!(
+            "const HAML_SEARCH_INDEX = {};\n\n{SEARCH}",
+            serde_json::to_string(index).unwrap_or_default()
+        )
+    }
+
+    pub fn index(pages: &[Page]) -> String {
+        let mut body = String::from("<h1>Reference</h1>\n");
+        for (package, pages) in by_package(pages) {
+            let _ = writeln!(body, "<h2>{}</h2>\n<table>", escape(package));
+            for page in pages {
+                let summary = page
+                    .docs
+                    .as_deref()
+                    .and_then(|docs| docs.lines().next())
+                    .unwrap_or("");
+                let _ = writeln!(
+                    body,
+                    "<tr><td><span class=\"kind\">{}</span> <a href=\"types/{}.html\">{}</a></td><td>{}</td></tr>",
+                    page.kind,
+                    escape(&page.file),
+                    escape(&page.name),
+                    escape(summary)
+                );
+            }
+            body.push_str("</table>\n");
+        }
+        layout("Reference", &body, pages, "")
+    }
+
+    pub fn page(page: &Page, pages: &[Page]) -> String {
+        let mut body = String::new();
+        let _ = writeln!(
+            body,
+            "<h1><span class=\"kind\">{}</span> {}</h1>",
+            page.kind,
+            escape(&page.name)
+        );
+        let _ = writeln!(
+            body,
+            "<p>Package <code>{}</code></p>",
+            escape(&page.package)
+        );
+        for annotation in &page.annotations {
+            let _ = writeln!(
+                body,
+                "<p class=\"annotation\"><code>{}</code></p>",
+                escape(annotation)
+            );
+        }
+        if let Some(docs) = &page.docs {
+            body.push_str(&paragraphs(docs));
+        }
+
+        for section in &page.sections {
+            let _ = writeln!(body, "<h2>{}</h2>", section.title);
+            if let Some(note) = &section.note {
+                body.push_str(&paragraphs(note));
+            }
+            if section.rows.is_empty() {
+                body.push_str("<p>None</p>\n");
+                continue;
+            }
+            body.push_str("<table>\n<tr><th>Name</th><th>Type</th><th>Description</th></tr>\n");
+            for row in &section.rows {
+                let name = escape(&row.name);
+                let optional = if row.optional { " (optional)" } else { "" };
+                let mut description = String::new();
+                for annotation in &row.annotations {
+                    let _ = write!(description, "<code>{}</code> ", escape(annotation));
+                }
+                if let Some(docs) = &row.docs {
+                    description.push_str(&escape(docs));
+                }
+                let _ = writeln!(
+                    body,
+                    "<tr id=\"{name}\"><td><code>{name}</code>{optional}</td><td><code>{}</code></td><td>{}</td></tr>",
+                    typ(&row.typ),
+                    description.trim_end()
+                );
+            }
+            body.push_str("</table>\n");
+        }
+
+        body.push_str("<h2>Used by</h2>\n");
+        if page.used_by.is_empty() {
+            body.push_str("<p>Nothing</p>\n");
+        } else {
+            body.push_str("<ul>\n");
+            for user in &page.used_by {
+                let _ = writeln!(
+                    body,
+                    "<li><a href=\"{}.html\">{}</a></li>",
+                    escape(&user.file),
+                    escape(&user.name)
+                );
+            }
+            body.push_str("</ul>\n");
+        }
+        layout(&page.name, &body, pages, "../")
+    }
+
+    // `root` is the way from the page to the root of the output, where the
+    // index, stylesheet and search are
+    fn layout(title: &str, body: &str, pages: &[Page], root: &str) -> String {
+        let mut nav = String::new();
+        for (package, pages) in by_package(pages) {
+            let _ = writeln!(nav, "<h2>{}</h2>\n<ul>", escape(package));
+            for page in pages {
+                let _ = writeln!(
+                    nav,
+                    "<li><a href=\"{root}types/{}.html\">{}</a></li>",
+                    escape(&page.file),
+                    escape(&page.name)
+                );
+            }
+            nav.push_str("</ul>\n");
+        }
+        format!(
+            "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{}</title>
+<link rel=\"stylesheet\" href=\"{root}style.css\">
+<script src=\"{root}search.js\"></script>
+</head>
+<body>
+<nav class=\"side\">
+<h1><a href=\"{root}index.html\">Reference</a></h1>
+<input id=\"search\" type=\"search\" placeholder=\"Search\" oninput=\"search(this.value)\">
+<ul id=\"results\"></ul>
+{nav}</nav>
+<main class=\"main\">
+{body}</main>
+</body>
+</html>
+",
+            escape(title)
+        )
+    }
+
+    fn typ(parts: &[TypePart]) -> String {
+        parts
+            .iter()
+            .map(|part| match part {
+                TypePart::Text(text) => escape(text),
+                TypePart::Link(link) => format!(
+                    "<a href=\"{}.html\">{}</a>",
+                    escape(&link.file),
+                    escape(&link.name)
+                ),
+            })
+            .collect()
+    }
+
+    fn paragraphs(text: &str) -> String {
+        text.split("\n\n")
+            .map(|paragraph| format!("<p>{}</p>\n", escape(paragraph.trim())))
+            .collect()
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+}
+
+mod markdown {
+    use super::*;
+
+    pub fn index(pages: &[Page]) -> String {
+        let mut text = String::from("# Reference\n");
+        for (package, pages) in by_package(pages) {
+            let _ = writeln!(text, "\n## {package}\n");
+            for page in pages {
+                let summary = page
+                    .docs
+                    .as_deref()
+                    .and_then(|docs| docs.lines().next())
+                    .map(|line| format!(": {line}"))
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    text,
+                    "- {} [{}](types/{}.md){summary}",
+                    page.kind, page.name, page.file
+                );
+            }
+        }
+        text
+    }
+
+    pub fn page(page: &Page) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "# {} `{}`\n", page.kind, page.name);
+        let _ = writeln!(text, "Package `{}`\n", page.package);
+        for annotation in &page.annotations {
+            let _ = writeln!(text, "`{annotation}`\n");
+        }
+        if let Some(docs) = &page.docs {
+            let _ = writeln!(text, "{docs}\n");
+        }
+
+        for section in &page.sections {
+            let _ = writeln!(text, "## {}\n", section.title);
+            if let Some(note) = &section.note {
+                let _ = writeln!(text, "{note}\n");
+            }
+            if section.rows.is_empty() {
+                text.push_str("None\n\n");
+                continue;
+            }
+            text.push_str("| Name | Type | Description |\n| --- | --- | --- |\n");
+            for row in &section.rows {
+                let optional = if row.optional { " (optional)" } else { "" };
+                let mut description: Vec<_> = row
+                    .annotations
+                    .iter()
+                    .map(|annotation| format!("`{annotation}`"))
+                    .collect();
+                if let Some(docs) = &row.docs {
+                    description.push(docs.replace('\n', " "));
+                }
+                let _ = writeln!(
+                    text,
+                    "| `{}`{optional} | {} | {} |",
+                    row.name,
+                    typ(&row.typ),
+                    description.join(" ").replace('|', "\\|")
+                );
+            }
+            text.push('\n');
+        }
+
+        text.push_str("## Used by\n\n");
+        if page.used_by.is_empty() {
+            text.push_str("Nothing\n");
+        }
+        for user in &page.used_by {
+            let _ = writeln!(text, "- [{}]({}.md)", user.name, user.file);
+        }
+        text
+    }
+
+    // Markdown can't link inside code spans, so the text between links is
+    // its own span
+    fn typ(parts: &[TypePart]) -> String {
+        let mut text = String::new();
+        let mut code = String::new();
+        for part in parts {
+            match part {
+                TypePart::Text(part) => code.push_str(part),
+                TypePart::Link(link) => {
+                    if !code.is_empty() {
+                        let _ = write!(text, "`{}`", std::mem::take(&mut code));
+                    }
+                    let _ = write!(text, "[`{}`]({}.md)", link.name, link.file);
+                }
+            }
+        }
+        if !code.is_empty() {
+            let _ = write!(text, "`{code}`");
+        }
+        text.replace('|', "\\|")
+    }
+}
+
+// Pages grouped by package, in order
+fn by_package(pages: &[Page]) -> BTreeMap<&str, Vec<&Page>> {
+    let mut packages: BTreeMap<&str, Vec<&Page>> = BTreeMap::new();
+    for page in pages {
+        packages.entry(&page.package).or_default().push(page);
+    }
+    packages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols;
+
+    const SPEC: &str = r#"
+        package api.spec;
+
+        annotation pack { index: uint32, }
+
+        /// A folder of files
+        struct Folder {
+            /// The unique ID
+            @pack(index = 1)
+            id: uint32;
+            parent?: Folder;
+            files: map<string, File>;
+        }
+
+        struct File { name: string; }
+    "#;
+
+    fn doc(format: Format) -> BTreeMap<String, String> {
+        let (db, symbols) = symbols(SPEC);
+        generate(&db, &symbols, format)
+    }
+
+    #[test]
+    fn test_markdown_pages_link_types_and_users() {
+        let files = doc(Format::Markdown);
+        let folder = &files["types/Folder.md"];
+        assert!(
+            folder.starts_with("# struct `Folder`\n\nPackage `api.spec`\n\nA folder of files\n")
+        );
+        assert!(folder.contains("| `id` | `uint32` | `@pack(index = 1)` The unique ID |"));
+        assert!(folder.contains("| `parent` (optional) | [`Folder`](Folder.md) |  |"));
+        assert!(folder.contains("| `files` | `map<string, `[`File`](File.md)`>` |  |"));
+        assert!(files["types/File.md"].ends_with("## Used by\n\n- [Folder](Folder.md)\n"));
+        assert!(files["types/pack.md"].ends_with("## Used by\n\n- [Folder](Folder.md)\n"));
+        assert!(
+            files["index.md"].contains("- struct [Folder](types/Folder.md): A folder of files\n")
+        );
+    }
+
+    #[test]
+    fn test_html_escapes_and_links_types() {
+        let files = doc(Format::Html);
+        let folder = &files["types/Folder.html"];
+        assert!(folder.contains("<code>map&lt;string, <a href=\"File.html\">File</a>&gt;</code>"));
+        assert!(folder.contains("<tr id=\"id\">"));
+        assert!(files.contains_key("style.css"));
+        assert!(files["search.js"].starts_with("const HAML_SEARCH_INDEX = ["));
+        assert!(folder.contains("<link rel=\"stylesheet\" href=\"../style.css\">"));
+        assert!(folder.contains("<li><a href=\"../types/File.html\">File</a></li>"));
+        assert!(files["index.html"].contains("<a href=\"types/Folder.html\">Folder</a>"));
+    }
+
+    #[test]
+    fn test_pages_dont_overwrite_the_index_or_each_other() {
+        let (db, symbols) = symbols(
+            "struct index { name: string; }
+             struct Folder { index: index; other: folder; }
+             struct folder { name: string; }",
+        );
+        let files = generate(&db, &symbols, Format::Markdown);
+        assert!(files["index.md"].starts_with("# Reference\n"));
+        assert!(files["types/index.md"].starts_with("# struct `index`\n"));
+        assert!(files["types/Folder.md"].starts_with("# struct `Folder`\n"));
+        assert!(files["types/folder-2.md"].starts_with("# struct `folder`\n"));
+        assert!(files["types/Folder.md"].contains("| `other` | [`folder`](folder-2.md) |  |"));
+        assert!(files["types/index.md"].ends_with("- [Folder](Folder.md)\n"));
+    }
+
+    #[test]
+    fn test_search_index_covers_pages_and_fields() {
+        let files = doc(Format::Html);
+        let index: serde_json::Value = serde_json::from_str(&files["search.json"]).unwrap();
+        let names: Vec<_> = index
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "File",
+                "File.name",
+                "Folder",
+                "Folder.id",
+                "Folder.parent",
+                "Folder.files",
+                "pack",
+                "pack.index",
+            ]
+        );
+        assert_eq!(index[1]["url"], "types/File.html#name");
+    }
+}
@@ -0,0 +1,352 @@
+//! JSON Schema (draft 2020-12) for the structs and constructors of a spec.
+
+use serde_json::{json, Map, Value as Json};
+
+use core::ast::node::{
+    find_annotation, AliasDecl, Annotation, BlockDecl, DocComment, FieldDecl, FieldType, MapDecl,
+    UnionDecl,
+};
+use core::diagnostics::Diagnostic;
+use core::gen::{self, Model, OutputFile};
+use core::sema::{type_name, Symbol, SymbolTable};
+use core::Db;
+
+pub const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+const DEFS: &str = "#/$defs/";
+
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Make the document validate this type, rather than only defining it
+    pub root: Option<String>,
+}
+
+/// Build a schema with a definition in `$defs` for every struct and
+/// constructor in `symbols`. Constructors are defined under their type name,
+/// so `constructor endpoint` becomes `Endpoint`.
+pub fn generate(db: &dyn Db, symbols: &SymbolTable, options: &Options) -> Result<Json, String> {
+    let defs = definitions(db, symbols, DEFS);
+    let mut schema = Map::new();
+    schema.insert("$schema".to_string(), json!(DRAFT));
+    if let Some(root) = &options.root {
+        if !defs.contains_key(root) {
+            return Err(format!(
+                "cannot find a struct or constructor named `{root}`"
+            ));
+        }
+        schema.insert("$ref".to_string(), json!(format!("{DEFS}{root}")));
+    }
+    schema.insert("$defs".to_string(), Json::Object(defs));
+    Ok(Json::Object(schema))
+}
+
+/// A schema for every struct and constructor in `symbols`, by name. Schemas
+/// refer to each other with `$ref`s made of `prefix` and the name, so they can
+/// be put somewhere other than `$defs`.
+pub fn definitions(db: &dyn Db, symbols: &SymbolTable, prefix: &str) -> Map<String, Json> {
+    let generator = Generator {
+        db,
+        symbols,
+        prefix,
+    };
+    let mut defs = Map::new();
+    for (name, symbol) in symbols.iter() {
+        match symbol {
+            Symbol::Struct(decl) => {
+                let schema = generator.block(name, &decl.content);
+                let schema = generator.describe(schema, &decl.docs, &decl.annotations);
+                defs.insert(name.to_string(), schema);
+            }
+            Symbol::Constructor(decl) => {
+                let name = type_name(name);
+                let schema = generator.block(&name, &decl.content);
+                let schema = generator.describe(schema, &decl.docs, &decl.annotations);
+                defs.insert(name, schema);
+            }
+            _ => {}
+        }
+    }
+    defs
+}
+
+struct Generator<'db> {
+    db: &'db dyn Db,
+    symbols: &'db SymbolTable,
+    prefix: &'db str,
+}
+
+impl<'db> Generator<'db> {
+    fn definition(&self, name: &str) -> Json {
+        json!({ "$ref": format!("{}{name}", self.prefix) })
+    }
+
+    // Doc comments become descriptions, and `@deprecated` marks the schema as
+    // deprecated
+    fn describe(&self, mut schema: Json, docs: &DocComment, annotations: &[Annotation]) -> Json {
+        let Json::Object(object) = &mut schema else {
+            return schema;
+        };
+        if let Some(text) = docs.text(self.db) {
+            object.insert("description".to_string(), json!(text));
+        }
+        if find_annotation(self.db, annotations, "deprecated").is_some() {
+            object.insert("deprecated".to_string(), json!(true));
+        }
+        schema
+    }
+
+    fn block(&self, name: &str, block: &BlockDecl) -> Json {
+        let mut schema = match block {
+            BlockDecl::FieldSet(fields) => self.fields(&fields.fields),
+            BlockDecl::Repeatable(fields) => json!({
+                "type": "object",
+                "additionalProperties": self.fields(&fields.fields),
+            }),
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => self.map(map),
+            BlockDecl::Union(union) => self.union(union),
+        };
+        if let Json::Object(schema) = &mut schema {
+            schema.insert("title".to_string(), json!(name));
+        }
+        schema
+    }
+
+    // Unknown fields are an error in haml, so they are in the schema too
+    fn fields(&self, fields: &[FieldDecl]) -> Json {
+        let mut properties = Map::new();
+        let mut required = vec![];
+        for field in fields {
+            let name = field.name.text(self.db);
+            let schema = self.field_type(&field.typ);
+            let schema = self.describe(schema, &field.docs, &field.annotations);
+            properties.insert(name.to_string(), schema);
+            if !field.optional {
+                required.push(json!(name));
+            }
+        }
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+
+    fn map(&self, map: &MapDecl) -> Json {
+        let mut schema = json!({
+            "type": "object",
+            "additionalProperties": self.field_type(&map.value),
+        });
+        let pattern = match map.key {
+            FieldType::Uint32(_) | FieldType::Uint64(_) => Some("^[0-9]+$"),
+            FieldType::Int32(_) | FieldType::Int64(_) => Some("^-?[0-9]+$"),
+            _ => None,
+        };
+        if let Some(pattern) = pattern {
+            schema["propertyNames"] = json!({ "pattern": pattern });
+        }
+        schema
+    }
+
+    // A variant with a discriminator is the variant's fields plus the
+    // discriminator, so its fields are copied rather than referenced
+    fn union(&self, union: &UnionDecl) -> Json {
+        let mut variants = vec![];
+        for variant in &union.variants.fields {
+            let name = variant.name.text(self.db);
+            let schema = match (union.discriminator, self.variant_fields(variant)) {
+                (Some(discriminator), Some(fields)) => {
+                    let tag = discriminator.text(self.db);
+                    let mut schema = self.fields(fields);
+                    schema["properties"][tag] = json!({ "const": name });
+                    if let Json::Array(required) = &mut schema["required"] {
+                        required.insert(0, json!(tag));
+                    }
+                    schema
+                }
+                _ => json!({
+                    "type": "object",
+                    "properties": { name: self.field_type(&variant.typ) },
+                    "required": [name],
+                    "additionalProperties": false,
+                }),
+            };
+            variants.push(schema);
+        }
+        json!({ "oneOf": variants })
+    }
+
+    fn variant_fields(&self, variant: &FieldDecl) -> Option<&'db [FieldDecl]> {
+        let FieldType::Ident(typ) = &variant.typ else {
+            return None;
+        };
+        match self.symbols.resolve_type(self.db, typ.text(self.db)) {
+            Some(Symbol::Struct(decl)) => match &decl.content {
+                BlockDecl::FieldSet(fields) => Some(&fields.fields),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn field_type(&self, typ: &FieldType) -> Json {
+        match typ {
+            FieldType::String(_) => json!({ "type": "string" }),
+            FieldType::Uint32(_) => integer(0, u32::MAX),
+            FieldType::Uint64(_) => integer(0, u64::MAX),
+            FieldType::Int32(_) => integer(i32::MIN, i32::MAX),
+            FieldType::Int64(_) => integer(i64::MIN, i64::MAX),
+            FieldType::Float32(_) => json!({
+                "type": "number",
+                "minimum": f32::MIN,
+                "maximum": f32::MAX,
+            }),
+            FieldType::Float64(_) => json!({ "type": "number" }),
+            FieldType::Unknown(_) => json!({}),
+            FieldType::Struct(_) => {
+                let names: Vec<_> = self
+                    .symbols
+                    .iter()
+                    .filter(|(_, symbol)| matches!(symbol, Symbol::Struct(_)))
+                    .map(|(name, _)| name)
+                    .collect();
+                json!({ "type": "string", "enum": names })
+            }
+            FieldType::Map(map) => self.map(map),
+            FieldType::Ident(token) => {
+                let name = token.text(self.db);
+                match self.symbols.resolve_type(self.db, name) {
+                    Some(Symbol::Constructor(decl)) => {
+                        self.definition(&type_name(decl.name.text(self.db)))
+                    }
+                    _ => self.definition(name),
+                }
+            }
+        }
+    }
+}
+
+fn integer(minimum: impl Into<Json>, maximum: impl Into<Json>) -> Json {
+    json!({
+        "type": "integer",
+        "minimum": minimum.into(),
+        "maximum": maximum.into(),
+    })
+}
+
+/// `json-schema` in the backend registry. The `root` option is the type the
+/// schema validates.
+pub struct Backend;
+
+impl gen::Generator for Backend {
+    fn name(&self) -> &str {
+        "json-schema"
+    }
+
+    fn description(&self) -> &str {
+        "JSON Schema (draft 2020-12) for every struct and constructor"
+    }
+
+    fn generate(
+        &self,
+        db: &dyn Db,
+        model: &Model,
+        options: &gen::Options,
+    ) -> Result<Vec<OutputFile>, Vec<Diagnostic>> {
+        gen::check_options(options, &["root"])?;
+        let options = Options {
+            root: options.get("root").cloned(),
+        };
+        let schema = generate(db, &model.symbols, &options).map_err(gen::option_error)?;
+        let text = serde_json::to_string_pretty(&schema).unwrap_or_default() + "\n";
+        Ok(vec![OutputFile::new("schema.json", text)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols;
+
+    fn schema(text: &str, options: &Options) -> Result<Json, String> {
+        let (db, symbols) = symbols(text);
+        generate(&db, &symbols, options)
+    }
+
+    #[test]
+    fn test_struct_gives_object_with_required_fields() {
+        let schema = schema(
+            "struct Folder { id: uint32; name?: string; tags: map<string, float64>; }",
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(schema["$schema"], json!(DRAFT));
+        assert_eq!(
+            schema["$defs"]["Folder"],
+            json!({
+                "title": "Folder",
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer", "minimum": 0, "maximum": 4294967295u32 },
+                    "name": { "type": "string" },
+                    "tags": {
+                        "type": "object",
+                        "additionalProperties": { "type": "number" },
+                    },
+                },
+                "required": ["id", "tags"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_constructors_are_referred_to_by_type_name() {
+        let schema = schema(
+            "constructor endpoint { name: string; }
+             constructor api { endpoints: map<string, Endpoint>; }",
+            &Options {
+                root: Some("Api".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(schema["$ref"], json!("#/$defs/Api"));
+        assert_eq!(
+            schema["$defs"]["Api"]["properties"]["endpoints"]["additionalProperties"],
+            json!({ "$ref": "#/$defs/Endpoint" })
+        );
+    }
+
+    #[test]
+    fn test_union_with_discriminator_gives_one_of() {
+        let schema = schema(
+            "struct Link { url: string; }
+             struct Item { union on kind { link: Link; } }",
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            schema["$defs"]["Item"]["oneOf"],
+            json!([{
+                "type": "object",
+                "properties": {
+                    "kind": { "const": "link" },
+                    "url": { "type": "string" },
+                },
+                "required": ["kind", "url"],
+                "additionalProperties": false,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_unknown_root_is_an_error() {
+        let result = schema(
+            "struct Folder {}",
+            &Options {
+                root: Some("Missing".to_string()),
+            },
+        );
+        assert!(result.is_err());
+    }
+}
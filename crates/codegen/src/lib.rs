@@ -0,0 +1,116 @@
+//! Generators that turn a checked haml spec into other formats.
+
+pub mod build;
+pub mod doc;
+pub mod jsonschema;
+pub mod openapi;
+pub mod plugin;
+pub mod proto;
+pub mod rust;
+pub mod typescript;
+
+use std::collections::BTreeMap;
+
+use core::ast::node::{Node, ValueExpr};
+use core::gen::{OutputFile, Registry};
+use core::queries::{parse_file, SourceFile};
+use core::Db;
+
+/// A registry of the built-in backends, for the CLI to select from. Crates
+/// with backends of their own can register them on top.
+pub fn registry() -> Registry {
+    let mut registry = Registry::default();
+    registry.register(jsonschema::Backend);
+    registry.register(openapi::Backend);
+    registry.register(proto::Backend);
+    registry.register(rust::Backend);
+    registry.register(typescript::Backend);
+    registry
+}
+
+/// The segments of the package a file declares, like `["api", "spec"]` for
+/// `package api.spec;`. Files without a package are named after the file, up
+/// to its first `.`.
+pub(crate) fn module_name(db: &dyn Db, file: SourceFile) -> Vec<String> {
+    let package = parse_file(db, file).and_then(|ast| {
+        ast.nodes.into_iter().find_map(|node| match node {
+            Node::PackageStmt(stmt) => Some(stmt.segments),
+            _ => None,
+        })
+    });
+    if let Some(segments) = package {
+        return segments
+            .iter()
+            .map(|segment| segment.text(db).to_string())
+            .collect();
+    }
+    let path = file.path(db);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    vec![name.split('.').next().unwrap_or(name).to_string()]
+}
+
+/// The text of a literal, without quotes if it's a string
+pub(crate) fn value_text(db: &dyn Db, value: &ValueExpr) -> String {
+    match value {
+        ValueExpr::String(token) => token.unquoted(db).to_string(),
+        value => value.token().text(db).to_string(),
+    }
+}
+
+/// Files by their path, as output files for the backend registry
+pub(crate) fn output_files(files: BTreeMap<String, String>) -> Vec<OutputFile> {
+    files
+        .into_iter()
+        .map(|(path, contents)| OutputFile::new(path, contents))
+        .collect()
+}
+
+/// A spec file named `spec.haml` with the text `src`, for backend tests
+#[cfg(test)]
+pub(crate) fn spec(db: &core::db::Database, src: &str) -> SourceFile {
+    SourceFile::new(db, std::path::PathBuf::from("spec.haml"), src.to_string())
+}
+
+/// The symbols of a spec with the text `src`, with the database they're in
+#[cfg(test)]
+pub(crate) fn symbols(src: &str) -> (core::db::Database, core::sema::SymbolTable) {
+    let db = core::db::Database::default();
+    let file = spec(&db, src);
+    let symbols = core::queries::build_symbol_table(&db, file).unwrap();
+    (db, symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::db::Database;
+    use core::gen::{Model, Options};
+
+    #[test]
+    fn test_registry_runs_built_in_backends() {
+        let registry = registry();
+        let names: Vec<_> = registry.iter().map(|generator| generator.name()).collect();
+        assert_eq!(
+            names,
+            ["json-schema", "openapi", "proto", "rust", "typescript"]
+        );
+
+        let db = Database::default();
+        let text = "package api.spec; struct Folder { name: string; }";
+        let model = Model::new(&db, spec(&db, text)).unwrap();
+        let rust = registry.get("rust").unwrap();
+        let files = rust.generate(&db, &model, &Options::new()).unwrap();
+        assert_eq!(files[0].path, "api_spec.rs");
+
+        let json_schema = registry.get("json-schema").unwrap();
+        let options = Options::from([("root".to_string(), "Folder".to_string())]);
+        assert!(json_schema.generate(&db, &model, &options).is_ok());
+        let options = Options::from([("root".to_string(), "Missing".to_string())]);
+        assert!(json_schema.generate(&db, &model, &options).is_err());
+        let options = Options::from([("depth".to_string(), "1".to_string())]);
+        assert!(json_schema.generate(&db, &model, &options).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! OpenAPI 3.1 documents built from instances of an API constructor, like
+//! `api FolderApi { ... }` in `examples/api.haml.spec`.
+
+use std::collections::BTreeSet;
+use std::fs;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value as Json};
+
+use core::ast::node::{find_annotation, Annotation, InstanceDecl, ValueExpr};
+use core::diagnostics::Diagnostic;
+use core::gen::{self, option_error, Model, Options, OutputFile};
+use core::sema::{Instance, Symbol, SymbolTable, Value};
+use core::Db;
+
+use crate::jsonschema;
+
+pub const VERSION: &str = "3.1.0";
+
+const SCHEMAS: &str = "#/components/schemas/";
+
+/// The names of the constructors and fields that describe an API. The
+/// defaults match the `api` and `endpoint` constructors of the example spec.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mapping {
+    /// The constructor of the instance that becomes the document
+    pub api: String,
+    /// The field of the API with the document's title
+    pub title: String,
+    /// The field of the API with the document's version, if it has one
+    pub version: String,
+    /// The field of the API with its endpoints by path. Each path maps to an
+    /// endpoint, or to an object of endpoints by HTTP method.
+    pub endpoints: String,
+    /// The field of an endpoint with its HTTP method, for paths that map
+    /// straight to an endpoint. Endpoints without one use `get`.
+    pub method: String,
+    /// The field of an endpoint with its operation ID
+    pub operation_id: String,
+    /// The field of an endpoint with the struct of its request body
+    pub request: String,
+    /// The field of an endpoint with the struct of its response body
+    pub response: String,
+}
+
+impl Default for Mapping {
+    fn default() -> Mapping {
+        Mapping {
+            api: "api".to_string(),
+            title: "name".to_string(),
+            version: "version".to_string(),
+            endpoints: "endpoints".to_string(),
+            method: "method".to_string(),
+            operation_id: "name".to_string(),
+            request: "request".to_string(),
+            response: "response".to_string(),
+        }
+    }
+}
+
+/// Build a document from the instance named `api`, or from the only instance
+/// of the API constructor if there's no name. The structs that requests and
+/// responses use, and the structs they use in turn, become component schemas.
+pub fn generate(
+    db: &dyn Db,
+    symbols: &SymbolTable,
+    instances: &[Instance],
+    api: Option<&str>,
+    mapping: &Mapping,
+) -> Result<Json, String> {
+    let generator = Generator {
+        db,
+        symbols,
+        instances,
+        mapping,
+    };
+    generator.document(generator.api(api)?)
+}
+
+struct Generator<'a> {
+    db: &'a dyn Db,
+    symbols: &'a SymbolTable,
+    instances: &'a [Instance],
+    mapping: &'a Mapping,
+}
+
+impl<'a> Generator<'a> {
+    fn api(&self, name: Option<&str>) -> Result<&'a Instance, String> {
+        let constructor = &self.mapping.api;
+        if let Some(name) = name {
+            return self
+                .instances
+                .iter()
+                .find(|instance| instance.name == name && &instance.constructor == constructor)
+                .ok_or_else(|| {
+                    format!("cannot find an instance of `{constructor}` named `{name}`")
+                });
+        }
+        let mut apis = self
+            .instances
+            .iter()
+            .filter(|instance| &instance.constructor == constructor);
+        match (apis.next(), apis.next()) {
+            (Some(api), None) => Ok(api),
+            (None, _) => Err(format!("cannot find an instance of `{constructor}`")),
+            (Some(_), Some(_)) => Err(format!(
+                "there is more than one instance of `{constructor}`, choose one with `--opt api=<name>`"
+            )),
+        }
+    }
+
+    fn document(&self, api: &Instance) -> Result<Json, String> {
+        let mut info = Map::new();
+        let title = field(&api.value, &self.mapping.title).and_then(string);
+        info.insert("title".to_string(), json!(title.unwrap_or(&api.name)));
+        let version = field(&api.value, &self.mapping.version).and_then(string);
+        info.insert("version".to_string(), json!(version.unwrap_or("0.0.0")));
+        self.describe(&mut info, &api.name);
+        // Only operations can be deprecated
+        info.remove("deprecated");
+
+        let mut paths = Map::new();
+        let mut used = BTreeSet::new();
+        let endpoints = match field(&api.value, &self.mapping.endpoints) {
+            Some(Value::Object(endpoints)) => endpoints.as_slice(),
+            _ => &[],
+        };
+        for (path, value) in endpoints {
+            let mut operations = Map::new();
+            match value {
+                Value::Ref(name) => {
+                    let endpoint = self.endpoint(name)?;
+                    let method = field(&endpoint.value, &self.mapping.method)
+                        .and_then(string)
+                        .unwrap_or("get")
+                        .to_lowercase();
+                    operations.insert(method, self.operation(endpoint, &mut used));
+                }
+                Value::Object(methods) => {
+                    for (method, value) in methods {
+                        let Value::Ref(name) = value else {
+                            return Err(format!("`{method}` of `{path}` isn't an endpoint"));
+                        };
+                        let endpoint = self.endpoint(name)?;
+                        operations
+                            .insert(method.to_lowercase(), self.operation(endpoint, &mut used));
+                    }
+                }
+                _ => return Err(format!("`{path}` isn't an endpoint or object of endpoints")),
+            }
+            paths.insert(path.clone(), Json::Object(operations));
+        }
+
+        Ok(json!({
+            "openapi": VERSION,
+            "info": info,
+            "paths": paths,
+            "components": { "schemas": self.schemas(used) },
+        }))
+    }
+
+    fn endpoint(&self, name: &str) -> Result<&'a Instance, String> {
+        self.instances
+            .iter()
+            .find(|instance| instance.name == name)
+            .ok_or_else(|| format!("cannot find the endpoint `{name}`"))
+    }
+
+    fn operation(&self, endpoint: &Instance, used: &mut BTreeSet<String>) -> Json {
+        let mut operation = Map::new();
+        let operation_id = field(&endpoint.value, &self.mapping.operation_id).and_then(string);
+        operation.insert(
+            "operationId".to_string(),
+            json!(operation_id.unwrap_or(&endpoint.name)),
+        );
+        self.describe(&mut operation, &endpoint.name);
+
+        if let Some(Value::Type(request)) = field(&endpoint.value, &self.mapping.request) {
+            used.insert(request.clone());
+            operation.insert(
+                "requestBody".to_string(),
+                json!({ "required": true, "content": content(request) }),
+            );
+        }
+        let mut response = json!({ "description": "OK" });
+        if let Some(Value::Type(name)) = field(&endpoint.value, &self.mapping.response) {
+            used.insert(name.clone());
+            response["content"] = content(name);
+        }
+        operation.insert("responses".to_string(), json!({ "200": response }));
+        Json::Object(operation)
+    }
+
+    // `@summary` and `@description` take the first string argument of the
+    // annotation, and doc comments are the description if there's no
+    // `@description`
+    fn describe(&self, object: &mut Map<String, Json>, instance: &str) {
+        let Some(Symbol::Instance(InstanceDecl {
+            docs, annotations, ..
+        })) = self.symbols.get(instance)
+        else {
+            return;
+        };
+        if let Some(summary) = self.annotation_text(annotations, "summary") {
+            object.insert("summary".to_string(), json!(summary));
+        }
+        let description = self
+            .annotation_text(annotations, "description")
+            .or_else(|| docs.text(self.db));
+        if let Some(description) = description {
+            object.insert("description".to_string(), json!(description));
+        }
+        if find_annotation(self.db, annotations, "deprecated").is_some() {
+            object.insert("deprecated".to_string(), json!(true));
+        }
+    }
+
+    fn annotation_text(&self, annotations: &[Annotation], name: &str) -> Option<String> {
+        let annotation = find_annotation(self.db, annotations, name)?;
+        annotation.args.iter().find_map(|arg| match &arg.value {
+            ValueExpr::String(token) => Some(token.unquoted(self.db).to_string()),
+            _ => None,
+        })
+    }
+
+    // The schemas of `used` and of every schema they refer to
+    fn schemas(&self, mut pending: BTreeSet<String>) -> Map<String, Json> {
+        let definitions = jsonschema::definitions(self.db, self.symbols, SCHEMAS);
+        let mut schemas = Map::new();
+        while let Some(name) = pending.pop_first() {
+            let Some(schema) = definitions.get(&name) else {
+                continue;
+            };
+            if schemas.contains_key(&name) {
+                continue;
+            }
+            refs(schema, &mut pending);
+            schemas.insert(name, schema.clone());
+        }
+        schemas
+    }
+}
+
+fn content(schema: &str) -> Json {
+    json!({ "application/json": { "schema": { "$ref": format!("{SCHEMAS}{schema}") } } })
+}
+
+fn field<'v>(value: &'v Value, name: &str) -> Option<&'v Value> {
+    let Value::Object(fields) = value else {
+        return None;
+    };
+    fields
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+fn string(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(value) => Some(value),
+        _ => None,
+    }
+}
+
+// Collect the names of the component schemas that `schema` refers to
+fn refs(schema: &Json, names: &mut BTreeSet<String>) {
+    match schema {
+        Json::Object(object) => {
+            for (key, value) in object {
+                match value.as_str().and_then(|value| value.strip_prefix(SCHEMAS)) {
+                    Some(name) if key == "$ref" => {
+                        names.insert(name.to_string());
+                    }
+                    _ => refs(value, names),
+                }
+            }
+        }
+        Json::Array(values) => values.iter().for_each(|value| refs(value, names)),
+        _ => {}
+    }
+}
+
+/// `openapi` in the backend registry. The `api` option names the API
+/// instance, and `mapping` a TOML file with the fields of [`Mapping`]. The
+/// fields can be given as options too, which take precedence over the file.
+pub struct Backend;
+
+impl gen::Generator for Backend {
+    fn name(&self) -> &str {
+        "openapi"
+    }
+
+    fn description(&self) -> &str {
+        "An OpenAPI 3.1 document for an instance of the API constructor"
+    }
+
+    fn generate(
+        &self,
+        db: &dyn Db,
+        model: &Model,
+        options: &Options,
+    ) -> Result<Vec<OutputFile>, Vec<Diagnostic>> {
+        let mut options = options.clone();
+        let api = options.remove("api");
+        let mut fields = match options.remove("mapping") {
+            Some(path) => read_mapping(&path).map_err(option_error)?,
+            None => Map::new(),
+        };
+        fields.extend(
+            options
+                .into_iter()
+                .map(|(key, value)| (key, Json::String(value))),
+        );
+        let mapping: Mapping = serde_json::from_value(Json::Object(fields))
+            .map_err(|err| option_error(err.to_string()))?;
+        let document = generate(
+            db,
+            &model.symbols,
+            &model.instances,
+            api.as_deref(),
+            &mapping,
+        )
+        .map_err(option_error)?;
+        let text = serde_json::to_string_pretty(&document).unwrap_or_default() + "\n";
+        Ok(vec![OutputFile::new("openapi.json", text)])
+    }
+}
+
+// The fields of a mapping file, left to be checked along with the options
+fn read_mapping(path: &str) -> Result<Map<String, Json>, String> {
+    fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| toml::from_str(&text).map_err(|err| err.to_string()))
+        .map_err(|err| format!("could not read mapping {path}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::db::Database;
+    use core::queries::{build_symbol_table, check_file, SourceFile};
+    use std::path::PathBuf;
+
+    const SPEC: &str = r#"
+        constructor api {
+            name: string;
+            endpoints: map<string, Endpoint>;
+        }
+
+        constructor endpoint {
+            name: string;
+            method?: string;
+            request: struct;
+            response: struct;
+        }
+
+        struct GetFolderRequest { id: string; }
+        struct GetFolderResponse { folder: Folder; }
+        struct Folder { name: string; }
+        struct Unused { name: string; }
+    "#;
+
+    fn openapi(text: &str, api: Option<&str>) -> Result<Json, String> {
+        let db = Database::default();
+        let text = format!("{SPEC}{text}");
+        let file = SourceFile::new(&db, PathBuf::from("api.haml"), text);
+        let instances = check_file(&db, file).unwrap();
+        let symbols = build_symbol_table(&db, file).unwrap();
+        generate(&db, &symbols, &instances, api, &Mapping::default())
+    }
+
+    #[test]
+    fn test_endpoints_become_operations() {
+        let document = openapi(
+            r#"
+            /// Get a folder by its ID
+            @summary(text = "Get folder")
+            @deprecated
+            endpoint GetFolder {
+                name: "get_folder",
+                request: GetFolderRequest,
+                response: GetFolderResponse,
+            }
+
+            api FolderApi {
+                name: "Folders",
+                endpoints: { "/folders/{id}": GetFolder },
+            }
+            "#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(document["openapi"], json!(VERSION));
+        assert_eq!(document["info"]["title"], json!("Folders"));
+        let operation = &document["paths"]["/folders/{id}"]["get"];
+        assert_eq!(operation["operationId"], json!("get_folder"));
+        assert_eq!(operation["summary"], json!("Get folder"));
+        assert_eq!(operation["description"], json!("Get a folder by its ID"));
+        assert_eq!(operation["deprecated"], json!(true));
+        assert_eq!(
+            operation["responses"]["200"]["content"]["application/json"]["schema"],
+            json!({ "$ref": "#/components/schemas/GetFolderResponse" })
+        );
+
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        let names: Vec<_> = schemas.keys().collect();
+        assert_eq!(names, ["Folder", "GetFolderRequest", "GetFolderResponse"]);
+        assert_eq!(
+            schemas["GetFolderResponse"]["properties"]["folder"],
+            json!({ "$ref": "#/components/schemas/Folder" })
+        );
+    }
+
+    #[test]
+    fn test_api_must_be_chosen_when_there_are_several() {
+        let result = openapi(
+            r#"
+            api First { name: "First", endpoints: {} }
+            api Second { name: "Second", endpoints: {} }
+            "#,
+            None,
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "there is more than one instance of `api`, choose one with `--opt api=<name>`"
+        );
+
+        let document = openapi(
+            r#"
+            api First { name: "First", endpoints: {} }
+            api Second { name: "Second", endpoints: {} }
+            "#,
+            Some("Second"),
+        )
+        .unwrap();
+        assert_eq!(document["info"]["title"], json!("Second"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Code generators that run as their own executable, like `protoc` plugins,
+//! so they can be written in any language. See `core::gen` for the protocol.
+
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use core::diagnostics::{Diagnostic, Level};
+use core::gen::{plugin_request, Model, Options, OutputFile, PluginResponse};
+use core::Db;
+
+pub struct Plugin {
+    program: PathBuf,
+}
+
+/// The files a plugin generated and the diagnostics it reported
+#[derive(Debug)]
+pub struct PluginOutput {
+    pub files: Vec<OutputFile>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl PluginOutput {
+    /// Whether the plugin reported any errors, in which case its files
+    /// shouldn't be written
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| matches!(diagnostic.level, Level::Error))
+    }
+}
+
+impl Plugin {
+    pub fn new(program: impl Into<PathBuf>) -> Plugin {
+        Plugin {
+            program: program.into(),
+        }
+    }
+
+    /// Run the plugin on `model`. Fails if the plugin can't be run, exits
+    /// unsuccessfully, or writes something that isn't a response.
+    pub fn run(
+        &self,
+        db: &dyn Db,
+        model: &Model,
+        options: &Options,
+    ) -> Result<PluginOutput, String> {
+        let program = self.program.display();
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| format!("could not run plugin `{program}`: {err}"))?;
+
+        // Write from another thread, so a plugin that writes before it has
+        // read everything can't fill its stdout and block forever
+        let request = plugin_request(db, model, options).to_string();
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let writer = thread::spawn(move || stdin.write_all(request.as_bytes()));
+        let output = child
+            .wait_with_output()
+            .map_err(|err| format!("could not run plugin `{program}`: {err}"))?;
+        // A plugin is free to exit without reading all of its input
+        let _ = writer.join();
+        if !output.status.success() {
+            return Err(format!("plugin `{program}` failed with {}", output.status));
+        }
+
+        let response: PluginResponse = serde_json::from_slice(&output.stdout)
+            .map_err(|err| format!("plugin `{program}` wrote an invalid response: {err}"))?;
+        for file in &response.files {
+            if !is_relative(&file.path) {
+                return Err(format!(
+                    "plugin `{program}` tried to write `{}`, outside of the output directory",
+                    file.path
+                ));
+            }
+        }
+        let diagnostics = response
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_diagnostic(db, model))
+            .collect();
+        Ok(PluginOutput {
+            files: response.files,
+            diagnostics,
+        })
+    }
+}
+
+// Whether a path stays inside the directory it's relative to
+fn is_relative(path: &str) -> bool {
+    let path = Path::new(path);
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        && path.components().next().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec;
+    use core::db::Database;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    // A plugin that saves the request it was given next to itself, reports a
+    // warning at `File` and writes one file
+    const PLUGIN: &str = r#"#!/bin/sh
+cat > "$0.request"
+echo '{
+  "files": [{ "path": "out/names.txt", "contents": "File,Folder" }],
+  "diagnostics": [{
+    "level": "warning",
+    "message": "v1",
+    "location": { "file": "spec.haml", "start": 39, "end": 43 },
+    "label": "first"
+  }]
+}'
+"#;
+
+    // A plugin script in the temp directory, removed along with the request
+    // it saved when dropped
+    struct Script {
+        path: PathBuf,
+    }
+
+    impl Script {
+        fn new(name: &str, text: &str) -> Script {
+            let path =
+                std::env::temp_dir().join(format!("haml-plugin-{}-{name}", std::process::id()));
+            fs::write(&path, text).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            Script { path }
+        }
+
+        fn plugin(&self) -> Plugin {
+            Plugin::new(&self.path)
+        }
+
+        fn request(&self) -> serde_json::Value {
+            let text = fs::read_to_string(self.request_path()).unwrap();
+            serde_json::from_str(&text).unwrap()
+        }
+
+        fn request_path(&self) -> PathBuf {
+            let mut path = self.path.clone().into_os_string();
+            path.push(".request");
+            path.into()
+        }
+    }
+
+    impl Drop for Script {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+            let _ = fs::remove_file(self.request_path());
+        }
+    }
+
+    fn model(db: &Database) -> Model {
+        let text = "struct Folder { name: string; } struct File {}";
+        Model::new(db, spec(db, text)).unwrap()
+    }
+
+    #[test]
+    fn test_plugin_gets_model_and_returns_files_and_diagnostics() {
+        let db = Database::default();
+        let options = Options::from([("suffix".to_string(), "!".to_string())]);
+        let script = Script::new("names", PLUGIN);
+        let output = script.plugin().run(&db, &model(&db), &options).unwrap();
+
+        let request = script.request();
+        assert_eq!(request["model"]["version"], 1);
+        let names: Vec<_> = request["model"]["declarations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|decl| decl["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["File", "Folder"]);
+        assert_eq!(request["options"]["suffix"], "!");
+
+        assert_eq!(
+            output.files,
+            [OutputFile::new("out/names.txt", "File,Folder")]
+        );
+        let diagnostic = &output.diagnostics[0];
+        assert_eq!(diagnostic.message, "v1");
+        assert_eq!(diagnostic.span_message.as_deref(), Some("first"));
+        let span = diagnostic.span.unwrap();
+        assert_eq!(span.text(&db), "File");
+        assert!(!output.has_errors());
+    }
+
+    #[test]
+    fn test_failing_and_escaping_plugins_are_errors() {
+        let db = Database::default();
+        let failing = Script::new("failing", "#!/bin/sh\nexit 3\n");
+        assert!(failing
+            .plugin()
+            .run(&db, &model(&db), &Options::new())
+            .is_err());
+
+        let escaping = Script::new(
+            "escaping",
+            "#!/bin/sh\necho '{\"files\": [{\"path\": \"../x\", \"contents\": \"\"}]}'\n",
+        );
+        let err = escaping
+            .plugin()
+            .run(&db, &model(&db), &Options::new())
+            .unwrap_err();
+        assert!(err.contains("outside of the output directory"));
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        assert!(is_relative("api/spec.py"));
+        assert!(is_relative("./spec.py"));
+        assert!(!is_relative("../spec.py"));
+        assert!(!is_relative("/etc/passwd"));
+        assert!(!is_relative(""));
+    }
+}
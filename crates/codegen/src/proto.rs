@@ -0,0 +1,486 @@
+//! proto3 messages for the structs and constructors of a spec. Field numbers
+//! come from `@pack(index = N)` annotations, with one file per haml package.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use core::ast::node::{
+    find_annotation, AliasDecl, BlockDecl, DocComment, FieldDecl, FieldType, MapDecl, UnionDecl,
+    ValueExpr,
+};
+use core::diagnostics::codes::{self, Code};
+use core::diagnostics::{Diagnostic, Level};
+use core::gen::{self, check_options, Case, Model, Options, OutputFile};
+use core::sema::{type_name, Symbol, SymbolTable};
+use core::syntax::Token;
+use core::Db;
+
+use crate::{module_name, output_files};
+
+const HEADER: &str = "// @generated by `haml gen proto`. Do not edit.\n";
+
+// Field numbers reserved by protobuf itself
+const RESERVED: std::ops::RangeInclusive<u32> = 19000..=19999;
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+/// Generate a `.proto` file for each package that declares a struct or
+/// constructor, by its path, like `api/spec.proto` for `package api.spec;`.
+/// Map aliases and repeatable blocks have no proto equivalent, so fields that
+/// use them get the underlying `map<>` instead.
+pub fn generate(
+    db: &dyn Db,
+    symbols: &SymbolTable,
+) -> Result<BTreeMap<String, String>, Vec<Diagnostic>> {
+    let mut files: BTreeMap<String, File> = BTreeMap::new();
+    let mut diagnostics = vec![];
+    for (name, symbol) in symbols.iter() {
+        let (name, docs, content) = match symbol {
+            Symbol::Struct(decl) => (name.to_string(), &decl.docs, &decl.content),
+            Symbol::Constructor(decl) => (type_name(name), &decl.docs, &decl.content),
+            _ => continue,
+        };
+        let package = module_name(db, symbol.name().span.file);
+        let path = format!("{}.proto", package.join("/"));
+        let mut generator = Generator {
+            db,
+            symbols,
+            file: files.remove(&path).unwrap_or_default(),
+            package,
+            diagnostics: &mut diagnostics,
+        };
+        generator.block(&name, docs, content);
+        let file = generator.file;
+        files.insert(path, file);
+    }
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    Ok(files
+        .into_iter()
+        .map(|(path, file)| (path, file.text()))
+        .collect())
+}
+
+#[derive(Default)]
+struct File {
+    package: Option<String>,
+    imports: BTreeSet<String>,
+    body: String,
+}
+
+impl File {
+    fn text(&self) -> String {
+        let mut text = String::from(HEADER);
+        text.push_str("\nsyntax = \"proto3\";\n");
+        if let Some(package) = &self.package {
+            let _ = writeln!(text, "\npackage {package};");
+        }
+        if !self.imports.is_empty() {
+            text.push('\n');
+        }
+        for import in &self.imports {
+            let _ = writeln!(text, "import \"{import}\";");
+        }
+        text.push_str(&self.body);
+        text
+    }
+}
+
+struct Generator<'a> {
+    db: &'a dyn Db,
+    symbols: &'a SymbolTable,
+    file: File,
+    package: Vec<String>,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> Generator<'a> {
+    fn block(&mut self, name: &str, docs: &DocComment, block: &BlockDecl) {
+        self.file.package = Some(self.package.join("."));
+        match block {
+            BlockDecl::FieldSet(fields) => {
+                self.file.body.push('\n');
+                self.docs(docs, "");
+                self.message(name, &fields.fields);
+            }
+            // Fields that use a repeatable block are a map of its entries
+            BlockDecl::Repeatable(fields) => {
+                self.file.body.push('\n');
+                self.docs(docs, "");
+                self.message(&format!("{name}Entry"), &fields.fields);
+            }
+            BlockDecl::Alias(AliasDecl::MapDecl(_)) => {}
+            BlockDecl::Union(union) => {
+                self.file.body.push('\n');
+                self.docs(docs, "");
+                let _ = writeln!(self.file.body, "message {name} {{");
+                self.oneof(name, union);
+                let _ = writeln!(self.file.body, "}}");
+            }
+        }
+    }
+
+    fn message(&mut self, name: &str, fields: &[FieldDecl]) {
+        let indices = self.indices(name, fields);
+        let _ = writeln!(self.file.body, "message {name} {{");
+        for (field, index) in fields.iter().zip(indices) {
+            let typ = self.field_type(&field.typ);
+            let field_name = Case::Snake.apply(field.name.text(self.db));
+            // Maps can't be optional in proto, but are empty when not given
+            let label = if field.optional && !typ.starts_with("map<") {
+                "optional "
+            } else {
+                ""
+            };
+            self.docs(&field.docs, "  ");
+            let _ = writeln!(
+                self.file.body,
+                "  {label}{typ} {field_name} = {};",
+                index.unwrap_or(0)
+            );
+        }
+        let _ = writeln!(self.file.body, "}}");
+    }
+
+    // Unions with a discriminator use it as the name of the oneof
+    fn oneof(&mut self, name: &str, union: &UnionDecl) {
+        let variants = &union.variants.fields;
+        let indices = self.indices(name, variants);
+        let oneof = match union.discriminator {
+            Some(discriminator) => Case::Snake.apply(discriminator.text(self.db)),
+            None => "value".to_string(),
+        };
+        let _ = writeln!(self.file.body, "  oneof {oneof} {{");
+        for (variant, index) in variants.iter().zip(indices) {
+            let typ = self.field_type(&variant.typ);
+            if typ.starts_with("map<") {
+                self.error(
+                    codes::UNSUPPORTED_PROTO_TYPE,
+                    variant.name,
+                    format!("`{name}` can't be exported to proto"),
+                    "variants of a oneof can't be maps",
+                );
+            }
+            let variant_name = Case::Snake.apply(variant.name.text(self.db));
+            self.docs(&variant.docs, "    ");
+            let _ = writeln!(
+                self.file.body,
+                "    {typ} {variant_name} = {};",
+                index.unwrap_or(0)
+            );
+        }
+        let _ = writeln!(self.file.body, "  }}");
+    }
+
+    // The field numbers of a message from the `@pack` annotations of its
+    // fields. Missing, invalid and duplicate numbers are errors.
+    fn indices(&mut self, message: &str, fields: &[FieldDecl]) -> Vec<Option<u32>> {
+        let mut seen: HashMap<u32, (&str, Token)> = HashMap::new();
+        let mut indices = vec![];
+        for field in fields {
+            let name = field.name.text(self.db);
+            let Some(pack) = find_annotation(self.db, &field.annotations, "pack") else {
+                self.error(
+                    codes::MISSING_PROTO_INDEX,
+                    field.name,
+                    format!("field `{name}` has no `@pack(index = N)` annotation"),
+                    "proto fields need an index to use as their number",
+                );
+                indices.push(None);
+                continue;
+            };
+            let index = match pack.arg(self.db, "index") {
+                Some(ValueExpr::Int(token)) => token
+                    .text(self.db)
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|index| (1..=MAX_FIELD_NUMBER).contains(index))
+                    .filter(|index| !RESERVED.contains(index))
+                    .ok_or(*token),
+                Some(value) => Err(value.token()),
+                None => Err(pack.name),
+            };
+            let index = match index {
+                Ok(index) => index,
+                Err(token) => {
+                    self.error(
+                        codes::INVALID_PROTO_INDEX,
+                        token,
+                        "`@pack` needs an `index` that is a valid proto field number".to_string(),
+                        "expected a number from 1 to 536870911, outside 19000 to 19999",
+                    );
+                    indices.push(None);
+                    continue;
+                }
+            };
+            // The labels always point at the first field, however many reuse
+            // its index
+            let token = pack
+                .arg(self.db, "index")
+                .map_or(pack.name, ValueExpr::token);
+            let (first, first_token) = *seen.entry(index).or_insert((name, token));
+            if first != name {
+                let diagnostic = diagnostic(
+                    codes::DUPLICATE_PROTO_INDEX,
+                    token,
+                    format!("index {index} is used more than once in `{message}`"),
+                    &format!("`{first}` already uses this index"),
+                )
+                .with_label(first_token.span, format!("first used by `{first}` here"));
+                self.diagnostics.push(diagnostic);
+            }
+            indices.push(Some(index));
+        }
+        indices
+    }
+
+    fn docs(&mut self, docs: &DocComment, indent: &str) {
+        let Some(text) = docs.text(self.db) else {
+            return;
+        };
+        for line in text.lines() {
+            let line = format!("{indent}// {line}");
+            let _ = writeln!(self.file.body, "{}", line.trim_end());
+        }
+    }
+
+    fn map(&mut self, map: &MapDecl) -> String {
+        let key = match map.key {
+            FieldType::String(_)
+            | FieldType::Uint32(_)
+            | FieldType::Uint64(_)
+            | FieldType::Int32(_)
+            | FieldType::Int64(_) => self.field_type(&map.key),
+            _ => {
+                self.error(
+                    codes::UNSUPPORTED_PROTO_TYPE,
+                    map.key.token(),
+                    "map keys must be strings or integers in proto".to_string(),
+                    "this key can't be exported to proto",
+                );
+                "string".to_string()
+            }
+        };
+        let value = self.field_type(&map.value);
+        if value.starts_with("map<") {
+            self.error(
+                codes::UNSUPPORTED_PROTO_TYPE,
+                map.value.token(),
+                "maps can't contain maps in proto".to_string(),
+                "this value can't be exported to proto",
+            );
+        }
+        format!("map<{key}, {value}>")
+    }
+
+    fn field_type(&mut self, typ: &FieldType) -> String {
+        match typ {
+            FieldType::String(_) => "string".to_string(),
+            FieldType::Uint32(_) => "uint32".to_string(),
+            FieldType::Uint64(_) => "uint64".to_string(),
+            FieldType::Int32(_) => "int32".to_string(),
+            FieldType::Int64(_) => "int64".to_string(),
+            FieldType::Float32(_) => "float".to_string(),
+            FieldType::Float64(_) => "double".to_string(),
+            FieldType::Unknown(_) => {
+                self.file
+                    .imports
+                    .insert("google/protobuf/struct.proto".to_string());
+                "google.protobuf.Value".to_string()
+            }
+            FieldType::Struct(_) => "string".to_string(),
+            FieldType::Map(map) => self.map(map),
+            FieldType::Ident(token) => self.named_type(token),
+        }
+    }
+
+    fn named_type(&mut self, token: &Token) -> String {
+        let name = token.text(self.db);
+        let Some(symbol) = self.symbols.resolve_type(self.db, name) else {
+            return name.to_string();
+        };
+        let (name, content) = match symbol {
+            Symbol::Struct(decl) => (name.to_string(), &decl.content),
+            Symbol::Constructor(decl) => (type_name(decl.name.text(self.db)), &decl.content),
+            _ => return name.to_string(),
+        };
+        let package = module_name(self.db, symbol.name().span.file);
+        let qualified = |name: String| {
+            if package == self.package {
+                name
+            } else {
+                format!("{}.{name}", package.join("."))
+            }
+        };
+        let typ = match content {
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => return self.map(map),
+            BlockDecl::Repeatable(_) => {
+                format!("map<string, {}>", qualified(format!("{name}Entry")))
+            }
+            _ => qualified(name),
+        };
+        if package != self.package {
+            self.file
+                .imports
+                .insert(format!("{}.proto", package.join("/")));
+        }
+        typ
+    }
+
+    fn error(&mut self, code: Code, token: Token, message: String, label: &str) {
+        self.diagnostics
+            .push(diagnostic(code, token, message, label));
+    }
+}
+
+fn diagnostic(code: Code, token: Token, message: String, label: &str) -> Diagnostic {
+    Diagnostic::new(
+        Level::Error,
+        message,
+        Some(token.span),
+        Some(label.to_string()),
+    )
+    .with_code(code)
+}
+
+/// `proto` in the backend registry
+pub struct Backend;
+
+impl gen::Generator for Backend {
+    fn name(&self) -> &str {
+        "proto"
+    }
+
+    fn description(&self) -> &str {
+        "proto3 messages numbered by `@pack(index = N)`, with one file per package"
+    }
+
+    fn generate(
+        &self,
+        db: &dyn Db,
+        model: &Model,
+        options: &Options,
+    ) -> Result<Vec<OutputFile>, Vec<Diagnostic>> {
+        check_options(options, &[])?;
+        generate(db, &model.symbols).map(output_files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols;
+
+    fn proto(text: &str) -> Result<BTreeMap<String, String>, Vec<String>> {
+        let (db, symbols) = symbols(text);
+        generate(&db, &symbols).map_err(|diagnostics| {
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_struct_gives_message_numbered_by_pack_index() {
+        let files = proto(
+            "package api.folder;
+
+             /// A folder of files
+             struct Folder {
+               /// The unique ID
+               @pack(index = 1)
+               id: uint32;
+               @pack(index = 3)
+               displayName?: string;
+               @pack(index = 2)
+               sizes: map<string, float64>;
+             }",
+        )
+        .unwrap();
+        let expected = format!(
+            "{HEADER}
+syntax = \"proto3\";
+
+package api.folder;
+
+// A folder of files
+message Folder {{
+  // The unique ID
+  uint32 id = 1;
+  optional string display_name = 3;
+  map<string, double> sizes = 2;
+}}
+"
+        );
+        assert_eq!(files["api/folder.proto"], expected);
+    }
+
+    #[test]
+    fn test_union_gives_oneof() {
+        let files = proto(
+            "struct Link { @pack(index = 1) url: string; }
+             struct Item { union on kind { @pack(index = 4) link: Link; } }",
+        )
+        .unwrap();
+        assert!(files["spec.proto"].contains(
+            "message Item {
+  oneof kind {
+    Link link = 4;
+  }
+}
+"
+        ));
+    }
+
+    #[test]
+    fn test_missing_and_duplicate_indices_are_errors() {
+        let messages = proto(
+            "struct Folder {
+               @pack(index = 1) id: uint32;
+               name: string;
+               @pack(index = 1) path: string;
+               @pack(index = 19000) size: uint64;
+             }",
+        )
+        .unwrap_err();
+        assert_eq!(
+            messages,
+            [
+                "field `name` has no `@pack(index = N)` annotation",
+                "index 1 is used more than once in `Folder`",
+                "`@pack` needs an `index` that is a valid proto field number",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_indices_point_at_the_first_use() {
+        let text = "struct Folder {
+               @pack(index = 1) id: uint32;
+               @pack(index = 1) name: string;
+               @pack(index = 1) path: string;
+             }";
+        let (db, symbols) = symbols(text);
+        let diagnostics = generate(&db, &symbols).unwrap_err();
+        let labels: Vec<_> = diagnostics
+            .iter()
+            .filter_map(|diagnostic| diagnostic.span_message.as_deref())
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "`id` already uses this index",
+                "`id` already uses this index"
+            ]
+        );
+        let first = text.find("1)").unwrap();
+        for diagnostic in &diagnostics {
+            assert_eq!(diagnostic.code, Some(codes::DUPLICATE_PROTO_INDEX));
+            assert_eq!(diagnostic.labels.len(), 1);
+            assert_eq!(diagnostic.labels[0].span.start, first);
+            assert_eq!(diagnostic.labels[0].message, "first used by `id` here");
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Rust types with serde derives for the structs and constructors of a spec.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use core::ast::node::{AliasDecl, BlockDecl, DocComment, FieldDecl, FieldType, UnionDecl};
+use core::diagnostics::Diagnostic;
+use core::gen::{self, check_options, line_comment, Case, Model, Options, OutputFile};
+use core::sema::{type_name, Symbol, SymbolTable};
+use core::syntax::Token;
+use core::Db;
+
+use crate::module_name;
+
+const HEADER: &str = "// @generated by `haml gen rust`. Do not edit.\n";
+
+const DERIVES: &str =
+    "#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]";
+
+// Words that can't be used as plain identifiers in Rust 2021
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "trait", "true", "try", "type", "unsafe", "use", "where",
+    "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "typeof",
+    "unsized", "virtual", "yield",
+];
+
+/// Generate a Rust module with a type for every struct and constructor in
+/// `symbols`. Paths in the output are absolute, so it can be `include!`d
+/// anywhere. The crate using it needs `serde` with the `derive` feature, and
+/// `serde_json` if the spec has `unknown` fields.
+pub fn generate(db: &dyn Db, symbols: &SymbolTable) -> String {
+    let mut generator = Generator {
+        db,
+        symbols,
+        holds: BTreeMap::new(),
+        out: String::from(HEADER),
+    };
+    generator.holds = generator.holds();
+    generator.generate()
+}
+
+struct Generator<'db> {
+    db: &'db dyn Db,
+    symbols: &'db SymbolTable,
+    // The types each type holds directly, rather than behind a map
+    holds: BTreeMap<String, BTreeSet<String>>,
+    out: String,
+}
+
+impl<'db> Generator<'db> {
+    fn holds(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut holds: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut hold = |owner: String, fields: &[FieldDecl]| {
+            let types = fields.iter().filter_map(|field| match &field.typ {
+                FieldType::Ident(token) => Some(self.type_ref(*token)),
+                _ => None,
+            });
+            holds.entry(owner).or_default().extend(types);
+        };
+        for (name, symbol) in self.symbols.iter() {
+            let (name, block) = match symbol {
+                Symbol::Struct(decl) => (name.to_string(), &decl.content),
+                Symbol::Constructor(decl) => (type_name(name), &decl.content),
+                _ => continue,
+            };
+            match block {
+                BlockDecl::FieldSet(fields) => hold(name, &fields.fields),
+                BlockDecl::Repeatable(fields) => hold(format!("{name}Entry"), &fields.fields),
+                BlockDecl::Union(union) => hold(name, &union.variants.fields),
+                BlockDecl::Alias(_) => {}
+            }
+        }
+        holds
+    }
+
+    // Whether `from` holds `to`, directly or through the types it holds
+    fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut next = vec![from];
+        while let Some(name) = next.pop() {
+            if name == to {
+                return true;
+            }
+            if seen.insert(name) {
+                next.extend(
+                    self.holds
+                        .get(name)
+                        .into_iter()
+                        .flatten()
+                        .map(String::as_str),
+                );
+            }
+        }
+        false
+    }
+
+    fn generate(mut self) -> String {
+        for (name, symbol) in self.symbols.iter() {
+            match symbol {
+                Symbol::Struct(decl) => self.block(name, &decl.docs, &decl.content),
+                Symbol::Constructor(decl) => {
+                    self.block(&type_name(name), &decl.docs, &decl.content)
+                }
+                _ => {}
+            }
+        }
+        self.out
+    }
+
+    fn block(&mut self, name: &str, docs: &DocComment, block: &BlockDecl) {
+        self.out.push('\n');
+        match block {
+            BlockDecl::FieldSet(fields) => {
+                self.docs(docs, "");
+                self.fields(name, &fields.fields);
+            }
+            // Repeatable blocks are a map of names to entries
+            BlockDecl::Repeatable(fields) => {
+                let entry = format!("{name}Entry");
+                self.docs(docs, "");
+                let _ = writeln!(
+                    self.out,
+                    "pub type {name} = ::std::collections::BTreeMap<String, {entry}>;\n"
+                );
+                self.fields(&entry, &fields.fields);
+            }
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => {
+                self.docs(docs, "");
+                let typ = self.map(name, &map.key, &map.value);
+                let _ = writeln!(self.out, "pub type {name} = {typ};");
+            }
+            BlockDecl::Union(union) => {
+                self.docs(docs, "");
+                self.union(name, union);
+            }
+        }
+    }
+
+    // Unknown fields are an error in haml, so they are when deserializing too
+    fn fields(&mut self, name: &str, fields: &[FieldDecl]) {
+        let _ = writeln!(self.out, "{DERIVES}");
+        let _ = writeln!(self.out, "#[serde(deny_unknown_fields)]");
+        let _ = writeln!(self.out, "pub struct {name} {{");
+        for field in fields {
+            let key = field.name.text(self.db);
+            let ident = field_ident(key);
+            let typ = self.field_type(name, &field.typ);
+            self.docs(&field.docs, "    ");
+            if ident.trim_start_matches("r#") != key {
+                let _ = writeln!(self.out, "    #[serde(rename = \"{key}\")]");
+            }
+            if field.optional {
+                let _ = writeln!(
+                    self.out,
+                    "    #[serde(default, skip_serializing_if = \"Option::is_none\")]"
+                );
+                let _ = writeln!(self.out, "    pub {ident}: Option<{typ}>,");
+            } else {
+                let _ = writeln!(self.out, "    pub {ident}: {typ},");
+            }
+        }
+        let _ = writeln!(self.out, "}}");
+    }
+
+    // Unions with a discriminator are internally tagged, and unions without one
+    // are externally tagged, which is what serde does by default
+    fn union(&mut self, name: &str, union: &UnionDecl) {
+        let _ = writeln!(self.out, "{DERIVES}");
+        if let Some(discriminator) = union.discriminator {
+            let tag = discriminator.text(self.db);
+            let _ = writeln!(self.out, "#[serde(tag = \"{tag}\")]");
+        }
+        let _ = writeln!(self.out, "pub enum {name} {{");
+        for variant in &union.variants.fields {
+            let key = variant.name.text(self.db);
+            let ident = Case::Pascal.apply(key);
+            let typ = self.field_type(name, &variant.typ);
+            self.docs(&variant.docs, "    ");
+            if ident != key {
+                let _ = writeln!(self.out, "    #[serde(rename = \"{key}\")]");
+            }
+            let _ = writeln!(self.out, "    {ident}({typ}),");
+        }
+        let _ = writeln!(self.out, "}}");
+    }
+
+    fn docs(&mut self, docs: &DocComment, indent: &str) {
+        if let Some(text) = docs.text(self.db) {
+            self.out.push_str(&line_comment(&text, "///", indent));
+        }
+    }
+
+    fn map(&self, owner: &str, key: &FieldType, value: &FieldType) -> String {
+        let key = self.field_type(owner, key);
+        let value = self.field_type(owner, value);
+        format!("::std::collections::BTreeMap<{key}, {value}>")
+    }
+
+    // `owner` is the type the field is in. Fields of a type that holds the
+    // owner, like the owner itself or a struct that refers back to it, are
+    // boxed so the types have a size.
+    fn field_type(&self, owner: &str, typ: &FieldType) -> String {
+        match typ {
+            FieldType::String(_) => "String".to_string(),
+            FieldType::Uint32(_) => "u32".to_string(),
+            FieldType::Uint64(_) => "u64".to_string(),
+            FieldType::Int32(_) => "i32".to_string(),
+            FieldType::Int64(_) => "i64".to_string(),
+            FieldType::Float32(_) => "f32".to_string(),
+            FieldType::Float64(_) => "f64".to_string(),
+            FieldType::Unknown(_) => "::serde_json::Value".to_string(),
+            FieldType::Struct(_) => "String".to_string(),
+            FieldType::Map(map) => self.map(owner, &map.key, &map.value),
+            FieldType::Ident(token) => {
+                let name = self.type_ref(*token);
+                if self.reaches(&name, owner) {
+                    format!("Box<{name}>")
+                } else {
+                    name
+                }
+            }
+        }
+    }
+
+    // The Rust type a field refers to by name
+    fn type_ref(&self, token: Token) -> String {
+        let name = token.text(self.db);
+        match self.symbols.resolve_type(self.db, name) {
+            Some(Symbol::Constructor(decl)) => type_name(decl.name.text(self.db)),
+            _ => name.to_string(),
+        }
+    }
+}
+
+fn field_ident(key: &str) -> String {
+    let ident = Case::Snake.apply(key);
+    if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{ident}")
+    } else {
+        ident
+    }
+}
+
+/// `rust` in the backend registry. The module is named after the spec's
+/// package.
+pub struct Backend;
+
+impl gen::Generator for Backend {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn description(&self) -> &str {
+        "Rust types with serde derives for every struct and constructor"
+    }
+
+    fn generate(
+        &self,
+        db: &dyn Db,
+        model: &Model,
+        options: &Options,
+    ) -> Result<Vec<OutputFile>, Vec<Diagnostic>> {
+        check_options(options, &[])?;
+        let path = format!("{}.rs", module_name(db, model.file).join("_"));
+        Ok(vec![OutputFile::new(path, generate(db, &model.symbols))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols;
+
+    fn rust(text: &str) -> String {
+        let (db, symbols) = symbols(text);
+        generate(&db, &symbols)
+    }
+
+    #[test]
+    fn test_struct_gives_struct_with_optional_and_map_fields() {
+        let rust = rust(
+            "/// A folder of files
+             struct Folder {
+               /// The unique ID
+               id: uint32;
+               displayName?: string;
+               tags: map<string, float64>;
+               type: string;
+             }",
+        );
+        let expected = format!(
+            "{HEADER}
+/// A folder of files
+{DERIVES}
+#[serde(deny_unknown_fields)]
+pub struct Folder {{
+    /// The unique ID
+    pub id: u32,
+    #[serde(rename = \"displayName\")]
+    #[serde(default, skip_serializing_if = \"Option::is_none\")]
+    pub display_name: Option<String>,
+    pub tags: ::std::collections::BTreeMap<String, f64>,
+    pub r#type: String,
+}}
+"
+        );
+        assert_eq!(rust, expected);
+    }
+
+    #[test]
+    fn test_union_gives_tagged_enum() {
+        let rust = rust(
+            "struct Link { url: string; }
+             struct Item { union on kind { link: Link; } }",
+        );
+        assert!(rust.contains(&format!(
+            "{DERIVES}
+#[serde(tag = \"kind\")]
+pub enum Item {{
+    #[serde(rename = \"link\")]
+    Link(Link),
+}}
+"
+        )));
+    }
+
+    #[test]
+    fn test_constructors_are_named_by_type_name() {
+        let rust = rust(
+            "constructor endpoint { name: string; }
+             constructor api { endpoints: map<string, endpoint>; next?: api; }",
+        );
+        assert!(rust.contains("pub struct Endpoint {"));
+        assert!(rust.contains("pub endpoints: ::std::collections::BTreeMap<String, Endpoint>,"));
+        assert!(rust.contains("pub next: Option<Box<Api>>,"));
+    }
+
+    #[test]
+    fn test_references_that_close_a_cycle_are_boxed() {
+        let rust = rust(
+            "struct Folder { parent?: Parent; name: Name; }
+             struct Parent { union on kind { folder: Folder; drive: Drive; } }
+             struct Drive { root: Folder; }
+             struct Name { text: string; }",
+        );
+        assert!(rust.contains("pub parent: Option<Box<Parent>>,"));
+        assert!(rust.contains("pub name: Name,"));
+        assert!(rust.contains("Folder(Box<Folder>),"));
+        assert!(rust.contains("Drive(Box<Drive>),"));
+        assert!(rust.contains("pub root: Box<Folder>,"));
+    }
+}
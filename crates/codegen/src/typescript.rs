@@ -0,0 +1,345 @@
+//! TypeScript declarations for the structs and constructors of a spec, with
+//! one module per haml package.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use core::ast::node::{
+    find_annotation, AliasDecl, Annotation, BlockDecl, DocComment, FieldDecl, FieldType, MapDecl,
+    UnionDecl,
+};
+use core::diagnostics::Diagnostic;
+use core::gen::{self, block_comment, check_options, Imports, Model, Options, OutputFile};
+use core::sema::{type_name, Symbol, SymbolTable};
+use core::Db;
+
+use crate::{module_name, output_files, value_text};
+
+const HEADER: &str = "// @generated by `haml gen typescript`. Do not edit.\n";
+
+/// Generate a module for each package that declares a struct or constructor.
+/// Returns the text of each module by its path, like `api/spec.ts` for
+/// `package api.spec;`. Types from other packages are imported with relative
+/// paths, so the modules need to be written out together.
+pub fn generate(db: &dyn Db, symbols: &SymbolTable) -> BTreeMap<String, String> {
+    let mut modules: BTreeMap<String, Module> = BTreeMap::new();
+    for (name, symbol) in symbols.iter() {
+        let (name, docs, annotations, content) = match symbol {
+            Symbol::Struct(decl) => (
+                name.to_string(),
+                &decl.docs,
+                &decl.annotations,
+                &decl.content,
+            ),
+            Symbol::Constructor(decl) => (
+                type_name(name),
+                &decl.docs,
+                &decl.annotations,
+                &decl.content,
+            ),
+            _ => continue,
+        };
+        let path = module_path(db, symbol);
+        let mut generator = Generator {
+            db,
+            symbols,
+            module: modules.remove(&path).unwrap_or_default(),
+            path,
+        };
+        generator.block(&name, docs, annotations, content);
+        modules.insert(generator.path, generator.module);
+    }
+
+    modules
+        .into_iter()
+        .map(|(path, module)| {
+            let text = module.text(&path);
+            (path, text)
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Module {
+    // Names to import, by the path of the module they're declared in
+    imports: Imports,
+    body: String,
+}
+
+impl Module {
+    fn text(&self, path: &str) -> String {
+        let mut text = String::from(HEADER);
+        if !self.imports.is_empty() {
+            text.push('\n');
+        }
+        for (module, names) in self.imports.iter() {
+            let _ = writeln!(
+                text,
+                "import type {{ {} }} from \"{}\";",
+                names.join(", "),
+                relative_import(path, module)
+            );
+        }
+        text.push_str(&self.body);
+        text
+    }
+}
+
+struct Generator<'db> {
+    db: &'db dyn Db,
+    symbols: &'db SymbolTable,
+    path: String,
+    module: Module,
+}
+
+impl<'db> Generator<'db> {
+    fn block(
+        &mut self,
+        name: &str,
+        docs: &DocComment,
+        annotations: &[Annotation],
+        block: &BlockDecl,
+    ) {
+        self.module.body.push('\n');
+        self.jsdoc(docs, annotations, "");
+        match block {
+            BlockDecl::FieldSet(fields) => self.interface(name, &fields.fields),
+            // Repeatable blocks are a record of names to entries
+            BlockDecl::Repeatable(fields) => {
+                let entry = format!("{name}Entry");
+                let _ = writeln!(
+                    self.module.body,
+                    "export type {name} = Record<string, {entry}>;\n"
+                );
+                self.interface(&entry, &fields.fields);
+            }
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => {
+                let typ = self.map(map);
+                let _ = writeln!(self.module.body, "export type {name} = {typ};");
+            }
+            BlockDecl::Union(union) => self.union(name, union),
+        }
+    }
+
+    fn interface(&mut self, name: &str, fields: &[FieldDecl]) {
+        if fields.is_empty() {
+            let _ = writeln!(self.module.body, "export interface {name} {{}}");
+            return;
+        }
+        let _ = writeln!(self.module.body, "export interface {name} {{");
+        for field in fields {
+            let key = field.name.text(self.db);
+            let optional = if field.optional { "?" } else { "" };
+            let typ = self.field_type(&field.typ);
+            self.jsdoc(&field.docs, &field.annotations, "  ");
+            let _ = writeln!(self.module.body, "  {key}{optional}: {typ};");
+        }
+        let _ = writeln!(self.module.body, "}}");
+    }
+
+    // Variants with a discriminator are the variant struct plus the
+    // discriminator. Variants without one are an object with a single field.
+    fn union(&mut self, name: &str, union: &UnionDecl) {
+        let _ = writeln!(self.module.body, "export type {name} =");
+        let variants = &union.variants.fields;
+        if variants.is_empty() {
+            let _ = writeln!(self.module.body, "  never;");
+        }
+        for (i, variant) in variants.iter().enumerate() {
+            let key = variant.name.text(self.db);
+            let typ = self.field_type(&variant.typ);
+            let typ = match union.discriminator {
+                Some(discriminator) => {
+                    let tag = discriminator.text(self.db);
+                    format!("({{ {tag}: \"{key}\" }} & {typ})")
+                }
+                None => format!("{{ {key}: {typ} }}"),
+            };
+            let end = if i + 1 == variants.len() { ";" } else { "" };
+            self.jsdoc(&variant.docs, &variant.annotations, "  ");
+            let _ = writeln!(self.module.body, "  | {typ}{end}");
+        }
+    }
+
+    fn jsdoc(&mut self, docs: &DocComment, annotations: &[Annotation], indent: &str) {
+        let mut lines: Vec<String> = match docs.text(self.db) {
+            Some(text) => text.lines().map(str::to_string).collect(),
+            None => vec![],
+        };
+        if let Some(deprecated) = find_annotation(self.db, annotations, "deprecated") {
+            let args: Vec<_> = deprecated
+                .args
+                .iter()
+                .map(|arg| {
+                    let key = arg.key.text(self.db);
+                    format!("{key}: {}", value_text(self.db, &arg.value))
+                })
+                .collect();
+            if args.is_empty() {
+                lines.push("@deprecated".to_string());
+            } else {
+                lines.push(format!("@deprecated {}", args.join(", ")));
+            }
+        }
+
+        if !lines.is_empty() {
+            let comment = block_comment(&lines.join("\n"), indent);
+            self.module.body.push_str(&comment);
+        }
+    }
+
+    fn map(&mut self, map: &MapDecl) -> String {
+        let key = match map.key {
+            FieldType::String(_) => "string".to_string(),
+            FieldType::Uint32(_)
+            | FieldType::Uint64(_)
+            | FieldType::Int32(_)
+            | FieldType::Int64(_) => "number".to_string(),
+            ref key => self.field_type(key),
+        };
+        let value = self.field_type(&map.value);
+        format!("Record<{key}, {value}>")
+    }
+
+    fn field_type(&mut self, typ: &FieldType) -> String {
+        match typ {
+            FieldType::String(_) => "string".to_string(),
+            FieldType::Uint32(_)
+            | FieldType::Uint64(_)
+            | FieldType::Int32(_)
+            | FieldType::Int64(_)
+            | FieldType::Float32(_)
+            | FieldType::Float64(_) => "number".to_string(),
+            FieldType::Unknown(_) => "unknown".to_string(),
+            FieldType::Struct(_) => "string".to_string(),
+            FieldType::Map(map) => self.map(map),
+            FieldType::Ident(token) => {
+                let name = token.text(self.db);
+                let Some(symbol) = self.symbols.resolve_type(self.db, name) else {
+                    return name.to_string();
+                };
+                let name = match symbol {
+                    Symbol::Constructor(decl) => type_name(decl.name.text(self.db)),
+                    _ => name.to_string(),
+                };
+                let path = module_path(self.db, symbol);
+                if path != self.path {
+                    self.module.imports.add(path, name.clone());
+                }
+                name
+            }
+        }
+    }
+}
+
+// The module a symbol is generated into, like `api/spec.ts`
+fn module_path(db: &dyn Db, symbol: &Symbol) -> String {
+    format!("{}.ts", module_name(db, symbol.name().span.file).join("/"))
+}
+
+// The path to import `to` with from `from`, like `../spec` or `./common`
+fn relative_import(from: &str, to: &str) -> String {
+    let from: Vec<_> = from.split('/').collect();
+    let to: Vec<_> = to.trim_end_matches(".ts").split('/').collect();
+    let from_dirs = &from[..from.len() - 1];
+    let common = from_dirs
+        .iter()
+        .zip(&to)
+        .take_while(|(from, to)| from == to)
+        .count();
+    let mut parts = vec![".."; from_dirs.len() - common];
+    if parts.is_empty() {
+        parts.push(".");
+    }
+    parts.extend(&to[common..]);
+    parts.join("/")
+}
+
+/// `typescript` in the backend registry
+pub struct Backend;
+
+impl gen::Generator for Backend {
+    fn name(&self) -> &str {
+        "typescript"
+    }
+
+    fn description(&self) -> &str {
+        "TypeScript declarations, with one module per package"
+    }
+
+    fn generate(
+        &self,
+        db: &dyn Db,
+        model: &Model,
+        options: &Options,
+    ) -> Result<Vec<OutputFile>, Vec<Diagnostic>> {
+        check_options(options, &[])?;
+        Ok(output_files(generate(db, &model.symbols)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols;
+
+    fn typescript(text: &str) -> BTreeMap<String, String> {
+        let (db, symbols) = symbols(text);
+        generate(&db, &symbols)
+    }
+
+    #[test]
+    fn test_struct_gives_interface_with_jsdoc() {
+        let modules = typescript(
+            r#"
+            package api.folder;
+
+            /// A folder of files
+            @deprecated(expiry = "2024-06-03")
+            struct Folder {
+              /// The unique ID
+              id: uint32;
+              name?: string;
+              tags: map<string, float64>;
+            }
+            "#,
+        );
+        let expected = format!(
+            r#"{HEADER}
+/**
+ * A folder of files
+ * @deprecated expiry: 2024-06-03
+ */
+export interface Folder {{
+  /** The unique ID */
+  id: number;
+  name?: string;
+  tags: Record<string, number>;
+}}
+"#
+        );
+        assert_eq!(modules["api/folder.ts"], expected);
+    }
+
+    #[test]
+    fn test_union_with_discriminator_gives_discriminated_union() {
+        let modules = typescript(
+            "struct Link { url: string; }
+             struct Note { text: string; }
+             struct Item { union on kind { link: Link; note: Note; } }",
+        );
+        assert!(modules["spec.ts"].contains(
+            "export type Item =
+  | ({ kind: \"link\" } & Link)
+  | ({ kind: \"note\" } & Note);
+"
+        ));
+    }
+
+    #[test]
+    fn test_relative_imports() {
+        assert_eq!(relative_import("api/folder.ts", "api/spec.ts"), "./spec");
+        assert_eq!(relative_import("api/folder.ts", "common.ts"), "../common");
+        assert_eq!(relative_import("common.ts", "api/spec.ts"), "./api/spec");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::syntax::Token;
+use crate::Db;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Ast {
@@ -13,6 +14,7 @@ pub enum Node {
     ConstructorDecl(ConstructorDecl),
     StructDecl(StructDecl),
     AnnotationDecl(AnnotationDecl),
+    InstanceDecl(InstanceDecl),
     Eof,
 }
 
@@ -21,6 +23,60 @@ pub struct Comment {
     pub value: Token,
 }
 
+/// The `///` lines directly above a declaration or field
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocComment {
+    pub lines: Vec<Token>,
+}
+
+impl DocComment {
+    /// The text of the comment without its slashes, with one line per line of
+    /// the comment. `None` if there isn't a comment.
+    pub fn text(&self, db: &dyn Db) -> Option<String> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let lines: Vec<_> = self
+            .lines
+            .iter()
+            .map(|line| {
+                let text = line.text(db).trim_end();
+                let text = text.strip_prefix("///").unwrap_or(text);
+                text.strip_prefix(' ').unwrap_or(text)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+}
+
+/// A use of an annotation, like `@deprecated` or `@pack(index = 1)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub name: Token,
+    pub args: Vec<ObjectEntry>,
+}
+
+impl Annotation {
+    /// The value of the argument named `key`
+    pub fn arg(&self, db: &dyn Db, key: &str) -> Option<&ValueExpr> {
+        self.args
+            .iter()
+            .find(|arg| arg.key.text(db) == key)
+            .map(|arg| &arg.value)
+    }
+}
+
+/// Find the annotation named `name` in a list of annotations
+pub fn find_annotation<'a>(
+    db: &dyn Db,
+    annotations: &'a [Annotation],
+    name: &str,
+) -> Option<&'a Annotation> {
+    annotations
+        .iter()
+        .find(|annotation| annotation.name.text(db) == name)
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PackageStmt {
     pub segments: Vec<Token>,
@@ -28,12 +84,14 @@ pub struct PackageStmt {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ImportStmt {
+    pub annotations: Vec<Annotation>,
     pub path: Token,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConstructorDecl {
-    pub annotations: Vec<Token>,
+    pub docs: DocComment,
+    pub annotations: Vec<Annotation>,
     pub name: Token,
     pub content: BlockDecl,
 }
@@ -41,7 +99,7 @@ pub struct ConstructorDecl {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConstructorDef {
     pub checked_comment: Option<Comment>,
-    pub annotations: Vec<Token>,
+    pub annotations: Vec<Annotation>,
     pub constructor: Token,
     pub name: Token,
     pub content: BlockDecl,
@@ -52,6 +110,7 @@ pub enum BlockDecl {
     Alias(AliasDecl),
     Repeatable(FieldSetDecl),
     FieldSet(FieldSetDecl),
+    Union(UnionDecl),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -64,6 +123,16 @@ pub struct FieldSetDecl {
     pub fields: Vec<FieldDecl>,
 }
 
+/// One of a set of variants, like `union on kind { first: First; }`. With a
+/// discriminator, values are a variant struct plus a field naming the variant.
+/// Without one, values are an object with a single field named after the
+/// variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnionDecl {
+    pub discriminator: Option<Token>,
+    pub variants: FieldSetDecl,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MapDecl {
     pub key: FieldType,
@@ -72,13 +141,16 @@ pub struct MapDecl {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct StructDecl {
-    pub annotations: Vec<Token>,
+    pub docs: DocComment,
+    pub annotations: Vec<Annotation>,
     pub name: Token,
     pub content: BlockDecl,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct FieldDecl {
+    pub docs: DocComment,
+    pub annotations: Vec<Annotation>,
     pub name: Token,
     pub typ: FieldType,
     pub optional: bool,
@@ -95,13 +167,36 @@ pub enum FieldType {
     Float32(Token),
     Float64(Token),
     Unknown(Token),
+    /// A field of type `struct` holds the name of a struct, so backends give
+    /// it their string type
     Struct(Token),
     Map(Box<MapDecl>),
 }
 
+impl FieldType {
+    /// The token a diagnostic about this type should point at. Maps point at
+    /// their key type.
+    pub fn token(&self) -> Token {
+        match self {
+            FieldType::Ident(token)
+            | FieldType::String(token)
+            | FieldType::Uint32(token)
+            | FieldType::Uint64(token)
+            | FieldType::Int32(token)
+            | FieldType::Int64(token)
+            | FieldType::Float32(token)
+            | FieldType::Float64(token)
+            | FieldType::Unknown(token)
+            | FieldType::Struct(token) => *token,
+            FieldType::Map(map) => map.key.token(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AnnotationDecl {
-    pub annotations: Vec<Token>,
+    pub docs: DocComment,
+    pub annotations: Vec<Annotation>,
     pub name: Token,
     pub fields: Vec<AnnotationFieldDecl>,
 }
@@ -123,3 +218,49 @@ pub enum AnnotationFieldValue {
     Float32(Token),
     Float64(Token),
 }
+
+/// A value built with a constructor, like `api FolderApi { ... }`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstanceDecl {
+    pub docs: DocComment,
+    pub annotations: Vec<Annotation>,
+    pub constructor: Token,
+    pub name: Token,
+    pub value: ObjectExpr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueExpr {
+    String(Token),
+    Int(Token),
+    Float(Token),
+    Ident(Token),
+    Object(ObjectExpr),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectExpr {
+    pub open: Token,
+    pub entries: Vec<ObjectEntry>,
+}
+
+/// A `key: value` pair inside an object, or a `key = value` annotation
+/// argument. The key is an identifier or a string literal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectEntry {
+    pub key: Token,
+    pub value: ValueExpr,
+}
+
+impl ValueExpr {
+    /// The token a diagnostic about this value should point at
+    pub fn token(&self) -> Token {
+        match self {
+            ValueExpr::String(token)
+            | ValueExpr::Int(token)
+            | ValueExpr::Float(token)
+            | ValueExpr::Ident(token) => *token,
+            ValueExpr::Object(object) => object.open,
+        }
+    }
+}
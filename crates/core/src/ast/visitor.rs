@@ -1,8 +1,7 @@
 use super::node::{
-    AliasDecl, AnnotationDecl, Ast, BlockDecl, Comment, ConstructorDecl, FieldDecl, FieldSetDecl,
-    FieldType, ImportStmt, Node, PackageStmt, StructDecl,
+    AliasDecl, Annotation, AnnotationDecl, Ast, BlockDecl, Comment, ConstructorDecl, FieldDecl,
+    FieldSetDecl, FieldType, ImportStmt, InstanceDecl, Node, PackageStmt, StructDecl, UnionDecl,
 };
-use crate::syntax::Token;
 
 pub trait Visitor {
     fn package(&mut self, _decl: &PackageStmt) {}
@@ -17,13 +16,17 @@ pub trait Visitor {
 
     fn alias_decl(&mut self, _decl: &AliasDecl) {}
 
+    fn union_decl(&mut self, _decl: &UnionDecl) {}
+
     fn field_decl(&mut self, _decl: &FieldDecl) {}
 
     fn field_type_decl(&mut self, _decl: &FieldType) {}
 
     fn annotation_decl(&mut self, _decl: &AnnotationDecl) {}
 
-    fn annotation_def(&mut self, _def: &Token) {}
+    fn annotation_def(&mut self, _def: &Annotation) {}
+
+    fn instance_decl(&mut self, _decl: &InstanceDecl) {}
 
     fn comment(&mut self, _stmt: &Comment) {}
 }
@@ -37,6 +40,7 @@ pub fn walk(ast: &Ast, visitor: &mut impl Visitor) {
             Node::ConstructorDecl(stmt) => constructor_decl(ast, visitor, stmt),
             Node::StructDecl(stmt) => visitor.struct_decl(stmt),
             Node::AnnotationDecl(stmt) => visitor.annotation_decl(stmt),
+            Node::InstanceDecl(stmt) => visitor.instance_decl(stmt),
             Node::Comment(stmt) => visitor.comment(stmt),
             Node::Eof => {}
         };
@@ -58,9 +62,16 @@ fn block_decl(ast: &Ast, visitor: &mut impl Visitor, decl: &BlockDecl) {
         BlockDecl::Alias(decl) => alias_decl(ast, visitor, decl),
         BlockDecl::Repeatable(decl) => repeatable_decl(ast, visitor, decl),
         BlockDecl::FieldSet(decl) => field_set_decl(ast, visitor, decl),
+        BlockDecl::Union(decl) => union_decl(ast, visitor, decl),
     }
 }
 
+#[allow(unused)]
+fn union_decl(ast: &Ast, visitor: &mut impl Visitor, union: &UnionDecl) {
+    visitor.union_decl(union);
+    field_set_decl(ast, visitor, &union.variants);
+}
+
 #[allow(unused)]
 fn alias_decl(_ast: &Ast, visitor: &mut impl Visitor, alias: &AliasDecl) {
     visitor.alias_decl(alias);
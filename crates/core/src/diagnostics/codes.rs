@@ -0,0 +1,67 @@
+//! Every diagnostic reported while reading a spec or generating code from it
+//! has a stable code, like `E0002`, so it can be searched for, suppressed by
+//! tools, and explained at length with `haml explain`. The explanations live
+//! in `explain/`, one Markdown file per code.
+
+/// A kind of diagnostic
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Code {
+    /// The code itself, like `E0002`
+    pub name: &'static str,
+    /// A short summary, like "unterminated string"
+    pub title: &'static str,
+    /// A long-form explanation with examples, as Markdown
+    pub explanation: &'static str,
+}
+
+macro_rules! codes {
+    ($($constant:ident = $name:literal, $title:literal;)*) => {
+        $(
+            pub const $constant: Code = Code {
+                name: $name,
+                title: $title,
+                explanation: include_str!(concat!("explain/", $name, ".md")),
+            };
+        )*
+
+        /// Every code, in order
+        pub const CODES: &[Code] = &[$($constant),*];
+    };
+}
+
+codes! {
+    UNEXPECTED_TOKEN = "E0001", "unexpected token";
+    UNTERMINATED_STRING = "E0002", "unterminated string";
+    UNTERMINATED_COMMENT = "E0003", "unterminated comment";
+    UNEXPECTED_EOF = "E0004", "unexpected end of file";
+    SYNTAX_ERROR = "E0005", "syntax error";
+    DUPLICATE_DEFINITION = "E0006", "name defined multiple times";
+    UNRESOLVED_IMPORT = "E0007", "cannot find import";
+    UNKNOWN_CONSTRUCTOR = "E0008", "cannot find constructor";
+    UNKNOWN_TYPE = "E0009", "cannot find type";
+    NON_STRUCT_VARIANT = "E0010", "union variant with a discriminator is not a struct with fields";
+    NOT_A_SINGLE_VARIANT = "E0011", "expected a single union variant";
+    UNKNOWN_VARIANT = "E0012", "not a variant of the union";
+    MISSING_FIELD = "E0013", "missing field";
+    DUPLICATE_FIELD = "E0014", "field specified more than once";
+    UNKNOWN_FIELD = "E0015", "unknown field";
+    MISMATCHED_TYPES = "E0016", "mismatched types";
+    UNKNOWN_STRUCT = "E0017", "cannot find struct";
+    UNKNOWN_NAME = "E0018", "cannot find instance or struct";
+    OUT_OF_RANGE = "E0019", "number out of range";
+    MISSING_PROTO_INDEX = "E0020", "field has no proto field number";
+    INVALID_PROTO_INDEX = "E0021", "invalid proto field number";
+    DUPLICATE_PROTO_INDEX = "E0022", "proto field number used more than once";
+    UNSUPPORTED_PROTO_TYPE = "E0023", "type can't be exported to proto";
+}
+
+/// Find a code by its name. The leading `E` and zeros can be left out, so
+/// `e0002`, `E2` and `2` all find `E0002`.
+pub fn find(name: &str) -> Option<Code> {
+    let name = name.trim();
+    let number: u32 = name.strip_prefix(['E', 'e']).unwrap_or(name).parse().ok()?;
+    CODES
+        .iter()
+        .find(|code| code.name[1..].parse() == Ok(number))
+        .copied()
+}
@@ -0,0 +1,135 @@
+//! Gathering every diagnostic about a set of files, from all the queries that
+//! find them. The same diagnostic can be accumulated along several query
+//! paths, like an unresolved import in a file that two of the files import,
+//! so they're deduplicated, then sorted by where they are.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Level};
+use crate::lint::{lint_file_in, Config};
+use crate::queries::{
+    build_symbol_table, canonical, check_file, parse_file, resolve_imports, SourceFile,
+    TrackedConfig,
+};
+use crate::Db;
+
+/// Files with more diagnostics than this only show the first of them,
+/// followed by how many more there are
+pub const MAX_DIAGNOSTICS_PER_FILE: usize = 100;
+
+/// The diagnostics from checking `file` and linting it with `config`, grouped
+/// like [`diagnostics_for_workspace`] by the file they're in: `file` itself or
+/// one it imports
+#[salsa::tracked]
+pub fn diagnostics_for(
+    db: &dyn Db,
+    file: SourceFile,
+    config: TrackedConfig,
+) -> Vec<(SourceFile, Vec<Diagnostic>)> {
+    diagnostics_for_workspace(db, &[(file, config.config(db))])
+}
+
+/// The diagnostics from checking and linting each file with its config,
+/// grouped by the file they're in and sorted by its path. Diagnostics in
+/// imported files are included once, however many of the files import them.
+/// Diagnostics without a span are in the file they were found checking. A
+/// declaration used by any of the files that import it isn't unused.
+pub fn diagnostics_for_workspace(
+    db: &dyn Db,
+    files: &[(SourceFile, &Config)],
+) -> Vec<(SourceFile, Vec<Diagnostic>)> {
+    // Files that are imported more than once are read once per import, so
+    // files and spans are compared by path
+    let mut groups: Vec<(PathBuf, SourceFile, Vec<Diagnostic>)> = vec![];
+    let mut seen = HashSet::new();
+    let workspace: Vec<_> = files.iter().map(|&(file, _)| file).collect();
+    for &(file, config) in files {
+        for diagnostic in gather(db, file, config, &workspace) {
+            let key = key(db, &diagnostic);
+            if !seen.insert(key) {
+                continue;
+            }
+
+            let in_file = diagnostic.span.map_or(file, |span| span.file);
+            let path = canonical(in_file.path(db));
+            match groups.iter_mut().find(|(other, ..)| *other == path) {
+                Some((.., diagnostics)) => diagnostics.push(diagnostic),
+                None => groups.push((path, in_file, vec![diagnostic])),
+            }
+        }
+    }
+
+    groups.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+    groups
+        .into_iter()
+        .map(|(_, file, mut diagnostics)| {
+            diagnostics
+                .sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.start, span.end)));
+            cap(&mut diagnostics);
+            (file, diagnostics)
+        })
+        .collect()
+}
+
+// Parsing, resolving imports and building the symbol table all happen while
+// checking, but a file that doesn't parse is never checked, so each of them
+// is asked for its diagnostics
+fn gather(
+    db: &dyn Db,
+    file: SourceFile,
+    config: &Config,
+    workspace: &[SourceFile],
+) -> Vec<Diagnostic> {
+    check_file(db, file);
+    let mut diagnostics = parse_file::accumulated::<Diagnostics>(db, file);
+    diagnostics.extend(resolve_imports::accumulated::<Diagnostics>(db, file));
+    diagnostics.extend(build_symbol_table::accumulated::<Diagnostics>(db, file));
+    diagnostics.extend(check_file::accumulated::<Diagnostics>(db, file));
+    diagnostics.extend(lint_file_in(db, file, config, workspace));
+    diagnostics
+}
+
+// What makes two diagnostics the same: their level, code and message, and
+// where their span is
+type Key = (
+    Level,
+    Option<&'static str>,
+    String,
+    Option<(PathBuf, usize, usize)>,
+);
+
+fn key(db: &dyn Db, diagnostic: &Diagnostic) -> Key {
+    (
+        diagnostic.level.clone(),
+        diagnostic.code.map(|code| code.name),
+        diagnostic.message.clone(),
+        diagnostic
+            .span
+            .map(|span| (canonical(span.file.path(db)), span.start, span.end)),
+    )
+}
+
+// Only the first diagnostics of a file are kept. The one that says how many
+// more there are is as severe as the worst of them, so hiding an error never
+// hides that there was one.
+fn cap(diagnostics: &mut Vec<Diagnostic>) {
+    if diagnostics.len() <= MAX_DIAGNOSTICS_PER_FILE {
+        return;
+    }
+    let hidden = diagnostics.split_off(MAX_DIAGNOSTICS_PER_FILE);
+    let level = [Level::Error, Level::Warning]
+        .into_iter()
+        .find(|level| hidden.iter().any(|diagnostic| diagnostic.level == *level))
+        .unwrap_or(Level::Info);
+    let diagnostic = Diagnostic::new(
+        level,
+        format!("{} more diagnostics aren't shown", hidden.len()),
+        None,
+        None,
+    )
+    .with_note(format!(
+        "only the first {MAX_DIAGNOSTICS_PER_FILE} diagnostics of a file are shown"
+    ));
+    diagnostics.push(diagnostic);
+}
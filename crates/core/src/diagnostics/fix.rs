@@ -0,0 +1,83 @@
+//! Applying the suggestions of diagnostics to the source, for
+//! `haml check --fix`.
+
+use crate::diagnostics::{Applicability, Diagnostic, Edit};
+use crate::queries::SourceFile;
+use crate::span::Span;
+use crate::Db;
+
+/// The new text of a file that suggestions were applied to
+#[derive(Debug)]
+pub struct Fixed {
+    pub file: SourceFile,
+    pub text: String,
+    /// How many suggestions were applied
+    pub fixes: usize,
+}
+
+/// Apply the machine-applicable suggestions of `diagnostics`, returning the
+/// new text of each file they change, in the order the files first come up.
+/// Suggestions with an edit that overlaps one that's already been taken are
+/// skipped, so the same suggestion is never applied twice. Running again picks
+/// up anything that was skipped.
+pub fn apply_fixes(db: &dyn Db, diagnostics: &[Diagnostic]) -> Vec<Fixed> {
+    let suggestions = diagnostics
+        .iter()
+        .flat_map(|diagnostic| &diagnostic.suggestions)
+        .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable);
+
+    let mut files: Vec<(SourceFile, Vec<&Edit>, usize)> = vec![];
+    for suggestion in suggestions {
+        let taken = |edit: &Edit| {
+            files.iter().any(|(file, edits, _)| {
+                *file == edit.span.file && edits.iter().any(|other| overlaps(edit.span, other.span))
+            })
+        };
+        if suggestion.edits.is_empty() || suggestion.edits.iter().any(taken) {
+            continue;
+        }
+        for edit in &suggestion.edits {
+            let index = match files.iter().position(|(file, ..)| *file == edit.span.file) {
+                Some(index) => index,
+                None => {
+                    files.push((edit.span.file, vec![], 0));
+                    files.len() - 1
+                }
+            };
+            files[index].1.push(edit);
+        }
+        // A suggestion counts once for every file it changes
+        for (file, _, fixes) in &mut files {
+            if suggestion.edits.iter().any(|edit| edit.span.file == *file) {
+                *fixes += 1;
+            }
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|(file, mut edits, fixes)| {
+            edits.sort_by_key(|edit| edit.span.start);
+            let text = file.text(db);
+            let mut fixed = String::with_capacity(text.len());
+            let mut position = 0;
+            for edit in edits {
+                fixed.push_str(&text[position..edit.span.start]);
+                fixed.push_str(&edit.replacement);
+                position = edit.span.end;
+            }
+            fixed.push_str(&text[position..]);
+            Fixed {
+                file,
+                text: fixed,
+                fixes,
+            }
+        })
+        .collect()
+}
+
+// Edits overlap if they change the same text, or insert at the same place,
+// where the order they'd be applied in is ambiguous
+fn overlaps(a: Span, b: Span) -> bool {
+    (a.start < b.end && b.start < a.end) || a.start == b.start
+}
@@ -0,0 +1,132 @@
+//! Diagnostics rendered for tools rather than people: one JSON object per
+//! diagnostic, or one line per diagnostic in the `file:line:col` form that
+//! editors and CI logs recognise.
+
+use serde_json::{json, Value as Json};
+
+use crate::diagnostics::{Applicability, Diagnostic, Level};
+use crate::queries::{line_index, SourceFile};
+use crate::span::Span;
+use crate::Db;
+
+impl Diagnostic {
+    /// The diagnostic as a JSON object, with 1-based lines and columns.
+    /// Diagnostics without a span are about `file` as a whole. The primary
+    /// label comes first, followed by any secondary labels.
+    ///
+    /// ```json
+    /// {
+    ///   "level": "error",
+    ///   "code": "E0006",
+    ///   "message": "`Folder` is defined multiple times",
+    ///   "file": "spec/api.haml",
+    ///   "range": { "start": { "line": 7, "column": 8 }, "end": { "line": 7, "column": 14 } },
+    ///   "labels": [
+    ///     { "message": "`Folder` redefined here", "primary": true, "file": "spec/api.haml", "range": { ... } },
+    ///     { "message": "previous definition of `Folder` here", "primary": false, "file": "spec/common.haml", "range": { ... } }
+    ///   ],
+    ///   "notes": ["`Folder` must be defined only once, including in imported files"],
+    ///   "help": [],
+    ///   "suggestions": [{
+    ///     "message": "replace `,` with `;`",
+    ///     "applicability": "machine-applicable",
+    ///     "edits": [{ "file": "spec/api.haml", "range": { ... }, "replacement": ";" }]
+    ///   }]
+    /// }
+    /// ```
+    pub fn to_json(&self, db: &dyn Db, file: &SourceFile) -> Json {
+        let file = self.span.map_or(*file, |span| span.file);
+        let primary = self
+            .span
+            .map(|span| label(db, span, self.span_message.as_deref(), true));
+        let secondary = self
+            .labels
+            .iter()
+            .map(|secondary| label(db, secondary.span, Some(&secondary.message), false));
+        let labels: Vec<_> = primary.into_iter().chain(secondary).collect();
+        json!({
+            "level": level_name(&self.level),
+            "code": self.code.map(|code| code.name),
+            "message": self.message,
+            "file": file.path(db).to_string_lossy(),
+            "range": self.span.map(|span| range(db, span)),
+            "labels": labels,
+            "notes": self.notes,
+            "help": self.help,
+            "suggestions": self.suggestions.iter().map(|suggestion| {
+                let edits: Vec<_> = suggestion
+                    .edits
+                    .iter()
+                    .map(|edit| {
+                        json!({
+                            "file": edit.span.file.path(db).to_string_lossy(),
+                            "range": range(db, edit.span),
+                            "replacement": edit.replacement,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "message": suggestion.message,
+                    "applicability": applicability_name(suggestion.applicability),
+                    "edits": edits,
+                })
+            }).collect::<Vec<_>>(),
+        })
+    }
+
+    /// The diagnostic on one line, like `spec/api.haml:7:10: error[E0001]: ...`
+    pub fn to_short_message(&self, db: &dyn Db, file: &SourceFile) -> String {
+        let file = self.span.map_or(*file, |span| span.file);
+        let path = file.path(db).to_string_lossy();
+        let level = match self.code {
+            Some(code) => format!("{}[{}]", level_name(&self.level), code.name),
+            None => level_name(&self.level).to_string(),
+        };
+        match self.span {
+            Some(span) => {
+                let (line, column) = line_column(db, file, span.start);
+                format!("{path}:{line}:{column}: {level}: {}", self.message)
+            }
+            None => format!("{path}: {level}: {}", self.message),
+        }
+    }
+}
+
+fn applicability_name(applicability: Applicability) -> &'static str {
+    match applicability {
+        Applicability::MachineApplicable => "machine-applicable",
+        Applicability::MaybeIncorrect => "maybe-incorrect",
+    }
+}
+
+pub(crate) fn level_name(level: &Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warning => "warning",
+        Level::Info => "info",
+    }
+}
+
+fn label(db: &dyn Db, span: Span, message: Option<&str>, primary: bool) -> Json {
+    json!({
+        "message": message,
+        "primary": primary,
+        "file": span.file.path(db).to_string_lossy(),
+        "range": range(db, span),
+    })
+}
+
+fn range(db: &dyn Db, span: Span) -> Json {
+    let (start_line, start_column) = line_column(db, span.file, span.start);
+    let (end_line, end_column) = line_column(db, span.file, span.end);
+    json!({
+        "start": { "line": start_line, "column": start_column },
+        "end": { "line": end_line, "column": end_column },
+    })
+}
+
+// The 1-based line and character column of a byte offset
+pub(crate) fn line_column(db: &dyn Db, file: SourceFile, offset: usize) -> (usize, usize) {
+    let position = line_index(db, file).position(offset);
+    (position.line + 1, position.chars + 1)
+}
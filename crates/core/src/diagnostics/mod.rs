@@ -0,0 +1,289 @@
+pub mod codes;
+mod collect;
+mod fix;
+mod json;
+mod render;
+mod sarif;
+mod suggest;
+#[cfg(test)]
+mod test;
+
+use crate::{
+    span::Span,
+    syntax::{ParseSession, Token, TokenKind},
+};
+use codes::Code;
+use derive_new::new;
+use serde::Deserialize;
+
+pub use collect::{diagnostics_for, diagnostics_for_workspace, MAX_DIAGNOSTICS_PER_FILE};
+pub use fix::{apply_fixes, Fixed};
+pub use render::RenderOptions;
+pub use sarif::{SarifLog, SARIF_VERSION};
+pub use suggest::{edit_distance, similar_name};
+
+pub trait Emitter {
+    fn emit_message(&self, message: &str);
+    fn emit_unexpected_eof(&self, eof: Span, unclosed: &[Token]);
+    fn emit_duplicate_identifier(&self, token: Token, previous: Token);
+    fn emit_unexpected_token(&self, token: Token, expected: &str);
+    fn emit_unexpected_separator(&self, token: Token, expected: TokenKind);
+    fn emit_unterminated_comment(&self, token: Token);
+    fn emit_unterminated_string(&self, token: Token);
+}
+
+#[derive(new, Copy, Clone)]
+pub struct DiagnosticEmitter<'db> {
+    sess: &'db ParseSession<'db>,
+}
+
+impl<'db> Emitter for DiagnosticEmitter<'db> {
+    fn emit_message(&self, message: &str) {
+        let diagnostic = Diagnostic::new(Level::Error, message.to_string(), None, None)
+            .with_code(codes::SYNTAX_ERROR);
+        Diagnostics::push(self.sess.db, diagnostic);
+    }
+
+    fn emit_unexpected_eof(&self, eof: Span, unclosed: &[Token]) {
+        let mut diagnostic = Diagnostic::new(
+            Level::Error,
+            "file ended unexpectedly".to_string(),
+            Some(eof),
+            Some("expected more code, but the file ended".to_string()),
+        )
+        .with_code(codes::UNEXPECTED_EOF);
+        if let Some(innermost) = unclosed.last() {
+            diagnostic.span_message = Some("expected `}`".to_string());
+            diagnostic = diagnostic
+                .with_label(innermost.span, "unclosed delimiter")
+                .with_suggestion(close_braces(self.sess, eof, unclosed));
+        }
+        Diagnostics::push(self.sess.db, diagnostic);
+    }
+
+    fn emit_duplicate_identifier(&self, token: Token, previous: Token) {
+        let name = token.text(self.sess.db);
+        let diagnostic = Diagnostic::new(
+            Level::Error,
+            format!("`{name}` is defined multiple times"),
+            Some(token.span),
+            Some(format!("`{name}` redefined here")),
+        )
+        .with_code(codes::DUPLICATE_DEFINITION)
+        .with_label(
+            previous.span,
+            format!("previous definition of `{name}` here"),
+        )
+        .with_note(format!(
+            "`{name}` must be defined only once, including in imported files"
+        ));
+        Diagnostics::push(self.sess.db, diagnostic);
+    }
+
+    fn emit_unexpected_token(&self, token: Token, expected: &str) {
+        let diagnostic = Diagnostic::new(
+            Level::Error,
+            format!("expected {expected} but found a {}", token.kind),
+            Some(token.span),
+            Some(format!("expected {expected}")),
+        )
+        .with_code(codes::UNEXPECTED_TOKEN);
+        Diagnostics::push(self.sess.db, diagnostic);
+    }
+
+    fn emit_unexpected_separator(&self, token: Token, expected: TokenKind) {
+        let note = match expected {
+            TokenKind::Semi => "fields of a declaration end with `;`",
+            _ => "entries of an object are separated by `,`",
+        };
+        let diagnostic = Diagnostic::new(
+            Level::Error,
+            format!("expected {expected} but found a {}", token.kind),
+            Some(token.span),
+            Some(format!("expected {expected}")),
+        )
+        .with_code(codes::UNEXPECTED_TOKEN)
+        .with_note(note)
+        .with_suggestion(Suggestion::replace(
+            format!("replace `{}` with `{expected}`", token.kind),
+            token.span,
+            expected.to_string(),
+            Applicability::MachineApplicable,
+        ));
+        Diagnostics::push(self.sess.db, diagnostic);
+    }
+
+    fn emit_unterminated_comment(&self, token: Token) {
+        let diagnostic = Diagnostic::new(
+            Level::Error,
+            "found unterminated comment".to_string(),
+            Some(token.span),
+            Some(format!("this comment must be ended with a `*/` characters")),
+        )
+        .with_code(codes::UNTERMINATED_COMMENT);
+        Diagnostics::push(self.sess.db, diagnostic);
+    }
+
+    fn emit_unterminated_string(&self, token: Token) {
+        // The string runs to the end of the file, but the quote was most
+        // likely left off the end of its first line
+        let text = self.sess.span_text(&token.span);
+        let line = text.lines().next().unwrap_or(text);
+        let content = line.trim_end().trim_end_matches([';', ',']).trim_end();
+        let line_span = Span::new(
+            token.span.start,
+            token.span.start + line.len(),
+            token.span.file,
+        );
+        let quote = token.span.start + content.len();
+        let diagnostic = Diagnostic::new(
+            Level::Error,
+            "found unterminated string".to_string(),
+            Some(line_span),
+            Some("this string is never closed".to_string()),
+        )
+        .with_code(codes::UNTERMINATED_STRING)
+        .with_suggestion(Suggestion::replace(
+            "add the closing `\"`",
+            Span::new(quote, quote, token.span.file),
+            "\"",
+            Applicability::MaybeIncorrect,
+        ));
+        Diagnostics::push(self.sess.db, diagnostic);
+    }
+}
+
+// Close every unclosed brace at the end of the file, each on its own line and
+// indented like the line it was opened on
+fn close_braces(sess: &ParseSession, eof: Span, unclosed: &[Token]) -> Suggestion {
+    let text = sess.text();
+    let mut closing = String::new();
+    if !text.is_empty() && !text.ends_with('\n') {
+        closing.push('\n');
+    }
+    for brace in unclosed.iter().rev() {
+        let line_start = text[..brace.span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line = &text[line_start..brace.span.start];
+        let indent = &line[..line.len() - line.trim_start().len()];
+        closing.push_str(&format!("{indent}}}\n"));
+    }
+    let message = match unclosed.len() {
+        1 => "add the missing `}`".to_string(),
+        count => format!("add the {count} missing `}}`s"),
+    };
+    Suggestion::replace(message, eof, closing, Applicability::MachineApplicable)
+}
+
+#[salsa::accumulator]
+pub struct Diagnostics(Diagnostic);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(new, Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Option<Span>,
+    pub span_message: Option<String>,
+    #[new(default)]
+    pub code: Option<Code>,
+    /// Other spans that explain the diagnostic, like the first definition of
+    /// a name that's defined twice. They can be in any file.
+    #[new(default)]
+    pub labels: Vec<Label>,
+    /// Footers giving more context, shown as `note: ...`
+    #[new(default)]
+    pub notes: Vec<String>,
+    /// Footers saying how to fix the problem, shown as `help: ...`
+    #[new(default)]
+    pub help: Vec<String>,
+    /// Changes to the source that would fix the problem
+    #[new(default)]
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// A secondary label, pointing at a span related to a diagnostic
+#[derive(new, Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A change to the source that fixes a diagnostic, made of edits that are
+/// applied together
+#[derive(new, Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    /// What the change does, like "replace `,` with `;`"
+    pub message: String,
+    pub edits: Vec<Edit>,
+    pub applicability: Applicability,
+}
+
+/// Replace the text at `span`, which can be empty to insert text
+#[derive(new, Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// Whether a suggestion can be applied without someone checking it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Applicability {
+    /// The suggestion is certainly what was meant, so `haml check --fix`
+    /// applies it
+    MachineApplicable,
+    /// The suggestion is likely, but not certainly, what was meant. It's
+    /// only offered, like in an editor.
+    MaybeIncorrect,
+}
+
+impl Suggestion {
+    /// A suggestion that replaces a single span
+    pub fn replace(
+        message: impl Into<String>,
+        span: Span,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> Suggestion {
+        let edit = Edit::new(span, replacement.into());
+        Suggestion::new(message.into(), vec![edit], applicability)
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic, with the code that `haml explain` describes it by
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// The diagnostic, with a secondary label at `span`
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label::new(span, message.into()));
+        self
+    }
+
+    /// The diagnostic, with a `note:` footer
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// The diagnostic, with a `help:` footer
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// The diagnostic, with a change that would fix it
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
+        self
+    }
+}
@@ -0,0 +1,620 @@
+//! Diagnostics rendered for people, in the style of rustc. Labels are drawn
+//! under the source they point at, grouped by file, with the primary label's
+//! file first:
+//!
+//! ```text
+//! error[E0006]: `Folder` is defined multiple times
+//!  --> spec/api.haml:5:8
+//!   |
+//! 2 | struct Folder {
+//!   |        ------ previous definition of `Folder` here
+//! ...
+//! 5 | struct Folder {
+//!   |        ^^^^^^ `Folder` redefined here
+//!   |
+//!   = note: `Folder` must be defined only once, including in imported files
+//! ```
+//!
+//! Spans over several lines are drawn in a margin to the left of the source,
+//! and lines too long for the terminal are trimmed to the part the labels are
+//! on.
+
+use crate::diagnostics::json::level_name;
+use crate::diagnostics::{Diagnostic, Level};
+use crate::line_index::char_width;
+use crate::queries::{line_index, SourceFile};
+use crate::span::Span;
+use crate::Db;
+
+// Multi-line spans longer than this only show their first and last lines
+const MAX_MULTILINE_LINES: usize = 6;
+
+// Trimmed lines keep this many columns before the first label on them
+const TRIM_CONTEXT: usize = 8;
+
+// Lines are never trimmed to fewer columns than this, however narrow the
+// terminal is
+const MIN_TRIMMED_WIDTH: usize = 20;
+
+/// How diagnostics are drawn in a terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Whether to color the output with ANSI escape codes
+    pub color: bool,
+    /// The widest a line of output can be. Lines of source that are wider are
+    /// trimmed around their labels, with `...` where they're cut.
+    pub max_width: Option<usize>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            color: true,
+            max_width: None,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic as it's shown in a terminal, in color and without
+    /// trimming long lines. Diagnostics without a span are about `file` as a
+    /// whole.
+    pub fn to_user_message(&self, db: &dyn Db, file: &SourceFile) -> String {
+        self.render(db, file, &RenderOptions::default())
+    }
+
+    /// The diagnostic as it's shown in a terminal, colored and trimmed
+    /// according to `options`
+    pub fn render(&self, db: &dyn Db, file: &SourceFile, options: &RenderOptions) -> String {
+        let color = options.color;
+        let marks = self.marks(db);
+        let width = marks
+            .iter()
+            .map(|mark| mark.end_line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(width);
+
+        let mut out = vec![self.header(color)];
+        if marks.is_empty() {
+            let path = file.path(db).to_string_lossy();
+            out.push(format!(
+                "{pad}{} {path}",
+                paint(Style::Gutter, "-->", color)
+            ));
+        }
+        for (index, group) in by_file(&marks).iter().enumerate() {
+            let first = group[0];
+            let arrow = if index == 0 { "-->" } else { ":::" };
+            if index > 0 {
+                out.push(gutter(&pad, color));
+            }
+            out.push(format!(
+                "{pad}{} {}:{}:{}",
+                paint(Style::Gutter, arrow, color),
+                first.file.path(db).to_string_lossy(),
+                first.start_line,
+                first.start_char + 1
+            ));
+            out.push(gutter(&pad, color));
+            snippet(db, group, width, options, &mut out);
+        }
+
+        let footers = self
+            .notes
+            .iter()
+            .map(|note| ("note", note))
+            .chain(self.help.iter().map(|help| ("help", help)))
+            .chain(self.suggestions.iter().map(|fix| ("help", &fix.message)));
+        for (index, (kind, text)) in footers.enumerate() {
+            if index == 0 && !marks.is_empty() {
+                out.push(gutter(&pad, color));
+            }
+            // Later lines of the footer line up with its first
+            let indent = format!("\n{pad}   {}", " ".repeat(kind.len() + 2));
+            out.push(format!(
+                "{pad} {} {}: {}",
+                paint(Style::Gutter, "=", color),
+                paint(Style::Bold, kind, color),
+                text.lines().collect::<Vec<_>>().join(&indent)
+            ));
+        }
+        out.join("\n")
+    }
+
+    // Like `error[E0002]: message`
+    fn header(&self, color: bool) -> String {
+        let level = match self.code {
+            Some(code) => format!("{}[{}]", level_name(&self.level), code.name),
+            None => level_name(&self.level).to_string(),
+        };
+        format!(
+            "{}{}",
+            paint(Style::Level(self.level.clone()), &level, color),
+            paint(Style::Bold, &format!(": {}", self.message), color)
+        )
+    }
+
+    // The primary label, then the secondary ones
+    fn marks(&self, db: &dyn Db) -> Vec<Mark<'_>> {
+        let primary = self.span.map(|span| {
+            let style = Style::Level(self.level.clone());
+            Mark::new(db, span, self.span_message.as_deref(), style)
+        });
+        let secondary = self
+            .labels
+            .iter()
+            .map(|label| Mark::new(db, label.span, Some(&label.message), Style::Gutter));
+        primary.into_iter().chain(secondary).collect()
+    }
+}
+
+// A label, located in its file with 1-based lines and 0-based columns on
+// screen, so wide characters take up two columns. The end is exclusive.
+struct Mark<'a> {
+    file: SourceFile,
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    // The character column of the start, for the `file:line:col` header
+    start_char: usize,
+    // Whether only whitespace comes before the mark on its first line
+    starts_line: bool,
+    message: Option<&'a str>,
+    style: Style,
+}
+
+impl<'a> Mark<'a> {
+    fn new(db: &dyn Db, span: Span, message: Option<&'a str>, style: Style) -> Mark<'a> {
+        let index = line_index(db, span.file);
+        let start = index.position(span.start);
+        let mut end = index.position(span.end);
+        // A span that ends with a line break ends on the line it breaks
+        if end.line > start.line && end.utf8 == 0 {
+            let line = index.line(end.line - 1).unwrap_or_default();
+            end = index.position(line.end);
+        }
+        let line_start = index.line(start.line).unwrap_or_default().start;
+        let before = &span.file.text(db)[line_start..line_start + start.utf8];
+        Mark {
+            file: span.file,
+            start_line: start.line + 1,
+            start_column: start.width,
+            end_line: end.line + 1,
+            end_column: end.width,
+            start_char: start.chars,
+            starts_line: before.trim().is_empty(),
+            message: message.filter(|message| !message.is_empty()),
+            style,
+        }
+    }
+
+    fn is_multiline(&self) -> bool {
+        self.start_line != self.end_line
+    }
+
+    // The column of the last character, where a caret goes
+    fn last_column(&self) -> usize {
+        self.end_column.saturating_sub(1)
+    }
+
+    fn underline(&self) -> char {
+        match self.style {
+            Style::Gutter => '-',
+            _ => '^',
+        }
+    }
+}
+
+// Marks grouped by the file they're in, in the order the files first appear
+fn by_file<'m, 'a>(marks: &'m [Mark<'a>]) -> Vec<Vec<&'m Mark<'a>>> {
+    let mut groups: Vec<Vec<&Mark>> = vec![];
+    for mark in marks {
+        match groups.iter_mut().find(|group| group[0].file == mark.file) {
+            Some(group) => group.push(mark),
+            None => groups.push(vec![mark]),
+        }
+    }
+    groups
+}
+
+// The lines of one file that have marks, with the marks drawn under them
+fn snippet(
+    db: &dyn Db,
+    marks: &[&Mark],
+    width: usize,
+    options: &RenderOptions,
+    out: &mut Vec<String>,
+) {
+    let file = marks[0].file;
+    let multiline = nest(marks);
+    let margin = multiline
+        .iter()
+        .map(|(depth, _)| depth * 2 + 2)
+        .max()
+        .unwrap_or(0);
+    let layout = Layout {
+        width,
+        margin,
+        options: *options,
+    };
+
+    let mut lines = vec![];
+    for mark in marks {
+        let count = mark.end_line - mark.start_line + 1;
+        if count <= MAX_MULTILINE_LINES {
+            lines.extend(mark.start_line..=mark.end_line);
+        } else {
+            lines.extend([mark.start_line, mark.start_line + 1]);
+            lines.extend([mark.end_line - 1, mark.end_line]);
+        }
+    }
+    lines.sort_unstable();
+    lines.dedup();
+
+    let mut previous: Option<usize> = None;
+    for number in lines {
+        match previous {
+            // A single hidden line takes as much room as the `...`
+            Some(previous) if number == previous + 2 => {
+                let hidden = previous + 1;
+                let source = source_line(line(db, file, hidden), hidden, &multiline, margin);
+                layout.push(hidden, source, vec![], None, out);
+            }
+            Some(previous) if number > previous + 2 => out.push("...".to_string()),
+            _ => {}
+        }
+        previous = Some(number);
+        let source = source_line(line(db, file, number), number, &multiline, margin);
+        let mut rows = vec![];
+
+        // Multi-line marks that start part way into this line are joined to
+        // the margin under it
+        for &(depth, mark) in &multiline {
+            if mark.start_line == number && !mark.starts_line {
+                let mut row = Row::margin(&multiline, |other| {
+                    other.start_line < number && number < other.end_line
+                });
+                row.fill(
+                    depth * 2 + 1,
+                    margin + mark.start_column,
+                    '_',
+                    mark.style.clone(),
+                );
+                row.put(
+                    margin + mark.start_column,
+                    mark.underline(),
+                    mark.style.clone(),
+                );
+                rows.push(row);
+            }
+        }
+
+        let singles: Vec<_> = marks
+            .iter()
+            .filter(|mark| !mark.is_multiline() && mark.start_line == number)
+            .collect();
+        let active = |other: &Mark| other.start_line <= number && number < other.end_line;
+        rows.extend(label_rows(&singles, &multiline, margin, &active));
+
+        for &(depth, mark) in &multiline {
+            if mark.end_line == number {
+                let mut row = Row::margin(&multiline, |other| {
+                    other.start_line <= number && number < other.end_line
+                });
+                let column = margin + mark.last_column();
+                row.put(depth * 2, '|', mark.style.clone());
+                row.fill(depth * 2 + 1, column, '_', mark.style.clone());
+                row.put(column, mark.underline(), mark.style.clone());
+                if let Some(message) = mark.message {
+                    row.text(column + 2, message, mark.style.clone());
+                }
+                rows.push(row);
+            }
+        }
+
+        layout.push(number, source, rows, columns(marks, number), out);
+    }
+}
+
+// The columns the marks on a line cover, from the start of the first to the
+// end of the last
+fn columns(marks: &[&Mark], number: usize) -> Option<(usize, usize)> {
+    let columns = marks.iter().filter_map(|mark| {
+        if mark.start_line == number && !mark.is_multiline() {
+            Some((
+                mark.start_column,
+                mark.end_column.max(mark.start_column + 1),
+            ))
+        } else if mark.start_line == number {
+            Some((mark.start_column, mark.start_column + 1))
+        } else if mark.end_line == number {
+            Some((mark.last_column(), mark.last_column() + 1))
+        } else {
+            None
+        }
+    });
+    columns.reduce(|(start, end), (other_start, other_end)| {
+        (start.min(other_start), end.max(other_end))
+    })
+}
+
+// Where the parts of a snippet go: the line numbers are `width` wide, and the
+// source starts `margin` columns after the `|`
+struct Layout {
+    width: usize,
+    margin: usize,
+    options: RenderOptions,
+}
+
+impl Layout {
+    // A line of source and the rows drawn under it. A line too wide for
+    // the terminal is trimmed to `columns`, and so are the rows under it.
+    fn push(
+        &self,
+        number: usize,
+        mut source: Row,
+        mut rows: Vec<Row>,
+        columns: Option<(usize, usize)>,
+        out: &mut Vec<String>,
+    ) {
+        let Layout {
+            width,
+            margin,
+            options,
+        } = *self;
+        if let Some(max_width) = options.max_width {
+            let available = max_width
+                .saturating_sub(width + 3 + margin)
+                .max(MIN_TRIMMED_WIDTH);
+            let length = source.cells.len().saturating_sub(margin);
+            if length > available {
+                let (start, end) = columns.unwrap_or_default();
+                let left = match end <= available {
+                    true => 0,
+                    false => start.saturating_sub(TRIM_CONTEXT).min(length - available),
+                };
+                for row in rows.iter_mut() {
+                    row.cut(margin, left);
+                }
+                source.cut(margin, left);
+                source.trim(margin, left > 0, available);
+            }
+        }
+
+        let color = options.color;
+        let number = paint(Style::Gutter, &format!("{number:>width$}"), color);
+        let bar = paint(Style::Gutter, "|", color);
+        out.push(format!("{number} {bar} {}", source.render(color)));
+        let pad = " ".repeat(width);
+        for row in rows {
+            out.push(format!("{pad} {bar} {}", row.render(color)));
+        }
+    }
+}
+
+// Multi-line marks, with the column of the margin each is drawn in. Marks
+// share a column when they don't overlap.
+fn nest<'m, 'a>(marks: &[&'m Mark<'a>]) -> Vec<(usize, &'m Mark<'a>)> {
+    let mut multiline: Vec<_> = marks.iter().filter(|mark| mark.is_multiline()).collect();
+    multiline.sort_by_key(|mark| mark.start_line);
+    let mut columns: Vec<usize> = vec![];
+    let mut nested = vec![];
+    for mark in multiline {
+        let depth = match columns.iter().position(|&end| end < mark.start_line) {
+            Some(depth) => depth,
+            None => {
+                columns.push(0);
+                columns.len() - 1
+            }
+        };
+        columns[depth] = mark.end_line;
+        nested.push((depth, *mark));
+    }
+    nested
+}
+
+// A line of source, with the margins of any multi-line marks it's part of
+fn source_line(line: &str, number: usize, multiline: &[(usize, &Mark)], margin: usize) -> Row {
+    let mut row = Row::default();
+    for &(depth, mark) in multiline {
+        if mark.start_line == number && mark.starts_line {
+            row.put(depth * 2, '/', mark.style.clone());
+        } else if mark.start_line < number && number <= mark.end_line {
+            row.put(depth * 2, '|', mark.style.clone());
+        }
+    }
+    row.text(margin, line, Style::Plain);
+    row
+}
+
+// Underlines for the marks on one line. The rightmost label goes at the end
+// of the underlines, and the others hang below them, like:
+//
+//     ----   ^^^^ primary
+//     |
+//     secondary
+fn label_rows(
+    marks: &[&&Mark],
+    multiline: &[(usize, &Mark)],
+    margin: usize,
+    active: &dyn Fn(&Mark) -> bool,
+) -> Vec<Row> {
+    if marks.is_empty() {
+        return vec![];
+    }
+    let mut marks = marks.to_vec();
+    marks.sort_by_key(|mark| mark.start_column);
+    let base = || Row::margin(multiline, active);
+
+    let mut underlines = base();
+    for mark in &marks {
+        let end = mark.end_column.max(mark.start_column + 1);
+        underlines.fill(
+            margin + mark.start_column,
+            margin + end,
+            mark.underline(),
+            mark.style.clone(),
+        );
+    }
+    let (last, rest) = marks.split_last().expect("marks isn't empty");
+    if let Some(message) = last.message {
+        let end = last.end_column.max(last.start_column + 1);
+        underlines.text(margin + end + 1, message, last.style.clone());
+    }
+
+    let mut rows = vec![underlines];
+    let hanging: Vec<_> = rest.iter().filter(|mark| mark.message.is_some()).collect();
+    for (index, mark) in hanging.iter().enumerate().rev() {
+        let mut connector = base();
+        for other in &hanging[..=index] {
+            connector.put(margin + other.start_column, '|', other.style.clone());
+        }
+        let mut label = base();
+        for other in &hanging[..index] {
+            label.put(margin + other.start_column, '|', other.style.clone());
+        }
+        let message = mark.message.expect("only labels with messages hang");
+        label.text(margin + mark.start_column, message, mark.style.clone());
+        rows.extend([connector, label]);
+    }
+    rows
+}
+
+// A 1-based line of a file, without its line break
+fn line(db: &dyn Db, file: SourceFile, number: usize) -> &str {
+    let text = file.text(db);
+    line_index(db, file)
+        .line(number - 1)
+        .map_or("", |range| &text[range])
+}
+
+fn gutter(pad: &str, color: bool) -> String {
+    format!("{pad} {}", paint(Style::Gutter, "|", color))
+}
+
+// A row of styled cells, drawn under or beside a line of source. Each cell is
+// one column on screen, so the second column of a wide character is an empty
+// cell.
+#[derive(Default)]
+struct Row {
+    cells: Vec<(String, Style)>,
+}
+
+impl Row {
+    // An empty row, with `|` in the margin for the multi-line marks that pass
+    // by it
+    fn margin(multiline: &[(usize, &Mark)], active: impl Fn(&Mark) -> bool) -> Row {
+        let mut row = Row::default();
+        for &(depth, mark) in multiline {
+            if active(mark) {
+                row.put(depth * 2, '|', mark.style.clone());
+            }
+        }
+        row
+    }
+
+    fn put(&mut self, column: usize, cell: impl Into<String>, style: Style) {
+        if self.cells.len() <= column {
+            self.cells
+                .resize(column + 1, (" ".to_string(), Style::Plain));
+        }
+        self.cells[column] = (cell.into(), style);
+    }
+
+    fn fill(&mut self, start: usize, end: usize, c: char, style: Style) {
+        for column in start..end {
+            self.put(column, c, style.clone());
+        }
+    }
+
+    // Tabs are drawn as spaces, and zero-width characters like combining
+    // accents are drawn with the character before them
+    fn text(&mut self, column: usize, text: &str, style: Style) {
+        let mut column = column;
+        for c in text.chars() {
+            let width = char_width(c);
+            if c == '\t' {
+                self.fill(column, column + width, ' ', style.clone());
+            } else if width == 0 {
+                if let Some((cell, _)) = column.checked_sub(1).and_then(|i| self.cells.get_mut(i)) {
+                    cell.push(c);
+                }
+            } else {
+                self.put(column, c, style.clone());
+                for extra in column + 1..column + width {
+                    self.put(extra, "", style.clone());
+                }
+            }
+            column += width;
+        }
+    }
+
+    // Remove the `left` columns after the margin, to line the row up with a
+    // line of source that's been cut
+    fn cut(&mut self, margin: usize, left: usize) {
+        if self.cells.len() > margin {
+            let end = (margin + left).min(self.cells.len());
+            self.cells.drain(margin..end);
+        }
+    }
+
+    // Shorten a line of source to `width` columns after the margin, with
+    // `...` where it's been cut
+    fn trim(&mut self, margin: usize, cut_left: bool, width: usize) {
+        if cut_left {
+            self.fill(margin, margin + 3, '.', Style::Plain);
+        }
+        if self.cells.len() > margin + width {
+            let end = margin + width;
+            self.cells.truncate(end);
+            self.fill(end - 3, end, '.', Style::Plain);
+            // A wide character can't be drawn without its second column
+            let (cell, _) = &mut self.cells[end - 4];
+            if cell.chars().next().is_some_and(|c| char_width(c) > 1) {
+                *cell = " ".to_string();
+            }
+        }
+    }
+
+    // Runs of the same style are painted together
+    fn render(&self, color: bool) -> String {
+        let mut out = String::new();
+        let mut run = String::new();
+        let mut style = Style::Plain;
+        for (cell, cell_style) in &self.cells {
+            if *cell_style != style && !run.is_empty() {
+                out.push_str(&paint(style, &run, color));
+                run.clear();
+            }
+            style = cell_style.clone();
+            run.push_str(cell);
+        }
+        out.push_str(&paint(style, &run, color));
+        out.trim_end().to_string()
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Style {
+    Plain,
+    Bold,
+    // Line numbers, secondary labels and footers
+    Gutter,
+    // The header and primary label
+    Level(Level),
+}
+
+fn paint(style: Style, text: &str, color: bool) -> String {
+    let code = match style {
+        _ if !color => return text.to_string(),
+        Style::Plain => return text.to_string(),
+        Style::Bold => "1",
+        Style::Gutter => "1;34",
+        Style::Level(Level::Error) => "1;31",
+        Style::Level(Level::Warning) => "1;33",
+        Style::Level(Level::Info) => "1;32",
+    };
+    format!("\x1B[{code}m{text}\x1B[0m")
+}
@@ -0,0 +1,209 @@
+//! Diagnostics as a SARIF 2.1.0 log, for code scanning dashboards.
+
+use std::collections::BTreeMap;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::{json, Value as Json};
+use url::Url;
+
+use crate::diagnostics::codes::Code;
+use crate::diagnostics::json::line_column;
+use crate::diagnostics::{Diagnostic, Level, Suggestion};
+use crate::queries::SourceFile;
+use crate::span::Span;
+use crate::Db;
+
+pub const SARIF_VERSION: &str = "2.1.0";
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+// The rule of diagnostics that don't have a more specific one
+const DEFAULT_RULE: &str = "haml";
+
+// Everything but the unreserved characters of RFC 3986 is encoded in the
+// segments of relative URIs
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Collects diagnostics into a SARIF log with a single run
+#[derive(Default)]
+pub struct SarifLog {
+    // Rule descriptors by ID, and the order they were first seen in, which
+    // results refer to them by
+    rules: BTreeMap<String, (usize, Json)>,
+    results: Vec<Json>,
+}
+
+impl SarifLog {
+    /// Add a diagnostic found while checking `file`. Diagnostics without a
+    /// span are located at `file` as a whole.
+    pub fn add(&mut self, db: &dyn Db, file: SourceFile, diagnostic: &Diagnostic) {
+        let rule_id = diagnostic.code.map_or(DEFAULT_RULE, |code| code.name);
+        let next_index = self.rules.len();
+        let (rule_index, _) = self
+            .rules
+            .entry(rule_id.to_string())
+            .or_insert_with(|| (next_index, rule(diagnostic.code)));
+
+        let primary = match diagnostic.span {
+            Some(span) => location(db, span, diagnostic.span_message.as_deref()),
+            None => json!({ "physicalLocation": { "artifactLocation": artifact(db, file) } }),
+        };
+        // Notes and help have nowhere else to go, so they follow the message
+        let mut message = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            message.push_str(&format!("\nnote: {note}"));
+        }
+        for help in &diagnostic.help {
+            message.push_str(&format!("\nhelp: {help}"));
+        }
+        let mut result = json!({
+            "ruleId": rule_id,
+            "ruleIndex": *rule_index,
+            "level": level(&diagnostic.level),
+            "message": { "text": message },
+            "locations": [primary],
+        });
+        if !diagnostic.labels.is_empty() {
+            let related: Vec<_> = diagnostic
+                .labels
+                .iter()
+                .enumerate()
+                .map(|(id, label)| {
+                    let mut related = location(db, label.span, Some(&label.message));
+                    related["id"] = json!(id);
+                    related
+                })
+                .collect();
+            result["relatedLocations"] = json!(related);
+        }
+        if !diagnostic.suggestions.is_empty() {
+            let fixes: Vec<_> = diagnostic
+                .suggestions
+                .iter()
+                .map(|suggestion| fix(db, suggestion))
+                .collect();
+            result["fixes"] = json!(fixes);
+        }
+        self.results.push(result);
+    }
+
+    /// The log, as a JSON document
+    pub fn to_json(&self) -> Json {
+        let mut rules: Vec<_> = self.rules.values().collect();
+        rules.sort_by_key(|(index, _)| *index);
+        let rules: Vec<_> = rules.into_iter().map(|(_, rule)| rule).collect();
+        json!({
+            "$schema": SARIF_SCHEMA,
+            "version": SARIF_VERSION,
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "haml",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    },
+                },
+                // Columns count characters, like the other formats
+                "columnKind": "unicodeCodePoints",
+                "results": self.results,
+            }],
+        })
+    }
+}
+
+// Rules are described by the explanation of their code
+fn rule(code: Option<Code>) -> Json {
+    let Some(code) = code else {
+        return json!({
+            "id": DEFAULT_RULE,
+            "shortDescription": { "text": "A problem found while checking a haml file" },
+        });
+    };
+    json!({
+        "id": code.name,
+        "name": code.title,
+        "shortDescription": { "text": code.title },
+        "help": { "text": code.explanation, "markdown": code.explanation },
+    })
+}
+
+fn level(level: &Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warning => "warning",
+        Level::Info => "note",
+    }
+}
+
+// A suggestion, with its edits grouped by the file they change
+fn fix(db: &dyn Db, suggestion: &Suggestion) -> Json {
+    let mut changes: Vec<(SourceFile, Vec<Json>)> = vec![];
+    for edit in &suggestion.edits {
+        let replacement = json!({
+            "deletedRegion": region(db, edit.span),
+            "insertedContent": { "text": edit.replacement },
+        });
+        match changes.iter_mut().find(|(file, _)| *file == edit.span.file) {
+            Some((_, replacements)) => replacements.push(replacement),
+            None => changes.push((edit.span.file, vec![replacement])),
+        }
+    }
+    let changes: Vec<_> = changes
+        .into_iter()
+        .map(|(file, replacements)| {
+            json!({
+                "artifactLocation": artifact(db, file),
+                "replacements": replacements,
+            })
+        })
+        .collect();
+    json!({
+        "description": { "text": suggestion.message },
+        "artifactChanges": changes,
+    })
+}
+
+fn location(db: &dyn Db, span: Span, message: Option<&str>) -> Json {
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": artifact(db, span.file),
+            "region": region(db, span),
+        },
+    });
+    if let Some(message) = message {
+        location["message"] = json!({ "text": message });
+    }
+    location
+}
+
+fn region(db: &dyn Db, span: Span) -> Json {
+    let (start_line, start_column) = line_column(db, span.file, span.start);
+    let (end_line, end_column) = line_column(db, span.file, span.end);
+    json!({
+        "startLine": start_line,
+        "startColumn": start_column,
+        "endLine": end_line,
+        "endColumn": end_column,
+    })
+}
+
+// Relative paths are relative URIs, which viewers resolve against the root
+// of the repository
+fn artifact(db: &dyn Db, file: SourceFile) -> Json {
+    let path = file.path(db);
+    if let Ok(uri) = Url::from_file_path(path) {
+        return json!({ "uri": uri.as_str() });
+    }
+    let segments: Vec<_> = path
+        .components()
+        .map(|component| {
+            let segment = component.as_os_str().to_string_lossy();
+            utf8_percent_encode(&segment, SEGMENT).to_string()
+        })
+        .collect();
+    json!({ "uri": segments.join("/") })
+}
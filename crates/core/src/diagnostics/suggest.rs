@@ -0,0 +1,78 @@
+//! "Did you mean" suggestions for names that don't resolve, taken from the
+//! names that do.
+
+use crate::diagnostics::{Applicability, Diagnostic, Suggestion};
+use crate::span::Span;
+
+impl Diagnostic {
+    /// Suggest replacing `name`, which is at `span` and doesn't resolve, with
+    /// the most similar of `candidates`. Nothing is suggested if none of them
+    /// is close enough to be what was meant.
+    pub fn with_similar_name<'a>(
+        self,
+        span: Span,
+        name: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        match similar_name(name, candidates) {
+            Some(similar) => self.with_suggestion(Suggestion::replace(
+                format!("did you mean `{similar}`?"),
+                span,
+                similar,
+                Applicability::MaybeIncorrect,
+            )),
+            None => self,
+        }
+    }
+}
+
+/// The candidate most like `name`, if any is close enough to be a typo of it.
+/// Names that only differ in case are the closest, and otherwise up to a third
+/// of the characters can be edited. Ties go to the first candidate in
+/// alphabetical order.
+pub fn similar_name<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        .filter_map(|candidate| {
+            let distance = match candidate.to_lowercase() == name.to_lowercase() {
+                true => 0,
+                false => edit_distance(name, candidate),
+            };
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The number of characters that have to be inserted, removed or replaced,
+/// or pairs of adjacent characters swapped, to turn one string into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // The distances between every prefix of `a` and every prefix of `b`
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
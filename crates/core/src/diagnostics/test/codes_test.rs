@@ -0,0 +1,47 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::db::Database;
+use crate::diagnostics::codes::{self, CODES};
+use crate::diagnostics::Diagnostics;
+use crate::queries::{check_file, SourceFile};
+
+#[test]
+fn test_codes_are_unique_and_explained() {
+    let mut names = HashSet::new();
+    for code in CODES {
+        assert!(names.insert(code.name), "{} is used twice", code.name);
+        assert!(!code.explanation.trim().is_empty(), "{}", code.name);
+    }
+    assert_eq!(CODES[0], codes::UNEXPECTED_TOKEN);
+    assert_eq!(CODES[1], codes::UNTERMINATED_STRING);
+}
+
+#[test]
+fn test_find_code_by_name() {
+    assert_eq!(codes::find("E0002"), Some(codes::UNTERMINATED_STRING));
+    assert_eq!(codes::find("e0002"), Some(codes::UNTERMINATED_STRING));
+    assert_eq!(codes::find("E2"), Some(codes::UNTERMINATED_STRING));
+    assert_eq!(codes::find("0002"), Some(codes::UNTERMINATED_STRING));
+    assert_eq!(codes::find("E9999"), None);
+    assert_eq!(codes::find(""), None);
+}
+
+#[test]
+fn test_diagnostics_have_codes() {
+    let db = Database::default();
+    let text = r#"
+        struct Folder { parent: Missing; }
+        constructor file { size: uint32; }
+        file Readme { size: "large", nmae: "README" }
+        import "missing.haml";
+    "#;
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    check_file(&db, file);
+    let codes: Vec<_> = check_file::accumulated::<Diagnostics>(&db, file)
+        .iter()
+        .map(|diagnostic| diagnostic.code.map(|code| code.name))
+        .collect();
+    assert!(!codes.is_empty());
+    assert!(codes.iter().all(Option::is_some), "{codes:?}");
+}
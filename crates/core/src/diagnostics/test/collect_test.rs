@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::db::Database;
+use crate::diagnostics::{
+    codes, diagnostics_for, diagnostics_for_workspace, Level, MAX_DIAGNOSTICS_PER_FILE,
+};
+use crate::lint::{Config, LintLevel, UNUSED_IMPORT, UNUSED_STRUCT};
+use crate::queries::{SourceFile, TrackedConfig};
+use crate::test::support::{project, read};
+
+fn file_name(db: &Database, file: SourceFile) -> String {
+    file.path(db)
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn test_diagnostics_in_shared_imports_are_reported_once() {
+    let dir = project(
+        "shared",
+        &[
+            ("a.haml", "import \"common\";\nstruct A { b: Common; }\n"),
+            ("b.haml", "import \"common\";\nstruct B { c: Common; }\n"),
+            ("common.haml", "import \"missing\";\nstruct Common {}\n"),
+        ],
+    );
+    let db = Database::default();
+    let config = Config {
+        lints: BTreeMap::from([(UNUSED_STRUCT.name.to_string(), LintLevel::Warn)]),
+    };
+    let a = read(&db, &dir.join("a.haml"));
+    let b = read(&db, &dir.join("b.haml"));
+    let common = read(&db, &dir.join("common.haml"));
+
+    // `Common` is used by the files that import it
+    let groups = diagnostics_for_workspace(&db, &[(b, &config), (a, &config), (common, &config)]);
+    let summary: Vec<_> = groups
+        .iter()
+        .map(|(file, diagnostics)| {
+            let codes: Vec<_> = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.code.map_or("", |code| code.name))
+                .collect();
+            (file_name(&db, *file), codes)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("a.haml".to_string(), vec![UNUSED_STRUCT.name]),
+            ("b.haml".to_string(), vec![UNUSED_STRUCT.name]),
+            (
+                "common.haml".to_string(),
+                vec![codes::UNRESOLVED_IMPORT.name]
+            ),
+        ]
+    );
+
+    // A file on its own is grouped the same way, with the files it imports
+    let config = TrackedConfig::new(&db, config);
+    let groups = diagnostics_for(&db, a, config);
+    let summary: Vec<_> = groups
+        .iter()
+        .map(|(file, diagnostics)| (file_name(&db, *file), diagnostics.len()))
+        .collect();
+    assert_eq!(
+        summary,
+        [("a.haml".to_string(), 1), ("common.haml".to_string(), 1)]
+    );
+    assert_eq!(groups[0].1[0].message, "struct `A` is never used");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_diagnostics_are_sorted_and_capped_per_file() {
+    let db = Database::default();
+    let count = MAX_DIAGNOSTICS_PER_FILE + 20;
+    let text = "struct A {}\n".repeat(count + 1);
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text);
+    let config = Config {
+        lints: BTreeMap::from([
+            (UNUSED_STRUCT.name.to_string(), LintLevel::Allow),
+            (UNUSED_IMPORT.name.to_string(), LintLevel::Allow),
+        ]),
+    };
+
+    let config = TrackedConfig::new(&db, config);
+    let [(_, diagnostics)] = &diagnostics_for(&db, file, config)[..] else {
+        panic!("expected diagnostics in one file");
+    };
+    assert_eq!(diagnostics.len(), MAX_DIAGNOSTICS_PER_FILE + 1);
+    let starts: Vec<_> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| diagnostic.span.map(|span| span.start))
+        .collect();
+    assert_eq!(starts.len(), MAX_DIAGNOSTICS_PER_FILE);
+    assert!(starts.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let last = diagnostics.last().unwrap();
+    assert_eq!(last.level, Level::Error);
+    assert_eq!(last.message, "20 more diagnostics aren't shown");
+    assert!(last.span.is_none());
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use crate::db::Database;
+use crate::diagnostics::{apply_fixes, Applicability, Diagnostic, Diagnostics, Level, Suggestion};
+use crate::queries::{parse_file, SourceFile};
+use crate::span::Span;
+
+fn fix(text: &str) -> (String, usize) {
+    let db = Database::default();
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text.to_string());
+    parse_file(&db, file);
+    let diagnostics = parse_file::accumulated::<Diagnostics>(&db, file);
+    match apply_fixes(&db, &diagnostics).pop() {
+        Some(fixed) => (fixed.text, fixed.fixes),
+        None => (text.to_string(), 0),
+    }
+}
+
+#[test]
+fn test_fix_replaces_commas_after_fields() {
+    let (text, fixes) = fix("struct Foo {\n  bar: string,\n  baz: string,\n}\n");
+    assert_eq!(text, "struct Foo {\n  bar: string;\n  baz: string;\n}\n");
+    assert_eq!(fixes, 2);
+}
+
+#[test]
+fn test_fix_replaces_semicolons_in_objects() {
+    let spec = "constructor api {\n  name: string;\n}\n\n";
+    let (text, fixes) = fix(&format!("{spec}api Foo {{\n  name: \"foo\";\n}}\n"));
+    assert_eq!(text, format!("{spec}api Foo {{\n  name: \"foo\",\n}}\n"));
+    assert_eq!(fixes, 1);
+}
+
+#[test]
+fn test_fix_suggests_closing_strings_on_their_first_line() {
+    // Where the quote belongs is a guess, so it's suggested but not applied
+    let text = "import \"common.haml;\n\nstruct Foo {}\n";
+    assert_eq!(fix(text), (text.to_string(), 0));
+
+    let db = Database::default();
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text.to_string());
+    parse_file(&db, file);
+    let diagnostics = parse_file::accumulated::<Diagnostics>(&db, file);
+    let suggestion = &diagnostics[0].suggestions[0];
+    assert_eq!(suggestion.applicability, Applicability::MaybeIncorrect);
+    let edit = &suggestion.edits[0];
+    assert_eq!(edit.span.start, "import \"common.haml".len());
+    assert_eq!(edit.replacement, "\"");
+}
+
+#[test]
+fn test_fix_closes_braces_at_the_end_of_the_file() {
+    let (text, fixes) = fix("struct Foo {\n  bar: string;\n");
+    assert_eq!(text, "struct Foo {\n  bar: string;\n}\n");
+    assert_eq!(fixes, 1);
+}
+
+#[test]
+fn test_fix_skips_overlapping_and_uncertain_suggestions() {
+    let db = Database::default();
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), "abc".to_string());
+    let span = Span::new(1, 2, file);
+    let suggest = |replacement: &str, applicability| {
+        Diagnostic::new(Level::Error, "bad".to_string(), Some(span), None)
+            .with_suggestion(Suggestion::replace("fix", span, replacement, applicability))
+    };
+    let diagnostics = [
+        suggest("x", Applicability::MaybeIncorrect),
+        suggest("y", Applicability::MachineApplicable),
+        suggest("y", Applicability::MachineApplicable),
+        suggest("z", Applicability::MachineApplicable),
+    ];
+    let fixed = apply_fixes(&db, &diagnostics);
+    assert_eq!(fixed.len(), 1);
+    assert_eq!(fixed[0].text, "ayc");
+    assert_eq!(fixed[0].fixes, 1);
+}
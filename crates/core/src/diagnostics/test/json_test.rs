@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use crate::db::Database;
+use crate::diagnostics::{codes, Applicability, Diagnostic, Level, Suggestion};
+use crate::queries::SourceFile;
+use crate::span::Span;
+
+#[test]
+fn test_json_has_one_based_ranges_and_labels() {
+    let db = Database::default();
+    let text = "struct A {}\nstruct é {}\n";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let span = Span::new(19, 21, file);
+    let diagnostic = Diagnostic::new(
+        Level::Error,
+        "bad name".to_string(),
+        Some(span),
+        Some("here".to_string()),
+    )
+    .with_code(codes::DUPLICATE_DEFINITION)
+    .with_label(Span::new(7, 8, file), "first")
+    .with_note("names are global")
+    .with_suggestion(Suggestion::replace(
+        "rename it",
+        span,
+        "e",
+        Applicability::MaybeIncorrect,
+    ));
+    let range = json!({
+        "start": { "line": 2, "column": 8 },
+        "end": { "line": 2, "column": 9 },
+    });
+    let first = json!({
+        "start": { "line": 1, "column": 8 },
+        "end": { "line": 1, "column": 9 },
+    });
+    assert_eq!(
+        diagnostic.to_json(&db, &file),
+        json!({
+            "level": "error",
+            "code": "E0006",
+            "message": "bad name",
+            "file": "spec.haml",
+            "range": range,
+            "labels": [
+                { "message": "here", "primary": true, "file": "spec.haml", "range": range },
+                { "message": "first", "primary": false, "file": "spec.haml", "range": first },
+            ],
+            "notes": ["names are global"],
+            "help": [],
+            "suggestions": [{
+                "message": "rename it",
+                "applicability": "maybe-incorrect",
+                "edits": [{ "file": "spec.haml", "range": range, "replacement": "e" }],
+            }],
+        })
+    );
+    assert_eq!(
+        diagnostic.to_short_message(&db, &file),
+        "spec.haml:2:8: error[E0006]: bad name"
+    );
+}
+
+#[test]
+fn test_diagnostics_without_spans_are_about_the_file() {
+    let db = Database::default();
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), String::new());
+    let diagnostic = Diagnostic::new(Level::Warning, "empty".to_string(), None, None);
+    assert_eq!(diagnostic.to_json(&db, &file)["range"], json!(null));
+    assert_eq!(
+        diagnostic.to_short_message(&db, &file),
+        "spec.haml: warning: empty"
+    );
+
+    // Spans at the end of an empty file still have a position
+    let eof = Diagnostic::new(
+        Level::Error,
+        "eof".to_string(),
+        Some(Span::new(0, 0, file)),
+        None,
+    );
+    assert_eq!(
+        eof.to_short_message(&db, &file),
+        "spec.haml:1:1: error: eof"
+    );
+}
@@ -0,0 +1,7 @@
+pub mod codes_test;
+pub mod collect_test;
+pub mod fix_test;
+pub mod json_test;
+pub mod render_test;
+pub mod sarif_test;
+pub mod suggest_test;
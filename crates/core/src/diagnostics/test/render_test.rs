@@ -0,0 +1,282 @@
+use std::path::PathBuf;
+
+use crate::db::Database;
+use crate::diagnostics::{codes, Diagnostic, Level, RenderOptions};
+use crate::queries::SourceFile;
+use crate::span::Span;
+
+// The rendered diagnostic, without colors
+fn render(db: &Database, diagnostic: &Diagnostic, file: SourceFile) -> String {
+    let message = diagnostic.to_user_message(db, &file);
+    let mut plain = String::new();
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+fn span(file: SourceFile, db: &Database, text: &str, nth: usize) -> Span {
+    let start = file
+        .text(db)
+        .match_indices(text)
+        .nth(nth)
+        .expect("text is in the file")
+        .0;
+    Span::new(start, start + text.len(), file)
+}
+
+#[test]
+fn test_labels_in_other_files_and_footers() {
+    let db = Database::default();
+    let text = "struct A {}\n\n\n\nstruct Folder {}\n";
+    let main = SourceFile::new(&db, PathBuf::from("main.haml"), text.to_string());
+    let common = SourceFile::new(
+        &db,
+        PathBuf::from("common.haml"),
+        "struct Folder {}\n".to_string(),
+    );
+    let diagnostic = Diagnostic::new(
+        Level::Error,
+        "`Folder` is defined multiple times".to_string(),
+        Some(span(main, &db, "Folder", 0)),
+        Some("redefined here".to_string()),
+    )
+    .with_code(codes::DUPLICATE_DEFINITION)
+    .with_label(span(common, &db, "Folder", 0), "first defined here")
+    .with_label(span(main, &db, "A", 0), "unrelated")
+    .with_note("names are global")
+    .with_help("rename one of them\nor remove it");
+
+    let expected = "\
+error[E0006]: `Folder` is defined multiple times
+ --> main.haml:5:8
+  |
+1 | struct A {}
+  |        - unrelated
+...
+5 | struct Folder {}
+  |        ^^^^^^ redefined here
+  |
+ ::: common.haml:1:8
+  |
+1 | struct Folder {}
+  |        ------ first defined here
+  |
+  = note: names are global
+  = help: rename one of them
+          or remove it";
+    assert_eq!(render(&db, &diagnostic, main), expected);
+}
+
+#[test]
+fn test_labels_on_one_line_hang_below_each_other() {
+    let db = Database::default();
+    let text = "file Readme { name: \"a\", name: \"b\" }";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let diagnostic = Diagnostic::new(
+        Level::Error,
+        "field `name` is specified more than once".to_string(),
+        Some(span(file, &db, "name", 1)),
+        Some("used more than once".to_string()),
+    )
+    .with_label(span(file, &db, "name", 0), "first specified here");
+
+    let expected = "\
+error: field `name` is specified more than once
+ --> spec.haml:1:26
+  |
+1 | file Readme { name: \"a\", name: \"b\" }
+  |               ----       ^^^^ used more than once
+  |               |
+  |               first specified here";
+    assert_eq!(render(&db, &diagnostic, file), expected);
+}
+
+#[test]
+fn test_multiline_spans_are_drawn_in_the_margin() {
+    let db = Database::default();
+    let text = "struct Folder {\n  name: string;\n}\nfile Readme {\n  name: \"a\",\n}\n";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let whole_line = Span::new(0, text.find("}\n").unwrap() + 1, file);
+    let start = text.find("{\n  name: \"a\"").unwrap();
+    let part_line = Span::new(start, text.len() - 1, file);
+    let diagnostic = Diagnostic::new(
+        Level::Warning,
+        "unused".to_string(),
+        Some(whole_line),
+        Some("never used".to_string()),
+    )
+    .with_label(part_line, "this object");
+
+    let expected = "\
+warning: unused
+ --> spec.haml:1:1
+  |
+1 | / struct Folder {
+2 | |   name: string;
+3 | | }
+  | |_^ never used
+4 |   file Readme {
+  |  _____________-
+5 | |   name: \"a\",
+6 | | }
+  | |_- this object";
+    assert_eq!(render(&db, &diagnostic, file), expected);
+}
+
+#[test]
+fn test_carets_line_up_under_wide_characters_and_tabs() {
+    let db = Database::default();
+    let text = "file Readme { 名前: \"日本\",\tnom: é }";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let diagnostic = Diagnostic::new(
+        Level::Error,
+        "unknown value".to_string(),
+        Some(span(file, &db, "é", 0)),
+        Some("not defined".to_string()),
+    )
+    .with_label(span(file, &db, "\"日本\"", 0), "a string")
+    .with_label(span(file, &db, "名前", 0), "a field");
+
+    let expected = "\
+error: unknown value
+ --> spec.haml:1:30
+  |
+1 | file Readme { 名前: \"日本\",    nom: é }
+  |               ----  ------          ^ not defined
+  |               |     |
+  |               |     a string
+  |               |
+  |               a field";
+    assert_eq!(render(&db, &diagnostic, file), expected);
+}
+
+#[test]
+fn test_crlf_line_breaks_are_not_drawn() {
+    let db = Database::default();
+    let text = "struct A {}\r\nstruct B {}\r\n";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let diagnostic = Diagnostic::new(
+        Level::Warning,
+        "struct is never used".to_string(),
+        Some(span(file, &db, "B", 0)),
+        None,
+    );
+
+    let expected = "\
+warning: struct is never used
+ --> spec.haml:2:8
+  |
+2 | struct B {}
+  |        ^";
+    assert_eq!(render(&db, &diagnostic, file), expected);
+}
+
+#[test]
+fn test_spans_at_the_end_of_a_file_after_its_last_line() {
+    let db = Database::default();
+    let text = "struct Folder {\r\n";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let diagnostic = Diagnostic::new(
+        Level::Error,
+        "unexpected end of file".to_string(),
+        Some(Span::new(text.len(), text.len(), file)),
+        Some("expected `}`".to_string()),
+    )
+    .with_label(span(file, &db, "{", 0), "unclosed");
+
+    let expected = "\
+error: unexpected end of file
+ --> spec.haml:2:1
+  |
+1 | struct Folder {
+  |               - unclosed
+2 | 
+  | ^ expected `}`";
+    assert_eq!(render(&db, &diagnostic, file), expected);
+}
+
+#[test]
+fn test_diagnostics_without_spans_name_the_file() {
+    let db = Database::default();
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), String::new());
+    let diagnostic = Diagnostic::new(Level::Error, "empty".to_string(), None, None)
+        .with_help("add a declaration");
+    assert_eq!(
+        render(&db, &diagnostic, file),
+        "error: empty\n--> spec.haml\n = help: add a declaration"
+    );
+
+    // Spans at the end of an empty file are still drawn
+    let eof = Diagnostic::new(
+        Level::Error,
+        "eof".to_string(),
+        Some(Span::new(0, 0, file)),
+        Some("here".to_string()),
+    );
+    assert_eq!(
+        render(&db, &eof, file),
+        "error: eof\n --> spec.haml:1:1\n  |\n1 | \n  | ^ here"
+    );
+}
+
+#[test]
+fn test_long_lines_are_trimmed_around_their_labels() {
+    let db = Database::default();
+    let padding = "a: 1, ".repeat(20);
+    let text = format!("file Readme {{ {padding}name: \"a\", {padding}}}");
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text);
+    let diagnostic = Diagnostic::new(
+        Level::Error,
+        "unknown field".to_string(),
+        Some(span(file, &db, "name", 0)),
+        Some("not a field of `File`".to_string()),
+    );
+    let options = RenderOptions {
+        color: false,
+        max_width: Some(40),
+    };
+
+    let expected = "\
+error: unknown field
+ --> spec.haml:1:135
+  |
+1 | ...: 1, name: \"a\", a: 1, a: 1, a:...
+  |         ^^^^ not a field of `File`";
+    assert_eq!(diagnostic.render(&db, &file, &options), expected);
+
+    // Lines that fit aren't trimmed
+    let options = RenderOptions {
+        max_width: Some(400),
+        ..options
+    };
+    let rendered = diagnostic.render(&db, &file, &options);
+    assert!(rendered.contains("1 | file Readme { a: 1,"));
+}
+
+#[test]
+fn test_diagnostics_without_color_have_no_escape_codes() {
+    let db = Database::default();
+    let text = "struct A {}\n";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let diagnostic = Diagnostic::new(
+        Level::Warning,
+        "struct is never used".to_string(),
+        Some(span(file, &db, "A", 0)),
+        None,
+    )
+    .with_note("`unused_struct` is set to `warn` by default");
+    let options = RenderOptions {
+        color: false,
+        max_width: None,
+    };
+
+    let rendered = diagnostic.render(&db, &file, &options);
+    assert!(!rendered.contains('\x1B'));
+    assert_eq!(rendered, render(&db, &diagnostic, file));
+}
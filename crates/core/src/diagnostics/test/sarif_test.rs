@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use crate::db::Database;
+use crate::diagnostics::{codes, Diagnostic, Level, SarifLog, SARIF_VERSION};
+use crate::queries::SourceFile;
+use crate::span::Span;
+
+#[test]
+fn test_sarif_log_has_rules_results_and_locations() {
+    let db = Database::default();
+    let file = SourceFile::new(
+        &db,
+        PathBuf::from("spec/api.haml"),
+        "struct A {}".to_string(),
+    );
+    let mut log = SarifLog::default();
+    let error = Diagnostic::new(
+        Level::Error,
+        "bad struct".to_string(),
+        Some(Span::new(7, 8, file)),
+        Some("here".to_string()),
+    )
+    .with_code(codes::UNKNOWN_TYPE)
+    .with_label(Span::new(0, 6, file), "in this struct")
+    .with_help("declare the type");
+    let info = Diagnostic::new(Level::Info, "a note".to_string(), None, None);
+    log.add(&db, file, &error);
+    log.add(&db, file, &info);
+
+    let sarif = log.to_json();
+    assert_eq!(sarif["version"], SARIF_VERSION);
+    let run = &sarif["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "haml");
+    let rules = &run["tool"]["driver"]["rules"];
+    assert_eq!(rules[0]["id"], "E0009");
+    assert_eq!(rules[0]["shortDescription"]["text"], "cannot find type");
+    assert_eq!(rules[1]["id"], "haml");
+
+    let results = run["results"].as_array().unwrap();
+    assert_eq!(results[0]["ruleId"], "E0009");
+    assert_eq!(results[0]["ruleIndex"], 0);
+    assert_eq!(results[0]["level"], "error");
+    assert_eq!(
+        results[0]["locations"][0],
+        json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": "spec/api.haml" },
+                "region": { "startLine": 1, "startColumn": 8, "endLine": 1, "endColumn": 9 },
+            },
+            "message": { "text": "here" },
+        })
+    );
+    assert_eq!(
+        results[0]["message"]["text"],
+        "bad struct\nhelp: declare the type"
+    );
+    let related = &results[0]["relatedLocations"][0];
+    assert_eq!(related["id"], 0);
+    assert_eq!(related["message"]["text"], "in this struct");
+    assert_eq!(related["physicalLocation"]["region"]["endColumn"], 7);
+    assert_eq!(results[1]["ruleIndex"], 1);
+    assert_eq!(results[1]["level"], "note");
+    assert_eq!(
+        results[1]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+        "spec/api.haml"
+    );
+}
+
+#[test]
+fn test_artifact_uris_are_percent_encoded() {
+    let db = Database::default();
+    let uri = |path: &str| {
+        let file = SourceFile::new(&db, PathBuf::from(path), "struct A {}".to_string());
+        let error = Diagnostic::new(
+            Level::Error,
+            "bad struct".to_string(),
+            Some(Span::new(7, 8, file)),
+            None,
+        );
+        let mut log = SarifLog::default();
+        log.add(&db, file, &error);
+        let sarif = log.to_json();
+        let location = &sarif["runs"][0]["results"][0]["locations"][0];
+        location["physicalLocation"]["artifactLocation"]["uri"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(uri("spec/my api#1.haml"), "spec/my%20api%231.haml");
+    assert_eq!(uri("../spec/100%.haml"), "../spec/100%25.haml");
+    assert_eq!(uri("/tmp/my spec.haml"), "file:///tmp/my%20spec.haml");
+}
@@ -0,0 +1,38 @@
+use crate::diagnostics::{edit_distance, similar_name};
+
+#[test]
+fn test_edit_distance_counts_characters() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("folder", ""), 6);
+    assert_eq!(edit_distance("folder", "folder"), 0);
+    assert_eq!(edit_distance("Requets", "Request"), 1);
+    assert_eq!(edit_distance("nmae", "name"), 1);
+    assert_eq!(edit_distance("depreciated", "deprecated"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("naïve", "naive"), 1);
+}
+
+#[test]
+fn test_similar_name_picks_the_closest_close_enough_name() {
+    let names = ["GetFolderRequest", "GetFolderResponse", "Folder"];
+    assert_eq!(
+        similar_name("GetFolderRequets", names),
+        Some("GetFolderRequest")
+    );
+    assert_eq!(similar_name("Foldr", names), Some("Folder"));
+    assert_eq!(similar_name("File", names), None);
+
+    // Differences in case are closest, and the name itself isn't similar
+    assert_eq!(
+        similar_name("folder", ["Folders", "Folder"]),
+        Some("Folder")
+    );
+    assert_eq!(similar_name("Folder", ["Folder"]), None);
+
+    // Short names can only be one edit away
+    assert_eq!(similar_name("id", ["ids", "name"]), Some("ids"));
+    assert_eq!(similar_name("id", ["idx_"]), None);
+
+    // Ties go to the first name alphabetically
+    assert_eq!(similar_name("bat", ["cat", "bar"]), Some("bar"));
+}
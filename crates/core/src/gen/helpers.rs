@@ -0,0 +1,95 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use heck::{ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+
+/// The casing conventions of identifiers in generated code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Case {
+    /// `FolderName`
+    Pascal,
+    /// `folderName`
+    Camel,
+    /// `folder_name`
+    Snake,
+    /// `FOLDER_NAME`
+    ScreamingSnake,
+    /// `folder-name`
+    Kebab,
+}
+
+impl Case {
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            Case::Pascal => name.to_upper_camel_case(),
+            Case::Camel => name.to_lower_camel_case(),
+            Case::Snake => name.to_snake_case(),
+            Case::ScreamingSnake => name.to_shouty_snake_case(),
+            Case::Kebab => name.to_kebab_case(),
+        }
+    }
+}
+
+/// The names to import from each module, so imports come out in the same
+/// order however they were added
+#[derive(Clone, Debug, Default)]
+pub struct Imports {
+    modules: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Imports {
+    pub fn add(&mut self, module: impl Into<String>, name: impl Into<String>) {
+        self.modules
+            .entry(module.into())
+            .or_default()
+            .insert(name.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Each module with the names imported from it, both in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Vec<&str>)> {
+        self.modules
+            .iter()
+            .map(|(module, names)| (module.as_str(), names.iter().map(String::as_str).collect()))
+    }
+}
+
+/// Turn doc comment text into line comments starting with `marker`, like
+/// `///` for Rust. Blank lines don't get trailing whitespace.
+pub fn line_comment(text: &str, marker: &str, indent: &str) -> String {
+    let mut comment = String::new();
+    for line in text.lines() {
+        comment.push_str(indent);
+        comment.push_str(marker);
+        if !line.is_empty() {
+            comment.push(' ');
+            comment.push_str(line);
+        }
+        comment.push('\n');
+    }
+    comment
+}
+
+/// Turn doc comment text into a `/** ... */` block comment, as used by JSDoc
+/// and Javadoc. A single line fits on one line.
+pub fn block_comment(text: &str, indent: &str) -> String {
+    let lines: Vec<_> = text.lines().collect();
+    if let [line] = lines.as_slice() {
+        return format!("{indent}/** {line} */\n");
+    }
+    let mut comment = format!("{indent}/**\n");
+    for line in lines {
+        let line = if line.is_empty() {
+            format!("{indent} *")
+        } else {
+            format!("{indent} * {line}")
+        };
+        comment.push_str(&line);
+        comment.push('\n');
+    }
+    comment.push_str(indent);
+    comment.push_str(" */\n");
+    comment
+}
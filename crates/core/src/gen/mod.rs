@@ -0,0 +1,137 @@
+//! The interface code generators implement, and helpers for writing them.
+//! Backends are found by name in a [`Registry`], so a crate can add its own by
+//! registering it alongside the built-in ones.
+
+mod helpers;
+#[cfg(test)]
+mod test;
+mod wire;
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Level};
+use crate::queries::{build_symbol_table, check_file, SourceFile};
+use crate::sema::{Instance, SymbolTable};
+use crate::Db;
+
+pub use helpers::{block_comment, line_comment, Case, Imports};
+pub use wire::{
+    plugin_request, PluginDiagnostic, PluginLocation, PluginResponse, MODEL_VERSION,
+};
+
+/// Options for a backend, like `root = Folder`
+pub type Options = BTreeMap<String, String>;
+
+/// A spec that parsed, resolved its imports and type-checked without errors
+#[derive(Clone, Debug)]
+pub struct Model {
+    /// The file the spec was loaded from
+    pub file: SourceFile,
+    /// The declarations of the file and the files it imports
+    pub symbols: SymbolTable,
+    /// The evaluated instances of the file and the files it imports, in
+    /// import order
+    pub instances: Vec<Instance>,
+}
+
+impl Model {
+    /// Check `file` and build its model. Returns the errors found while
+    /// checking if there were any.
+    pub fn new(db: &dyn Db, file: SourceFile) -> Result<Model, Vec<Diagnostic>> {
+        let instances = check_file(db, file);
+        let errors: Vec<_> = check_file::accumulated::<Diagnostics>(db, file)
+            .into_iter()
+            .filter(|diagnostic| matches!(diagnostic.level, Level::Error))
+            .collect();
+        match (instances, build_symbol_table(db, file)) {
+            (Some(instances), Some(symbols)) if errors.is_empty() => Ok(Model {
+                file,
+                symbols,
+                instances,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// A file written by a backend
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct OutputFile {
+    /// Where to write the file, relative to the output directory
+    pub path: String,
+    pub contents: String,
+}
+
+impl OutputFile {
+    pub fn new(path: impl Into<String>, contents: impl Into<String>) -> OutputFile {
+        OutputFile {
+            path: path.into(),
+            contents: contents.into(),
+        }
+    }
+}
+
+/// A backend that turns a checked spec into files
+pub trait Generator {
+    /// The name the backend is selected by, like `rust`
+    fn name(&self) -> &str;
+
+    /// What the backend generates, in a line
+    fn description(&self) -> &str;
+
+    /// Generate files for `model`. Options the backend doesn't know about
+    /// should be reported as errors rather than ignored.
+    fn generate(
+        &self,
+        db: &dyn Db,
+        model: &Model,
+        options: &Options,
+    ) -> Result<Vec<OutputFile>, Vec<Diagnostic>>;
+}
+
+/// Backends by name
+#[derive(Default)]
+pub struct Registry {
+    generators: BTreeMap<String, Box<dyn Generator>>,
+}
+
+impl Registry {
+    /// Add a backend. Returns the backend it replaces if there was already one
+    /// with the same name.
+    pub fn register(&mut self, generator: impl Generator + 'static) -> Option<Box<dyn Generator>> {
+        let name = generator.name().to_string();
+        self.generators.insert(name, Box::new(generator))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Generator> {
+        self.generators.get(name).map(Box::as_ref)
+    }
+
+    /// Every backend, ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Generator> {
+        self.generators.values().map(Box::as_ref)
+    }
+}
+
+/// An error about the options given to a backend
+pub fn option_error(message: impl Into<String>) -> Vec<Diagnostic> {
+    vec![Diagnostic::new(Level::Error, message.into(), None, None)]
+}
+
+/// Report any of `options` that aren't in `known`
+pub fn check_options(options: &Options, known: &[&str]) -> Result<(), Vec<Diagnostic>> {
+    let unknown: Vec<_> = options
+        .keys()
+        .filter(|key| !known.contains(&key.as_str()))
+        .map(|key| format!("`{key}`"))
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    Err(option_error(format!(
+        "unknown option {}",
+        unknown.join(", ")
+    )))
+}
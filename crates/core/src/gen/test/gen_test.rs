@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use crate::db::Database;
+use crate::diagnostics::Diagnostic;
+use crate::gen::{
+    block_comment, check_options, line_comment, Case, Generator, Imports, Model, Options,
+    OutputFile, Registry, MODEL_VERSION,
+};
+use crate::queries::SourceFile;
+use crate::Db;
+
+struct Names;
+
+impl Generator for Names {
+    fn name(&self) -> &str {
+        "names"
+    }
+
+    fn description(&self) -> &str {
+        "The name of every declaration"
+    }
+
+    fn generate(
+        &self,
+        _db: &dyn Db,
+        model: &Model,
+        options: &Options,
+    ) -> Result<Vec<OutputFile>, Vec<Diagnostic>> {
+        check_options(options, &["case"])?;
+        let case = match options.get("case").map(String::as_str) {
+            Some("snake") => Case::Snake,
+            _ => Case::Pascal,
+        };
+        let names: Vec<_> = model
+            .symbols
+            .iter()
+            .map(|(name, _)| case.apply(name))
+            .collect();
+        Ok(vec![OutputFile::new("names.txt", names.join("\n"))])
+    }
+}
+
+#[test]
+fn test_registry_selects_backends_by_name() {
+    let mut registry = Registry::default();
+    assert!(registry.register(Names).is_none());
+    assert!(registry.register(Names).is_some());
+    assert!(registry.get("other").is_none());
+
+    let names: Vec<_> = registry.iter().map(|generator| generator.name()).collect();
+    assert_eq!(names, ["names"]);
+
+    let db = Database::default();
+    let text = "struct FolderName {} constructor file_name {}";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let model = Model::new(&db, file).unwrap();
+    let options = Options::from([("case".to_string(), "snake".to_string())]);
+    let files = registry
+        .get("names")
+        .unwrap()
+        .generate(&db, &model, &options)
+        .unwrap();
+    assert_eq!(
+        files,
+        [OutputFile::new("names.txt", "folder_name\nfile_name")]
+    );
+
+    let options = Options::from([("root".to_string(), "Folder".to_string())]);
+    let errors = Names.generate(&db, &model, &options).unwrap_err();
+    assert_eq!(errors[0].message, "unknown option `root`");
+}
+
+#[test]
+fn test_model_is_only_built_without_errors() {
+    let db = Database::default();
+    let text = "struct Folder { parent: Missing; }";
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let errors = Model::new(&db, file).unwrap_err();
+    assert!(!errors.is_empty());
+}
+
+#[test]
+fn test_cases() {
+    assert_eq!(Case::Pascal.apply("folder_name"), "FolderName");
+    assert_eq!(Case::Camel.apply("folder_name"), "folderName");
+    assert_eq!(Case::Snake.apply("FolderName"), "folder_name");
+    assert_eq!(Case::ScreamingSnake.apply("folderName"), "FOLDER_NAME");
+    assert_eq!(Case::Kebab.apply("FolderName"), "folder-name");
+}
+
+#[test]
+fn test_imports_are_ordered() {
+    let mut imports = Imports::default();
+    imports.add("./spec", "Folder");
+    imports.add("../common", "Link");
+    imports.add("./spec", "File");
+    imports.add("./spec", "Folder");
+    let imports: Vec<_> = imports.iter().collect();
+    assert_eq!(
+        imports,
+        [
+            ("../common", vec!["Link"]),
+            ("./spec", vec!["File", "Folder"])
+        ]
+    );
+}
+
+#[test]
+fn test_doc_comments() {
+    assert_eq!(
+        line_comment("A folder\n\nOf files", "///", "    "),
+        "    /// A folder\n    ///\n    /// Of files\n"
+    );
+    assert_eq!(block_comment("A folder", ""), "/** A folder */\n");
+    assert_eq!(
+        block_comment("A folder\n\nOf files", "  "),
+        "  /**\n   * A folder\n   *\n   * Of files\n   */\n"
+    );
+}
+
+#[test]
+fn test_model_json_is_versioned() {
+    let db = Database::default();
+    let text = r#"
+        package api.spec;
+
+        /// An endpoint
+        constructor endpoint {
+            @deprecated(since = 2)
+            response: Folder;
+            tags?: map<string, uint32>;
+        }
+
+        struct Folder { name: string; }
+
+        endpoint GetFolder {
+            response: {
+                name: "root",
+            },
+        }
+    "#;
+    let file = SourceFile::new(&db, PathBuf::from("spec.haml"), text.to_string());
+    let json = Model::new(&db, file).unwrap().to_json(&db);
+    assert_eq!(json["version"], MODEL_VERSION);
+
+    let endpoint = &json["declarations"][1];
+    assert_eq!(endpoint["kind"], "constructor");
+    assert_eq!(endpoint["type_name"], "Endpoint");
+    assert_eq!(endpoint["package"], serde_json::json!(["api", "spec"]));
+    assert_eq!(endpoint["docs"], "An endpoint");
+
+    let fields = &endpoint["block"]["fields"];
+    assert_eq!(
+        fields[0]["type"],
+        serde_json::json!({ "kind": "ref", "name": "Folder" })
+    );
+    assert_eq!(
+        fields[0]["annotations"],
+        serde_json::json!([{ "name": "deprecated", "args": { "since": 2 } }])
+    );
+    assert_eq!(fields[1]["optional"], true);
+    assert_eq!(fields[1]["type"]["kind"], "map");
+
+    let instance = &json["instances"][0];
+    assert_eq!(instance["constructor"], "endpoint");
+    assert_eq!(
+        instance["value"]["entries"][0]["value"]["entries"][0],
+        serde_json::json!({ "key": "name", "value": { "kind": "string", "value": "root" } })
+    );
+}
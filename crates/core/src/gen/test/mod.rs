@@ -0,0 +1 @@
+pub mod gen_test;
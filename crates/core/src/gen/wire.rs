@@ -0,0 +1,402 @@
+//! The JSON form of a [`Model`], and the protocol of plugins: code generators
+//! that run as another process. A plugin reads a [`plugin_request`] from stdin
+//! and writes a [`PluginResponse`] to stdout.
+//!
+//! The model's shape only changes along with [`MODEL_VERSION`], so it's
+//! documented here field by field.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "file": "spec/api.haml",
+//!   "declarations": [Declaration],
+//!   "instances": [Instance]
+//! }
+//! ```
+//!
+//! A `Declaration` has a `kind` of `struct`, `constructor` or `annotation`,
+//! its `name`, the `type_name` fields refer to it by (`Endpoint` for
+//! `constructor endpoint`), its `package` segments (or `null`), `docs` (or
+//! `null`), `annotations`, a `block`, and a `location`. Declarations are
+//! ordered by name.
+//!
+//! A `block` has a `kind` of:
+//! - `fields`, with `fields`
+//! - `repeatable`, a map of names to entries with `fields`
+//! - `map`, an alias with a `key` and `value` type
+//! - `union`, with a `discriminator` field name (or `null`) and `variants`,
+//!   which are fields
+//!
+//! A field has a `name`, `type`, `optional`, `docs`, `annotations` and
+//! `location`. A type has a `kind` of `string`, `uint32`, `uint64`, `int32`,
+//! `int64`, `float32`, `float64`, `unknown` or `struct`; or `map` with a `key`
+//! and `value`; or `ref` with the `name` of the declaration it refers to.
+//!
+//! An annotation has a `name` and `args`, an object of its arguments.
+//!
+//! An `Instance` has a `name`, the `constructor` it's made with, a `value`
+//! and a `location`. A value has a `kind` of `string`, `int`, `uint` or
+//! `float` with a `value`; `object` with `entries`, a list of `key` and
+//! `value` pairs in the order they're written; `ref` with the `name` of an
+//! instance; or `type` with the `name` of a struct.
+//!
+//! A `location` is the `file` and the byte offsets `start` and `end` of the
+//! declaration's name, so diagnostics about it can point there.
+
+use serde::Deserialize;
+use serde_json::{json, Map, Number, Value as Json};
+
+use crate::ast::node::{
+    AliasDecl, Annotation, AnnotationFieldDecl, AnnotationFieldValue, BlockDecl, DocComment,
+    FieldDecl, FieldType, Node, ValueExpr,
+};
+use crate::diagnostics::{Diagnostic, Level};
+use crate::gen::{Model, Options, OutputFile};
+use crate::queries::{parse_file, resolve_imports, SourceFile};
+use crate::sema::{type_name, Instance, Symbol, SymbolTable, Value};
+use crate::span::Span;
+use crate::Db;
+
+/// The version of the JSON form of a model. Changes that could break a reader
+/// of the previous version bump it.
+pub const MODEL_VERSION: u32 = 1;
+
+impl Model {
+    /// The model in its versioned JSON form
+    pub fn to_json(&self, db: &dyn Db) -> Json {
+        let writer = Writer {
+            db,
+            symbols: &self.symbols,
+        };
+        let declarations: Vec<_> = self
+            .symbols
+            .iter()
+            .filter_map(|(name, symbol)| writer.declaration(name, symbol))
+            .collect();
+        let instances: Vec<_> = self
+            .instances
+            .iter()
+            .map(|instance| writer.instance(instance))
+            .collect();
+        json!({
+            "version": MODEL_VERSION,
+            "file": self.file.path(db).to_string_lossy(),
+            "declarations": declarations,
+            "instances": instances,
+        })
+    }
+}
+
+/// What a plugin reads from stdin: the model, and the options it was given.
+///
+/// ```json
+/// { "model": { "version": 1, ... }, "options": { "key": "value" } }
+/// ```
+pub fn plugin_request(db: &dyn Db, model: &Model, options: &Options) -> Json {
+    json!({ "model": model.to_json(db), "options": options })
+}
+
+struct Writer<'db> {
+    db: &'db dyn Db,
+    symbols: &'db SymbolTable,
+}
+
+impl<'db> Writer<'db> {
+    fn declaration(&self, name: &str, symbol: &Symbol) -> Option<Json> {
+        let (kind, type_name, docs, annotations, block) = match symbol {
+            Symbol::Struct(decl) => (
+                "struct",
+                json!(name),
+                &decl.docs,
+                &decl.annotations,
+                self.block(&decl.content),
+            ),
+            Symbol::Constructor(decl) => (
+                "constructor",
+                json!(type_name(name)),
+                &decl.docs,
+                &decl.annotations,
+                self.block(&decl.content),
+            ),
+            Symbol::Annotation(decl) => (
+                "annotation",
+                Json::Null,
+                &decl.docs,
+                &decl.annotations,
+                self.annotation_fields(&decl.fields),
+            ),
+            Symbol::Instance(_) => return None,
+        };
+        let span = symbol.name().span;
+        Some(json!({
+            "kind": kind,
+            "name": name,
+            "type_name": type_name,
+            "package": package(self.db, span.file),
+            "docs": self.docs(docs),
+            "annotations": self.annotations(annotations),
+            "block": block,
+            "location": self.location(span),
+        }))
+    }
+
+    fn block(&self, block: &BlockDecl) -> Json {
+        match block {
+            BlockDecl::FieldSet(fields) => json!({
+                "kind": "fields",
+                "fields": self.fields(&fields.fields),
+            }),
+            BlockDecl::Repeatable(fields) => json!({
+                "kind": "repeatable",
+                "fields": self.fields(&fields.fields),
+            }),
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => json!({
+                "kind": "map",
+                "key": self.field_type(&map.key),
+                "value": self.field_type(&map.value),
+            }),
+            BlockDecl::Union(union) => json!({
+                "kind": "union",
+                "discriminator": union.discriminator.map(|token| token.text(self.db)),
+                "variants": self.fields(&union.variants.fields),
+            }),
+        }
+    }
+
+    fn fields(&self, fields: &[FieldDecl]) -> Vec<Json> {
+        fields
+            .iter()
+            .map(|field| {
+                json!({
+                    "name": field.name.text(self.db),
+                    "type": self.field_type(&field.typ),
+                    "optional": field.optional,
+                    "docs": self.docs(&field.docs),
+                    "annotations": self.annotations(&field.annotations),
+                    "location": self.location(field.name.span),
+                })
+            })
+            .collect()
+    }
+
+    // Annotation fields only have primitive types, and no docs or annotations
+    // of their own
+    fn annotation_fields(&self, fields: &[AnnotationFieldDecl]) -> Json {
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|field| {
+                let kind = match field.value {
+                    AnnotationFieldValue::String(_) => "string",
+                    AnnotationFieldValue::Uint32(_) => "uint32",
+                    AnnotationFieldValue::Uint64(_) => "uint64",
+                    AnnotationFieldValue::Int32(_) => "int32",
+                    AnnotationFieldValue::Int64(_) => "int64",
+                    AnnotationFieldValue::Float32(_) => "float32",
+                    AnnotationFieldValue::Float64(_) => "float64",
+                };
+                json!({
+                    "name": field.name.text(self.db),
+                    "type": { "kind": kind },
+                    "optional": field.optional,
+                    "docs": null,
+                    "annotations": [],
+                    "location": self.location(field.name.span),
+                })
+            })
+            .collect();
+        json!({ "kind": "fields", "fields": fields })
+    }
+
+    fn field_type(&self, typ: &FieldType) -> Json {
+        let kind = match typ {
+            FieldType::String(_) => "string",
+            FieldType::Uint32(_) => "uint32",
+            FieldType::Uint64(_) => "uint64",
+            FieldType::Int32(_) => "int32",
+            FieldType::Int64(_) => "int64",
+            FieldType::Float32(_) => "float32",
+            FieldType::Float64(_) => "float64",
+            FieldType::Unknown(_) => "unknown",
+            FieldType::Struct(_) => "struct",
+            FieldType::Map(map) => {
+                return json!({
+                    "kind": "map",
+                    "key": self.field_type(&map.key),
+                    "value": self.field_type(&map.value),
+                })
+            }
+            FieldType::Ident(token) => {
+                let name = token.text(self.db);
+                let name = match self.symbols.resolve_type(self.db, name) {
+                    Some(symbol) => symbol.name().text(self.db),
+                    None => name,
+                };
+                return json!({ "kind": "ref", "name": name });
+            }
+        };
+        json!({ "kind": kind })
+    }
+
+    fn annotations(&self, annotations: &[Annotation]) -> Vec<Json> {
+        annotations
+            .iter()
+            .map(|annotation| {
+                let args: Map<_, _> = annotation
+                    .args
+                    .iter()
+                    .map(|arg| (arg.key.text(self.db).to_string(), self.arg(&arg.value)))
+                    .collect();
+                json!({ "name": annotation.name.text(self.db), "args": args })
+            })
+            .collect()
+    }
+
+    // Arguments are plain JSON, with identifiers as strings
+    fn arg(&self, value: &ValueExpr) -> Json {
+        match value {
+            ValueExpr::String(token) => json!(token.unquoted(self.db)),
+            ValueExpr::Ident(token) => json!(token.text(self.db)),
+            ValueExpr::Int(token) | ValueExpr::Float(token) => {
+                let text = token.text(self.db);
+                serde_json::from_str::<Number>(text).map_or_else(|_| json!(text), Json::Number)
+            }
+            ValueExpr::Object(object) => {
+                let entries: Map<_, _> = object
+                    .entries
+                    .iter()
+                    .map(|entry| (entry.key.text(self.db).to_string(), self.arg(&entry.value)))
+                    .collect();
+                Json::Object(entries)
+            }
+        }
+    }
+
+    fn instance(&self, instance: &Instance) -> Json {
+        json!({
+            "name": instance.name,
+            "constructor": instance.constructor,
+            "value": value(&instance.value),
+            "location": self.location(instance.span),
+        })
+    }
+
+    fn docs(&self, docs: &DocComment) -> Option<String> {
+        docs.text(self.db)
+    }
+
+    fn location(&self, span: Span) -> Json {
+        json!({
+            "file": span.file.path(self.db).to_string_lossy(),
+            "start": span.start,
+            "end": span.end,
+        })
+    }
+}
+
+fn value(value: &Value) -> Json {
+    match value {
+        Value::String(value) => json!({ "kind": "string", "value": value }),
+        Value::Int(value) => json!({ "kind": "int", "value": value }),
+        Value::Uint(value) => json!({ "kind": "uint", "value": value }),
+        Value::Float(value) => json!({ "kind": "float", "value": value }),
+        Value::Object(entries) => {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|(key, entry)| json!({ "key": key, "value": self::value(entry) }))
+                .collect();
+            json!({ "kind": "object", "entries": entries })
+        }
+        Value::Ref(name) => json!({ "kind": "ref", "name": name }),
+        Value::Type(name) => json!({ "kind": "type", "name": name }),
+    }
+}
+
+fn package(db: &dyn Db, file: SourceFile) -> Option<Vec<String>> {
+    parse_file(db, file)?
+        .nodes
+        .into_iter()
+        .find_map(|node| match node {
+            Node::PackageStmt(stmt) => Some(
+                stmt.segments
+                    .iter()
+                    .map(|segment| segment.text(db).to_string())
+                    .collect(),
+            ),
+            _ => None,
+        })
+}
+
+/// What a plugin writes to stdout: the files it generated and anything it has
+/// to report. A plugin that reports errors has failed, whatever files it
+/// returns.
+///
+/// ```json
+/// {
+///   "files": [{ "path": "api/spec.py", "contents": "..." }],
+///   "diagnostics": [{
+///     "level": "error",
+///     "message": "`Folder` has no `id` field",
+///     "location": { "file": "spec/api.haml", "start": 120, "end": 126 },
+///     "label": "needs an `id` field",
+///     "help": ["add `id: string;` to `Folder`"]
+///   }]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PluginResponse {
+    #[serde(default)]
+    pub files: Vec<OutputFile>,
+    #[serde(default)]
+    pub diagnostics: Vec<PluginDiagnostic>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PluginDiagnostic {
+    /// `error`, `warning` or `info`
+    pub level: Level,
+    pub message: String,
+    /// Where in the spec the diagnostic is about, like the `location` of a
+    /// declaration in the model
+    #[serde(default)]
+    pub location: Option<PluginLocation>,
+    /// What to say at the location
+    #[serde(default)]
+    pub label: Option<String>,
+    /// `note:` footers
+    #[serde(default)]
+    pub notes: Vec<String>,
+    /// `help:` footers
+    #[serde(default)]
+    pub help: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PluginLocation {
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl PluginDiagnostic {
+    /// Turn the diagnostic into one that points into the files of `model`.
+    /// Locations that aren't in those files are dropped rather than trusted.
+    pub fn to_diagnostic(&self, db: &dyn Db, model: &Model) -> Diagnostic {
+        let span = self.location.as_ref().and_then(|location| {
+            let file = resolve_imports(db, model.file)
+                .into_iter()
+                .find(|file| file.path(db).to_string_lossy() == location.file)?;
+            let text = file.text(db);
+            let (start, end) = (location.start, location.end);
+            let valid = start <= end
+                && end <= text.len()
+                && text.is_char_boundary(start)
+                && text.is_char_boundary(end);
+            valid.then_some(Span { start, end, file })
+        });
+        let label = span.map(|_| self.label.clone().unwrap_or_default());
+        let mut diagnostic = Diagnostic::new(self.level.clone(), self.message.clone(), span, label);
+        diagnostic.notes = self.notes.clone();
+        diagnostic.help = self.help.clone();
+        diagnostic
+    }
+}
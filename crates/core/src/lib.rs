@@ -4,9 +4,15 @@
 pub mod ast;
 pub mod db;
 pub mod diagnostics;
+pub mod gen;
+pub mod line_index;
+pub mod lint;
 pub mod queries;
+pub mod sema;
 pub mod span;
 pub mod syntax;
+#[cfg(test)]
+mod test;
 
 // The salsa database is defined in terms of jars. These store all the
 // intermediate state managed by salsa; all annotations refer to jars.
@@ -17,10 +23,15 @@ pub struct Jar(
     crate::queries::SourceFile,
     crate::queries::TrackedAst,
     crate::queries::TrackedSpan,
+    crate::queries::TrackedConfig,
     crate::queries::read_file,
+    crate::queries::line_index,
     crate::queries::parse_file,
+    crate::queries::resolve_imports,
     crate::queries::build_symbol_table,
+    crate::queries::check_file,
     crate::queries::read_span,
+    crate::diagnostics::diagnostics_for,
 );
 
 pub trait Db: salsa::DbWithJar<Jar> {}
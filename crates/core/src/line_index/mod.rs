@@ -0,0 +1,149 @@
+//! Lines and columns of byte offsets. Spans are byte offsets into a file, but
+//! people, editors and other tools locate them by line and column, and each
+//! of them counts columns differently: LSP clients count UTF-16 code units,
+//! JSON and SARIF output counts characters, and carets in a terminal have to
+//! count the columns characters take up on screen.
+
+#[cfg(test)]
+mod test;
+
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthChar;
+
+/// The columns a tab takes up when it's drawn in a terminal
+pub const TAB_WIDTH: usize = 4;
+
+/// Where a byte offset is in its file. Lines and columns start at 0.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    /// The column in bytes, or UTF-8 code units
+    pub utf8: usize,
+    /// The column in characters
+    pub chars: usize,
+    /// The column in UTF-16 code units, like LSP positions
+    pub utf16: usize,
+    /// The column on screen, where wide characters take up two columns and
+    /// tabs take up `TAB_WIDTH`
+    pub width: usize,
+}
+
+/// The lines of a file. Lines end with `\n` or `\r\n`, and the line break
+/// isn't part of the line. A file that ends with a line break has an empty
+/// last line after it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineIndex {
+    // The range of each line, without its line break
+    lines: Vec<Range<usize>>,
+    // The characters that aren't a single byte and column, by line. Most
+    // lines don't have any.
+    wide: Vec<(usize, Vec<WideChar>)>,
+    len: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct WideChar {
+    // The offset from the start of the line
+    offset: usize,
+    utf8: usize,
+    utf16: usize,
+    width: usize,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        let mut index = LineIndex {
+            len: text.len(),
+            ..LineIndex::default()
+        };
+        let mut start = 0;
+        let mut wide = vec![];
+        for (offset, c) in text.char_indices() {
+            if c == '\n' {
+                let end = match text[start..offset].ends_with('\r') {
+                    true => offset - 1,
+                    false => offset,
+                };
+                index.push_line(start..end, std::mem::take(&mut wide));
+                start = offset + 1;
+                continue;
+            }
+            let width = char_width(c);
+            if c.len_utf8() != 1 || width != 1 {
+                wide.push(WideChar {
+                    offset: offset - start,
+                    utf8: c.len_utf8(),
+                    utf16: c.len_utf16(),
+                    width,
+                });
+            }
+        }
+        index.push_line(start..text.len(), wide);
+        index
+    }
+
+    fn push_line(&mut self, range: Range<usize>, wide: Vec<WideChar>) {
+        if !wide.is_empty() {
+            self.wide.push((self.lines.len(), wide));
+        }
+        self.lines.push(range);
+    }
+
+    /// The number of lines, which is always at least one
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The byte range of a line, without its line break
+    pub fn line(&self, line: usize) -> Option<Range<usize>> {
+        self.lines.get(line).cloned()
+    }
+
+    /// The position of a byte offset. Offsets past the end of the file are at
+    /// the end of it, offsets inside a character are at the start of that
+    /// character, and offsets inside a `\r\n` are at the end of its line.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.len);
+        let line = self.lines.partition_point(|range| range.start <= offset) - 1;
+        let range = &self.lines[line];
+        let wide = self.wide_chars(line);
+
+        let mut column = offset.min(range.end) - range.start;
+        if let Some(c) = wide
+            .iter()
+            .find(|c| c.offset < column && column < c.offset + c.utf8)
+        {
+            column = c.offset;
+        }
+        let mut position = Position {
+            line,
+            utf8: column,
+            chars: column,
+            utf16: column,
+            width: column,
+        };
+        for c in wide.iter().take_while(|c| c.offset < column) {
+            position.chars -= c.utf8 - 1;
+            position.utf16 = position.utf16 + c.utf16 - c.utf8;
+            position.width = position.width + c.width - c.utf8;
+        }
+        position
+    }
+
+    fn wide_chars(&self, line: usize) -> &[WideChar] {
+        match self.wide.binary_search_by_key(&line, |(line, _)| *line) {
+            Ok(index) => &self.wide[index].1,
+            Err(_) => &[],
+        }
+    }
+}
+
+/// The columns a character takes up on screen. Control characters other than
+/// tabs take up one, like the replacement character terminals draw for them.
+pub fn char_width(c: char) -> usize {
+    match c {
+        '\t' => TAB_WIDTH,
+        c => c.width().unwrap_or(1),
+    }
+}
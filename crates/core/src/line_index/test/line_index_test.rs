@@ -0,0 +1,81 @@
+use crate::line_index::{LineIndex, Position, TAB_WIDTH};
+
+fn position(line: usize, utf8: usize, chars: usize, utf16: usize, width: usize) -> Position {
+    Position {
+        line,
+        utf8,
+        chars,
+        utf16,
+        width,
+    }
+}
+
+#[test]
+fn test_ascii_lines_and_columns() {
+    let index = LineIndex::new("struct A {}\nstruct B {}\n");
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.position(0), position(0, 0, 0, 0, 0));
+    assert_eq!(index.position(7), position(0, 7, 7, 7, 7));
+    assert_eq!(index.position(11), position(0, 11, 11, 11, 11));
+    assert_eq!(index.position(19), position(1, 7, 7, 7, 7));
+    assert_eq!(index.line(1), Some(12..23));
+}
+
+#[test]
+fn test_crlf_line_breaks_are_not_part_of_lines() {
+    let index = LineIndex::new("a {}\r\nb {}\r\n");
+    assert_eq!(index.line(0), Some(0..4));
+    assert_eq!(index.line(1), Some(6..10));
+    assert_eq!(index.position(6), position(1, 0, 0, 0, 0));
+    // Offsets inside the line break are at the end of the line
+    assert_eq!(index.position(4), position(0, 4, 4, 4, 4));
+    assert_eq!(index.position(5), position(0, 4, 4, 4, 4));
+}
+
+#[test]
+fn test_columns_of_non_ascii_characters() {
+    // `é` is 2 bytes, `名` is 3 bytes and 2 columns wide, and `😀` is 4 bytes,
+    // 2 UTF-16 code units and 2 columns wide
+    let text = "struct é名😀 {}";
+    let index = LineIndex::new(text);
+    assert_eq!(
+        index.position(text.find('名').unwrap()),
+        position(0, 9, 8, 8, 8)
+    );
+    assert_eq!(
+        index.position(text.find('😀').unwrap()),
+        position(0, 12, 9, 9, 10)
+    );
+    assert_eq!(
+        index.position(text.find(' ').unwrap() + 10),
+        position(0, 16, 10, 11, 12)
+    );
+}
+
+#[test]
+fn test_offsets_inside_characters_are_at_their_start() {
+    let index = LineIndex::new("é名");
+    assert_eq!(index.position(1), position(0, 0, 0, 0, 0));
+    assert_eq!(index.position(3), position(0, 2, 1, 1, 1));
+    assert_eq!(index.position(4), position(0, 2, 1, 1, 1));
+}
+
+#[test]
+fn test_tabs_are_wide() {
+    let index = LineIndex::new("\tstruct A {}");
+    assert_eq!(index.position(1), position(0, 1, 1, 1, TAB_WIDTH));
+}
+
+#[test]
+fn test_end_of_file() {
+    let empty = LineIndex::new("");
+    assert_eq!(empty.line_count(), 1);
+    assert_eq!(empty.position(0), Position::default());
+    assert_eq!(empty.position(10), Position::default());
+
+    let index = LineIndex::new("a\n");
+    assert_eq!(index.position(2), position(1, 0, 0, 0, 0));
+    assert_eq!(index.position(100), position(1, 0, 0, 0, 0));
+    assert_eq!(index.line(1), Some(2..2));
+    assert_eq!(index.line(2), None);
+}
@@ -0,0 +1 @@
+pub mod line_index_test;
@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::{Lint, LintLevel};
+
+/// The name of a project's config file. Specs use the nearest one in their
+/// directory or above it.
+pub const CONFIG_FILE: &str = "haml.toml";
+
+/// The lint levels a project sets in its config file, like
+///
+/// ```toml
+/// [lints]
+/// unused_struct = "allow"
+/// non_pascal_case_types = "deny"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub lints: BTreeMap<String, LintLevel>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|err| err.to_string())?;
+        if let Some(name) = config.lints.keys().find(|name| super::find(name).is_none()) {
+            return Err(format!("unknown lint `{name}`"));
+        }
+        Ok(config)
+    }
+
+    /// The config of the project `spec` is in. Projects without a config file
+    /// use the default levels.
+    pub fn find(spec: &Path) -> Result<Config, String> {
+        let spec = fs::canonicalize(spec).unwrap_or_else(|_| spec.to_path_buf());
+        for dir in spec.ancestors().skip(1) {
+            let path = dir.join(CONFIG_FILE);
+            if !path.is_file() {
+                continue;
+            }
+            return fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| Config::parse(&text))
+                .map_err(|err| format!("Could not read config {}: {err}", path.display()));
+        }
+        Ok(Config::default())
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.lints.get(lint.name).copied().unwrap_or(lint.default)
+    }
+}
@@ -0,0 +1,665 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use heck::ToUpperCamelCase;
+
+use super::{
+    Config, Lint, LintLevel, ALLOW, CONFIG_FILE, LINTS, MISSPELLED_ANNOTATIONS,
+    NON_PASCAL_CASE_TYPES, OPTIONAL_FIELD_ALWAYS_PROVIDED, UNKNOWN_LINTS, UNUSED_ANNOTATION,
+    UNUSED_IMPORT, UNUSED_STRUCT,
+};
+use crate::ast::node::{
+    AliasDecl, Annotation, Ast, BlockDecl, FieldDecl, FieldType, ImportStmt, Node, ObjectExpr,
+    ValueExpr,
+};
+use crate::diagnostics::{similar_name, Applicability, Diagnostic, Level, Suggestion};
+use crate::queries::{
+    build_symbol_table, canonical, check_file, find_import, parse_file, resolve_imports, SourceFile,
+};
+use crate::sema::{Symbol, SymbolTable, Value};
+use crate::span::Span;
+use crate::syntax::Token;
+use crate::Db;
+
+/// Lint `file`, returning what the lints that aren't allowed found. Names
+/// used anywhere in the file or the files it imports count as used, but only
+/// the declarations of `file` itself are linted.
+pub fn lint_file(db: &dyn Db, file: SourceFile, config: &Config) -> Vec<Diagnostic> {
+    lint_file_in(db, file, config, &[])
+}
+
+/// Like [`lint_file`], for a file checked along with the files of
+/// `workspace`. Names used in the files that import it count as used too, so
+/// a file of declarations shared by the others isn't linted as if nothing
+/// used them.
+pub fn lint_file_in(
+    db: &dyn Db,
+    file: SourceFile,
+    config: &Config,
+    workspace: &[SourceFile],
+) -> Vec<Diagnostic> {
+    let (Some(ast), Some(symbols)) = (parse_file(db, file), build_symbol_table(db, file)) else {
+        return vec![];
+    };
+    let files = resolve_imports(db, file);
+    let mut uses = Uses {
+        db,
+        symbols: &symbols,
+        file,
+        within: None,
+        uses: vec![],
+    };
+    for &file in &files {
+        if let Some(ast) = parse_file(db, file) {
+            uses.file = file;
+            uses.ast(&ast);
+        }
+    }
+    // Instances are only evaluated once everything checks
+    let mut counts = FieldCounts {
+        db,
+        symbols: &symbols,
+        counts: HashMap::new(),
+    };
+    for instance in check_file(db, file).unwrap_or_default() {
+        if let Some(Symbol::Constructor(decl)) = symbols.get(&instance.constructor) {
+            counts.block(&decl.content, &instance.value);
+        }
+    }
+
+    let mut linter = Linter {
+        db,
+        config,
+        symbols: &symbols,
+        file,
+        uses: uses.uses,
+        used_by_importers: used_by_importers(db, file, workspace),
+        counts: counts.counts,
+        diagnostics: vec![],
+    };
+    linter.imports(&ast, &files);
+    for node in &ast.nodes {
+        match node {
+            Node::StructDecl(decl) => {
+                let scopes = [decl.annotations.as_slice()];
+                linter.annotations(&decl.annotations, &scopes);
+                linter.unused(UNUSED_STRUCT, "struct", decl.name, &scopes);
+                linter.pascal_case(decl.name, &scopes);
+                linter.block(decl.name, &decl.content, &scopes);
+            }
+            Node::ConstructorDecl(decl) => {
+                let scopes = [decl.annotations.as_slice()];
+                linter.annotations(&decl.annotations, &scopes);
+                linter.block(decl.name, &decl.content, &scopes);
+            }
+            Node::AnnotationDecl(decl) => {
+                let scopes = [decl.annotations.as_slice()];
+                linter.annotations(&decl.annotations, &scopes);
+                linter.unused(UNUSED_ANNOTATION, "annotation", decl.name, &scopes);
+            }
+            Node::InstanceDecl(decl) => linter.annotations(&decl.annotations, &[&decl.annotations]),
+            _ => {}
+        }
+    }
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    db: &'a dyn Db,
+    config: &'a Config,
+    symbols: &'a SymbolTable,
+    file: SourceFile,
+    uses: Vec<Use>,
+    // The starts of the declarations that files importing this one use
+    used_by_importers: HashSet<usize>,
+    counts: HashMap<(SourceFile, usize), Count>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    // An import is used if anything it makes visible is, including what the
+    // imported file imports in turn
+    fn imports(&mut self, ast: &Ast, files: &[SourceFile]) {
+        let graph: HashMap<PathBuf, Vec<PathBuf>> = files
+            .iter()
+            .map(|&file| (canonical(file.path(self.db)), self.imported_by(file)))
+            .collect();
+        for node in &ast.nodes {
+            let Node::ImportStmt(stmt) = node else {
+                continue;
+            };
+            let scopes = [stmt.annotations.as_slice()];
+            self.annotations(&stmt.annotations, &scopes);
+            let import = stmt.path.unquoted(self.db);
+            // Missing imports are already an error
+            let Some(path) = find_import(self.file.path(self.db), import) else {
+                continue;
+            };
+            let visible = reachable(&graph, canonical(&path));
+            let used = self.uses.iter().any(|use_| {
+                use_.file == self.file
+                    && visible.contains(&canonical(use_.target.span.file.path(self.db)))
+            });
+            if used {
+                continue;
+            }
+            let mut diagnostic = Diagnostic::new(
+                Level::Warning,
+                format!("unused import `{import}`"),
+                Some(stmt.path.span),
+                None,
+            );
+            if let Some(line) = self.statement_line(stmt) {
+                diagnostic = diagnostic.with_suggestion(Suggestion::replace(
+                    "remove the unused import",
+                    line,
+                    "",
+                    Applicability::MachineApplicable,
+                ));
+            }
+            self.lint(UNUSED_IMPORT, &scopes, diagnostic);
+        }
+    }
+
+    fn imported_by(&self, file: SourceFile) -> Vec<PathBuf> {
+        let Some(ast) = parse_file(self.db, file) else {
+            return vec![];
+        };
+        ast.nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::ImportStmt(stmt) => {
+                    find_import(file.path(self.db), stmt.path.unquoted(self.db))
+                }
+                _ => None,
+            })
+            .map(|path| canonical(&path))
+            .collect()
+    }
+
+    // The line an import is on, if nothing else is on it, so it can be
+    // removed along with its line break
+    fn statement_line(&self, stmt: &ImportStmt) -> Option<Span> {
+        if !stmt.annotations.is_empty() {
+            return None;
+        }
+        let text = self.file.text(self.db);
+        let span = stmt.path.span;
+        let start = text[..span.start].rfind('\n').map_or(0, |index| index + 1);
+        let end = text[span.end..]
+            .find('\n')
+            .map_or(text.len(), |index| span.end + index + 1);
+        let line = text[start..end].trim();
+        (line.starts_with("import") && line.ends_with(';'))
+            .then(|| Span::new(start, end, self.file))
+    }
+
+    // Uses inside the declaration itself, like a recursive struct, don't count
+    fn unused(&mut self, lint: Lint, kind: &str, name: Token, scopes: &[&[Annotation]]) {
+        let used = self
+            .uses
+            .iter()
+            .any(|use_| use_.target == name && use_.within != Some(name))
+            || self.used_by_importers.contains(&name.span.start);
+        if used {
+            return;
+        }
+        let diagnostic = Diagnostic::new(
+            Level::Warning,
+            format!("{kind} `{}` is never used", name.text(self.db)),
+            Some(name.span),
+            None,
+        );
+        self.lint(lint, scopes, diagnostic);
+    }
+
+    fn pascal_case(&mut self, name: Token, scopes: &[&[Annotation]]) {
+        let text = name.text(self.db);
+        let starts_upper = text.chars().next().is_some_and(char::is_uppercase);
+        if starts_upper && !text.contains('_') {
+            return;
+        }
+        let pascal = text.to_upper_camel_case();
+        let diagnostic = Diagnostic::new(
+            Level::Warning,
+            format!("struct `{text}` should have a Pascal case name"),
+            Some(name.span),
+            None,
+        )
+        .with_suggestion(Suggestion::replace(
+            format!("convert the name to Pascal case: `{pascal}`"),
+            name.span,
+            pascal,
+            // Uses of the name aren't renamed with it
+            Applicability::MaybeIncorrect,
+        ));
+        self.lint(NON_PASCAL_CASE_TYPES, scopes, diagnostic);
+    }
+
+    fn block(&mut self, owner: Token, block: &BlockDecl, scopes: &[&[Annotation]]) {
+        let fields = match block {
+            BlockDecl::FieldSet(fields) | BlockDecl::Repeatable(fields) => &fields.fields,
+            BlockDecl::Union(union) => &union.variants.fields,
+            BlockDecl::Alias(_) => return,
+        };
+        for field in fields {
+            let scopes = [scopes[0], field.annotations.as_slice()];
+            self.annotations(&field.annotations, &scopes);
+            self.always_provided(owner, field, &scopes);
+        }
+    }
+
+    // A lone value says little about the rest, so there have to be at least
+    // two of them
+    fn always_provided(&mut self, owner: Token, field: &FieldDecl, scopes: &[&[Annotation]]) {
+        let span = field.name.span;
+        let Some(&Count { values, given }) = self.counts.get(&(span.file, span.start)) else {
+            return;
+        };
+        if !field.optional || values < 2 || given < values {
+            return;
+        }
+        let name = field.name.text(self.db);
+        let owner = owner.text(self.db);
+        let mut diagnostic = Diagnostic::new(
+            Level::Warning,
+            format!("field `{name}` is optional, but every `{owner}` gives it"),
+            Some(span),
+            None,
+        )
+        .with_note(format!("all {values} values of `{owner}` give `{name}`"));
+        if let Some(question) = self.question_mark(span) {
+            diagnostic = diagnostic.with_suggestion(Suggestion::replace(
+                "make the field required",
+                question,
+                "",
+                Applicability::MaybeIncorrect,
+            ));
+        }
+        self.lint(OPTIONAL_FIELD_ALWAYS_PROVIDED, scopes, diagnostic);
+    }
+
+    // The `?` after the name of an optional field
+    fn question_mark(&self, name: Span) -> Option<Span> {
+        let rest = &self.file.text(self.db)[name.end..];
+        let offset = rest.len() - rest.trim_start().len();
+        let start = name.end + offset;
+        rest.trim_start()
+            .starts_with('?')
+            .then(|| Span::new(start, start + 1, self.file))
+    }
+
+    // Annotations don't have to be declared, but one that isn't declared and
+    // is named almost like one that is was probably meant to be it. The names
+    // in an `@allow` have to be lints.
+    fn annotations(&mut self, annotations: &[Annotation], scopes: &[&[Annotation]]) {
+        for annotation in annotations {
+            let name = annotation.name.text(self.db);
+            if name != ALLOW {
+                self.misspelled(annotation, scopes);
+                continue;
+            }
+            for arg in &annotation.args {
+                let name = arg.key.text(self.db);
+                if super::find(name).is_some() {
+                    continue;
+                }
+                let diagnostic = Diagnostic::new(
+                    Level::Warning,
+                    format!("unknown lint: `{name}`"),
+                    Some(arg.key.span),
+                    None,
+                )
+                .with_similar_name(arg.key.span, name, LINTS.iter().map(|lint| lint.name))
+                .with_help("run `haml explain` to list the lints");
+                self.lint(UNKNOWN_LINTS, scopes, diagnostic);
+            }
+        }
+    }
+
+    fn misspelled(&mut self, annotation: &Annotation, scopes: &[&[Annotation]]) {
+        let name = annotation.name.text(self.db);
+        if self.symbols.get(name).is_some() {
+            return;
+        }
+        let names = self.symbols.annotation_names().chain([ALLOW]);
+        let Some(similar) = similar_name(name, names) else {
+            return;
+        };
+        let span = annotation.name.span;
+        let diagnostic = Diagnostic::new(
+            Level::Warning,
+            format!("annotation `{name}` is not declared"),
+            Some(span),
+            Some("not declared".to_string()),
+        )
+        .with_similar_name(span, name, [similar]);
+        self.lint(MISSPELLED_ANNOTATIONS, scopes, diagnostic);
+    }
+
+    // Report a diagnostic at the level the lint is set to, unless it's allowed
+    // by the project or by an `@allow` in one of the annotation scopes
+    fn lint(&mut self, lint: Lint, scopes: &[&[Annotation]], mut diagnostic: Diagnostic) {
+        let level = self.config.level(lint);
+        let allowed = scopes
+            .iter()
+            .any(|annotations| allows(self.db, annotations, lint));
+        if level == LintLevel::Allow || allowed {
+            return;
+        }
+        let origin = match self.config.lints.contains_key(lint.name) {
+            true => format!("in `{CONFIG_FILE}`"),
+            false => "by default".to_string(),
+        };
+        let note = format!("`{}` is set to `{level}` {origin}", lint.name);
+        diagnostic.notes.insert(0, note);
+        diagnostic.level = match level {
+            LintLevel::Deny => Level::Error,
+            _ => Level::Warning,
+        };
+        self.diagnostics.push(diagnostic.with_code(lint.code()));
+    }
+}
+
+/// Whether `@allow` in `annotations` names `lint`
+fn allows(db: &dyn Db, annotations: &[Annotation], lint: Lint) -> bool {
+    annotations
+        .iter()
+        .filter(|annotation| annotation.name.text(db) == ALLOW)
+        .flat_map(|annotation| &annotation.args)
+        .any(|arg| arg.key.text(db) == lint.name)
+}
+
+// Every file that can be reached from `start` through imports
+fn reachable(graph: &HashMap<PathBuf, Vec<PathBuf>>, start: PathBuf) -> HashSet<PathBuf> {
+    let mut seen = HashSet::new();
+    let mut next = vec![start];
+    while let Some(path) = next.pop() {
+        if let Some(imports) = graph.get(&path) {
+            next.extend(imports.iter().filter(|path| !seen.contains(*path)).cloned());
+        }
+        seen.insert(path);
+    }
+    seen
+}
+
+/// A name that refers to a declaration
+// The declarations of `file` that the files of `workspace` importing it use,
+// by where their names start. Each importer reads a copy of `file` of its
+// own, so the copies are found by their path.
+fn used_by_importers(db: &dyn Db, file: SourceFile, workspace: &[SourceFile]) -> HashSet<usize> {
+    let path = canonical(file.path(db));
+    let mut paths: HashMap<SourceFile, PathBuf> = HashMap::new();
+    let mut is_copy = |file: SourceFile| {
+        *paths
+            .entry(file)
+            .or_insert_with(|| canonical(file.path(db)))
+            == path
+    };
+    let mut used = HashSet::new();
+    for &root in workspace {
+        let files = resolve_imports(db, root);
+        // The file itself, or one that doesn't import it
+        if is_copy(root) || !files.iter().any(|&other| is_copy(other)) {
+            continue;
+        }
+        let Some(symbols) = build_symbol_table(db, root) else {
+            continue;
+        };
+        let mut uses = Uses {
+            db,
+            symbols: &symbols,
+            file: root,
+            within: None,
+            uses: vec![],
+        };
+        for &file in &files {
+            if let Some(ast) = parse_file(db, file) {
+                uses.file = file;
+                uses.ast(&ast);
+            }
+        }
+        for use_ in uses.uses {
+            let start = use_.target.span.start;
+            let within_itself = use_
+                .within
+                .is_some_and(|within| within.span.start == start && is_copy(within.span.file));
+            if is_copy(use_.target.span.file) && !within_itself {
+                used.insert(start);
+            }
+        }
+    }
+    used
+}
+
+struct Use {
+    /// The name of the declaration it refers to
+    target: Token,
+    /// The file the name is in
+    file: SourceFile,
+    /// The name of the declaration the name is in
+    within: Option<Token>,
+}
+
+// Finds every name that refers to a declaration
+struct Uses<'a> {
+    db: &'a dyn Db,
+    symbols: &'a SymbolTable,
+    file: SourceFile,
+    within: Option<Token>,
+    uses: Vec<Use>,
+}
+
+impl<'a> Uses<'a> {
+    fn ast(&mut self, ast: &Ast) {
+        for node in &ast.nodes {
+            match node {
+                Node::ImportStmt(stmt) => {
+                    self.within = None;
+                    self.annotations(&stmt.annotations);
+                }
+                Node::StructDecl(decl) => {
+                    self.within = Some(decl.name);
+                    self.annotations(&decl.annotations);
+                    self.block(&decl.content);
+                }
+                Node::ConstructorDecl(decl) => {
+                    self.within = Some(decl.name);
+                    self.annotations(&decl.annotations);
+                    self.block(&decl.content);
+                }
+                Node::AnnotationDecl(decl) => {
+                    self.within = Some(decl.name);
+                    self.annotations(&decl.annotations);
+                }
+                Node::InstanceDecl(decl) => {
+                    self.within = Some(decl.name);
+                    self.annotations(&decl.annotations);
+                    self.name(decl.constructor);
+                    self.object(&decl.value);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn block(&mut self, block: &BlockDecl) {
+        let fields = match block {
+            BlockDecl::FieldSet(fields) | BlockDecl::Repeatable(fields) => &fields.fields,
+            BlockDecl::Union(union) => &union.variants.fields,
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => {
+                self.typ(&map.key);
+                self.typ(&map.value);
+                return;
+            }
+        };
+        for field in fields {
+            self.annotations(&field.annotations);
+            self.typ(&field.typ);
+        }
+    }
+
+    fn typ(&mut self, typ: &FieldType) {
+        match typ {
+            FieldType::Ident(token) => {
+                if let Some(symbol) = self.symbols.resolve_type(self.db, token.text(self.db)) {
+                    self.push(symbol);
+                }
+            }
+            FieldType::Map(map) => {
+                self.typ(&map.key);
+                self.typ(&map.value);
+            }
+            _ => {}
+        }
+    }
+
+    fn annotations(&mut self, annotations: &[Annotation]) {
+        for annotation in annotations {
+            self.name(annotation.name);
+            // The arguments of `@allow` name lints, not declarations
+            if annotation.name.text(self.db) == ALLOW {
+                continue;
+            }
+            for arg in &annotation.args {
+                self.value(&arg.value);
+            }
+        }
+    }
+
+    fn object(&mut self, object: &ObjectExpr) {
+        for entry in &object.entries {
+            self.value(&entry.value);
+        }
+    }
+
+    fn value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Ident(token) => self.name(*token),
+            ValueExpr::Object(object) => self.object(object),
+            _ => {}
+        }
+    }
+
+    fn name(&mut self, token: Token) {
+        if let Some(symbol) = self.symbols.get(token.text(self.db)) {
+            self.push(symbol);
+        }
+    }
+
+    fn push(&mut self, symbol: &Symbol) {
+        self.uses.push(Use {
+            target: symbol.name(),
+            file: self.file,
+            within: self.within,
+        });
+    }
+}
+
+/// How many values a field could have been given in, and how many gave it
+#[derive(Clone, Copy, Default)]
+struct Count {
+    values: usize,
+    given: usize,
+}
+
+// Counts how often each field is given, by following the evaluated values of
+// instances through the declarations of their types
+struct FieldCounts<'a> {
+    db: &'a dyn Db,
+    symbols: &'a SymbolTable,
+    // Fields by the file and position of their name
+    counts: HashMap<(SourceFile, usize), Count>,
+}
+
+impl<'a> FieldCounts<'a> {
+    fn block(&mut self, block: &BlockDecl, value: &Value) {
+        let Value::Object(entries) = value else {
+            return;
+        };
+        match block {
+            BlockDecl::FieldSet(fields) => self.fields(&fields.fields, entries),
+            BlockDecl::Repeatable(fields) => {
+                for (_, value) in entries {
+                    if let Value::Object(entries) = value {
+                        self.fields(&fields.fields, entries);
+                    }
+                }
+            }
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => {
+                for (_, value) in entries {
+                    self.typ(&map.value, value);
+                }
+            }
+            BlockDecl::Union(union) => {
+                let Some(discriminator) = union.discriminator else {
+                    // The value is wrapped in the name of its variant
+                    for (name, value) in entries {
+                        if let Some(variant) = self.variant(&union.variants.fields, name) {
+                            self.typ(&variant.typ, value);
+                        }
+                    }
+                    return;
+                };
+                let discriminator = discriminator.text(self.db);
+                let Some((_, Value::String(name))) =
+                    entries.iter().find(|(key, _)| key == discriminator)
+                else {
+                    return;
+                };
+                let Some(variant) = self.variant(&union.variants.fields, name) else {
+                    return;
+                };
+                let rest = entries
+                    .iter()
+                    .filter(|(key, _)| key != discriminator)
+                    .cloned()
+                    .collect();
+                self.typ(&variant.typ, &Value::Object(rest));
+            }
+        }
+    }
+
+    fn variant<'f>(&self, variants: &'f [FieldDecl], name: &str) -> Option<&'f FieldDecl> {
+        variants
+            .iter()
+            .find(|variant| variant.name.text(self.db) == name)
+    }
+
+    fn fields(&mut self, fields: &[FieldDecl], entries: &[(String, Value)]) {
+        for field in fields {
+            let name = field.name.text(self.db);
+            let value = entries
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value);
+            let span = field.name.span;
+            let count = self.counts.entry((span.file, span.start)).or_default();
+            count.values += 1;
+            if let Some(value) = value {
+                count.given += 1;
+                self.typ(&field.typ, value);
+            }
+        }
+    }
+
+    fn typ(&mut self, typ: &FieldType, value: &Value) {
+        match typ {
+            FieldType::Ident(token) => {
+                match self.symbols.resolve_type(self.db, token.text(self.db)) {
+                    Some(Symbol::Struct(decl)) => self.block(&decl.content, value),
+                    Some(Symbol::Constructor(decl)) => self.block(&decl.content, value),
+                    _ => {}
+                }
+            }
+            FieldType::Map(map) => {
+                if let Value::Object(entries) = value {
+                    for (_, value) in entries {
+                        self.typ(&map.value, value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Lints point out things that are allowed in a spec but are probably
+//! mistakes, like a struct that's never used. Each lint can be allowed,
+//! warned about or denied in the `[lints]` table of the project's
+//! `haml.toml`, and allowed for a single declaration with
+//! `@allow(lint_name)`. Like codes, every lint has an explanation in
+//! `explain/` for `haml explain`.
+
+mod config;
+mod linter;
+#[cfg(test)]
+mod test;
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::diagnostics::codes::Code;
+
+pub use config::{Config, CONFIG_FILE};
+pub use linter::{lint_file, lint_file_in};
+
+/// The annotation that allows lints for the declaration it's on
+pub const ALLOW: &str = "allow";
+
+/// What happens when a lint finds something
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    /// Nothing is reported
+    Allow,
+    /// A warning is reported
+    Warn,
+    /// An error is reported
+    Deny,
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintLevel::Allow => write!(f, "allow"),
+            LintLevel::Warn => write!(f, "warn"),
+            LintLevel::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    /// The name it's configured and allowed by, like `unused_struct`
+    pub name: &'static str,
+    /// A short summary, like "struct is never used"
+    pub title: &'static str,
+    /// The level when the project doesn't configure one
+    pub default: LintLevel,
+    /// A long-form explanation with examples, as Markdown
+    pub explanation: &'static str,
+}
+
+impl Lint {
+    /// The code of the lint's diagnostics, which is its name
+    pub fn code(&self) -> Code {
+        Code {
+            name: self.name,
+            title: self.title,
+            explanation: self.explanation,
+        }
+    }
+}
+
+macro_rules! lints {
+    ($($constant:ident = $name:literal, $default:ident, $title:literal;)*) => {
+        $(
+            pub const $constant: Lint = Lint {
+                name: $name,
+                title: $title,
+                default: LintLevel::$default,
+                explanation: include_str!(concat!("explain/", $name, ".md")),
+            };
+        )*
+
+        /// Every lint, in order
+        pub const LINTS: &[Lint] = &[$($constant),*];
+    };
+}
+
+lints! {
+    UNUSED_STRUCT = "unused_struct", Allow, "struct is never used";
+    UNUSED_IMPORT = "unused_import", Warn, "import is never used";
+    UNUSED_ANNOTATION = "unused_annotation", Warn, "annotation is never used";
+    NON_PASCAL_CASE_TYPES = "non_pascal_case_types", Warn, "struct name is not in Pascal case";
+    OPTIONAL_FIELD_ALWAYS_PROVIDED = "optional_field_always_provided", Warn,
+        "optional field is always given";
+    UNKNOWN_LINTS = "unknown_lints", Warn, "unknown lint";
+    MISSPELLED_ANNOTATIONS = "misspelled_annotations", Warn,
+        "annotation is not declared but a similar one is";
+}
+
+/// Find a lint by its name
+pub fn find(name: &str) -> Option<Lint> {
+    LINTS.iter().find(|lint| lint.name == name.trim()).copied()
+}
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::db::Database;
+use crate::diagnostics::{apply_fixes, Level};
+use crate::lint::{self, lint_file, lint_file_in, Config, LintLevel, LINTS, UNUSED_STRUCT};
+use crate::queries::SourceFile;
+use crate::test::support::{project, read};
+
+// The lints found in `text`, as `lint: message`
+fn lint(text: &str, config: &Config) -> Vec<String> {
+    let db = Database::default();
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text.to_string());
+    lint_file(&db, file, config)
+        .into_iter()
+        .map(|diagnostic| {
+            let name = diagnostic.code.map_or("", |code| code.name);
+            format!("{name}: {}", diagnostic.message)
+        })
+        .collect()
+}
+
+// The default levels, but warning about unused structs, which are allowed by
+// default
+fn warn_unused_structs() -> Config {
+    Config {
+        lints: BTreeMap::from([(UNUSED_STRUCT.name.to_string(), LintLevel::Warn)]),
+    }
+}
+
+#[test]
+fn test_lints_are_unique_and_explained() {
+    for (index, lint) in LINTS.iter().enumerate() {
+        assert!(!lint.explanation.trim().is_empty(), "{}", lint.name);
+        assert_eq!(lint::find(lint.name), Some(*lint));
+        assert!(LINTS[..index].iter().all(|other| other.name != lint.name));
+    }
+    assert_eq!(lint::find("unused_structs"), None);
+}
+
+#[test]
+fn test_unused_structs_and_annotations() {
+    let messages = lint(
+        r#"
+        annotation pack { index: uint32; }
+        annotation deprecated {}
+
+        struct Node {
+            @pack(index = 1)
+            next?: Node;
+        }
+
+        struct Folder { name: string; }
+        constructor drive { root: Folder; }
+        "#,
+        &warn_unused_structs(),
+    );
+    assert_eq!(
+        messages,
+        vec![
+            "unused_annotation: annotation `deprecated` is never used",
+            "unused_struct: struct `Node` is never used",
+        ]
+    );
+    assert_eq!(
+        lint("struct Node { next?: Node; }", &Config::default()),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn test_struct_names_are_pascal_case() {
+    let db = Database::default();
+    let text = "struct folder_item {}\nstruct Folder { items?: map<string, folder_item>; }\n";
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text.to_string());
+    let diagnostics = lint_file(&db, file, &warn_unused_structs());
+    let messages: Vec<_> = diagnostics.iter().map(|d| &d.message).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "struct `folder_item` should have a Pascal case name",
+            "struct `Folder` is never used",
+        ]
+    );
+    let suggestion = &diagnostics[0].suggestions[0];
+    assert_eq!(suggestion.edits[0].replacement, "FolderItem");
+}
+
+#[test]
+fn test_optional_fields_given_by_every_value() {
+    let spec = r#"
+        constructor endpoint {
+            path: string;
+            method?: string;
+            summary?: string;
+        }
+
+        endpoint GetFolder { path: "/folder", method: "GET", summary: "Get" }
+        "#;
+    // A single value isn't enough to go on
+    assert_eq!(lint(spec, &Config::default()), Vec::<String>::new());
+
+    let text = format!("{spec}endpoint ListFolders {{ path: \"/folders\", method: \"GET\" }}\n");
+    assert_eq!(
+        lint(&text, &Config::default()),
+        vec![
+            "optional_field_always_provided: field `method` is optional, but every `endpoint` \
+             gives it"
+        ]
+    );
+}
+
+#[test]
+fn test_optional_fields_count_nested_values() {
+    let messages = lint(
+        r#"
+        struct Route { path: string; method?: string; }
+        constructor api { routes: map<string, Route>; }
+
+        api Folders {
+            routes: {
+                get: { path: "/folder", method: "GET" },
+                list: { path: "/folders", method: "GET" },
+            },
+        }
+        "#,
+        &Config::default(),
+    );
+    assert_eq!(
+        messages,
+        vec!["optional_field_always_provided: field `method` is optional, but every `Route` gives it"]
+    );
+}
+
+#[test]
+fn test_allow_silences_lints_inline() {
+    let messages = lint(
+        r#"
+        @allow(unused_struct, non_pascal_case_types)
+        struct folder {}
+
+        @allow(unused_structs)
+        struct Unused {}
+        "#,
+        &warn_unused_structs(),
+    );
+    assert_eq!(
+        messages,
+        vec![
+            "unknown_lints: unknown lint: `unused_structs`",
+            "unused_struct: struct `Unused` is never used",
+        ]
+    );
+}
+
+#[test]
+fn test_misspelled_annotations_and_lints() {
+    let db = Database::default();
+    let text = r#"
+        annotation deprecated {}
+
+        @depreciated
+        @pack
+        @allow(unused_structs)
+        struct Folder {}
+
+        @alow(unused_struct)
+        struct File {}
+        "#;
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text.to_string());
+    let suggestions: Vec<_> = lint_file(&db, file, &warn_unused_structs())
+        .into_iter()
+        .map(|diagnostic| {
+            let messages: Vec<_> = diagnostic
+                .suggestions
+                .iter()
+                .map(|suggestion| suggestion.message.as_str())
+                .collect();
+            format!("{}: {}", diagnostic.message, messages.join(", "))
+        })
+        .collect();
+    assert_eq!(
+        suggestions,
+        vec![
+            "annotation `deprecated` is never used: ",
+            "annotation `depreciated` is not declared: did you mean `deprecated`?",
+            "unknown lint: `unused_structs`: did you mean `unused_struct`?",
+            "struct `Folder` is never used: ",
+            "annotation `alow` is not declared: did you mean `allow`?",
+            "struct `File` is never used: ",
+        ]
+    );
+}
+
+#[test]
+fn test_config_sets_levels() {
+    let config = Config::parse("[lints]\nunused_struct = \"deny\"\nunknown_lints = \"allow\"\n");
+    let config = config.unwrap();
+    assert_eq!(config.level(lint::UNUSED_STRUCT), LintLevel::Deny);
+    assert_eq!(config.level(lint::UNUSED_IMPORT), LintLevel::Warn);
+
+    let db = Database::default();
+    let text = "@allow(nothing)\nstruct Folder {}\n";
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text.to_string());
+    let diagnostics = lint_file(&db, file, &config);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].level, Level::Error);
+    assert_eq!(
+        diagnostics[0].notes,
+        vec!["`unused_struct` is set to `deny` in `haml.toml`"]
+    );
+
+    let error = Config::parse("[lints]\nunused_structs = \"allow\"\n").unwrap_err();
+    assert_eq!(error, "unknown lint `unused_structs`");
+    assert!(Config::parse("[lints]\nunused_struct = \"sometimes\"\n").is_err());
+}
+
+#[test]
+fn test_config_is_found_above_the_spec() {
+    let dir = project(
+        "config",
+        &[("haml.toml", "[lints]\nunused_struct = \"warn\"\n")],
+    );
+    fs::create_dir_all(dir.join("specs")).unwrap();
+    let config = Config::find(&dir.join("specs/api.haml")).unwrap();
+    assert_eq!(config.level(lint::UNUSED_STRUCT), LintLevel::Warn);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_unused_imports() {
+    let dir = project(
+        "imports",
+        &[
+            (
+                "main.haml",
+                "import \"used\";\nimport \"unused\";\n\nconstructor drive { root: Root; }\n",
+            ),
+            ("used.haml", "import \"nested\";\n"),
+            ("nested.haml", "struct Root {}\n"),
+            ("unused.haml", "struct Other {}\n"),
+        ],
+    );
+    let db = Database::default();
+    let file = read(&db, &dir.join("main.haml"));
+    let diagnostics = lint_file(&db, file, &Config::default());
+    let messages: Vec<_> = diagnostics.iter().map(|d| &d.message).collect();
+    assert_eq!(messages, vec!["unused import `unused`"]);
+
+    let fixed = apply_fixes(&db, &diagnostics);
+    assert_eq!(
+        fixed[0].text,
+        "import \"used\";\n\nconstructor drive { root: Root; }\n"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_uses_in_importers_count_when_checked_together() {
+    let dir = project(
+        "importers",
+        &[
+            (
+                "api.haml",
+                "import \"common\";\n\n@tag\nconstructor drive { root: Folder; }\n",
+            ),
+            (
+                "common.haml",
+                "annotation tag {}\nstruct Folder {}\nstruct Orphan {}\n",
+            ),
+        ],
+    );
+    let db = Database::default();
+    let api = read(&db, &dir.join("api.haml"));
+    let common = read(&db, &dir.join("common.haml"));
+    let messages = |workspace: &[SourceFile]| -> Vec<_> {
+        lint_file_in(&db, common, &warn_unused_structs(), workspace)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    };
+
+    // On its own, nothing uses the shared declarations
+    assert_eq!(
+        messages(&[common]),
+        vec![
+            "annotation `tag` is never used",
+            "struct `Folder` is never used",
+            "struct `Orphan` is never used",
+        ]
+    );
+    assert_eq!(
+        messages(&[api, common]),
+        vec!["struct `Orphan` is never used"]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
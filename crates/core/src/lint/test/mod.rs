@@ -0,0 +1 @@
+pub mod lint_test;
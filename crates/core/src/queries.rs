@@ -1,10 +1,18 @@
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path as FsPath, PathBuf};
 
+use crate::ast::node::Node;
 use crate::ast::Ast;
-use crate::diagnostics::DiagnosticEmitter;
+use crate::diagnostics::{codes, Diagnostic, DiagnosticEmitter, Diagnostics, Emitter, Level};
+use crate::line_index::LineIndex;
+use crate::lint::Config;
+use crate::sema::{Checker, Instance, Symbol, SymbolTable};
 use crate::span::Span;
-use crate::syntax::{ParseSession, Parser, Token};
+use crate::syntax::{ParseSession, Parser};
+
+/// Endings tried, in order, when an import doesn't name a file exactly
+const IMPORT_EXTENSIONS: [&str; 2] = ["haml", "haml.spec"];
 
 #[salsa::input]
 pub struct TrackedAst {
@@ -16,6 +24,13 @@ pub struct TrackedSpan {
     pub span: Span,
 }
 
+/// The lint config of a project, so queries that lint can depend on it
+#[salsa::input]
+pub struct TrackedConfig {
+    #[return_ref]
+    pub config: Config,
+}
+
 #[salsa::input]
 pub struct Path {
     pub path: PathBuf,
@@ -37,6 +52,12 @@ pub fn read_file(db: &dyn crate::Db, path: Path) -> Option<SourceFile> {
     Some(SourceFile::new(db, path, text))
 }
 
+/// Find the lines of a file, to turn its byte offsets into lines and columns
+#[salsa::tracked(return_ref)]
+pub fn line_index(db: &dyn crate::Db, file: SourceFile) -> LineIndex {
+    LineIndex::new(file.text(db))
+}
+
 /// Turn a file into an AST
 #[salsa::tracked]
 pub fn parse_file(db: &dyn crate::Db, file: SourceFile) -> Option<Ast> {
@@ -46,13 +67,115 @@ pub fn parse_file(db: &dyn crate::Db, file: SourceFile) -> Option<Ast> {
     parser.parse()
 }
 
+/// Find the file and every file it imports, directly or indirectly. Imports
+/// are relative to the importing file. The file itself always comes first.
+#[salsa::tracked]
+pub fn resolve_imports(db: &dyn crate::Db, file: SourceFile) -> Vec<SourceFile> {
+    let mut files = vec![file];
+    // The import statement each file was first imported by
+    let mut imported_at: Vec<Option<Span>> = vec![None];
+    let mut seen = HashSet::from([canonical(file.path(db))]);
+    let mut next = 0;
+    while let Some(&current) = files.get(next) {
+        let importer = imported_at[next];
+        next += 1;
+        let Some(ast) = parse_file(db, current) else {
+            continue;
+        };
+        for node in &ast.nodes {
+            let Node::ImportStmt(stmt) = node else {
+                continue;
+            };
+            let import = stmt.path.unquoted(db);
+            let Some(path) = find_import(current.path(db), import) else {
+                let diagnostic = Diagnostic::new(
+                    Level::Error,
+                    format!("cannot find import `{import}`"),
+                    Some(stmt.path.span),
+                    Some("no file at this path".to_string()),
+                )
+                .with_code(codes::UNRESOLVED_IMPORT);
+                let diagnostic = match importer {
+                    Some(span) => diagnostic.with_label(
+                        span,
+                        format!("`{}` is imported here", current.path(db).display()),
+                    ),
+                    None => diagnostic,
+                };
+                Diagnostics::push(db, diagnostic);
+                continue;
+            };
+            if !seen.insert(canonical(&path)) {
+                continue;
+            }
+            if let Ok(text) = fs::read_to_string(&path) {
+                files.push(SourceFile::new(db, path, text));
+                imported_at.push(Some(stmt.path.span));
+            }
+        }
+    }
+    files
+}
+
 /// Turn a file into a symbol table of declared and imported symbols
 #[salsa::tracked]
-pub fn build_symbol_table(db: &dyn crate::Db, file: SourceFile) -> Option<Vec<Token>> {
-    // let _ast = parse_file(db, file)?;
-    // Iterate through imports and build their symbol tables. While walking, and
-    // combining symbols from all tables, detect duplicate symbols.
-    todo!()
+pub fn build_symbol_table(db: &dyn crate::Db, file: SourceFile) -> Option<SymbolTable> {
+    parse_file(db, file)?;
+    let mut symbols = SymbolTable::default();
+    for file in resolve_imports(db, file) {
+        let Some(ast) = parse_file(db, file) else {
+            continue;
+        };
+        let sess = ParseSession::new(db, file);
+        let emitter = DiagnosticEmitter::new(&sess);
+        for node in ast.nodes {
+            let symbol = match node {
+                Node::StructDecl(decl) => Symbol::Struct(decl),
+                Node::ConstructorDecl(decl) => Symbol::Constructor(decl),
+                Node::AnnotationDecl(decl) => Symbol::Annotation(decl),
+                Node::InstanceDecl(decl) => Symbol::Instance(decl),
+                _ => continue,
+            };
+            let name = symbol.name();
+            if let Some(previous) = symbols.insert(db, symbol) {
+                emitter.emit_duplicate_identifier(name, previous.name());
+            }
+        }
+    }
+    Some(symbols)
+}
+
+/// Type-check a file and the files it imports. Returns the evaluated instances
+/// of all of them, in import order, if there were no errors.
+#[salsa::tracked]
+pub fn check_file(db: &dyn crate::Db, file: SourceFile) -> Option<Vec<Instance>> {
+    let symbols = build_symbol_table(db, file)?;
+    let checker = Checker::new(db, &symbols);
+    let mut instances = vec![];
+    let mut ok = true;
+    for file in resolve_imports(db, file) {
+        match parse_file(db, file).and_then(|ast| checker.check_ast(&ast)) {
+            Some(checked) => instances.extend(checked),
+            None => ok = false,
+        }
+    }
+    ok.then_some(instances)
+}
+
+pub(crate) fn find_import(importer: &FsPath, import: &str) -> Option<PathBuf> {
+    let dir = importer.parent().unwrap_or_else(|| FsPath::new(""));
+    let path = dir.join(import);
+    if path.is_file() {
+        return Some(path);
+    }
+    IMPORT_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{import}.{ext}")))
+        .find(|path| path.is_file())
+}
+
+pub(crate) fn canonical(path: &FsPath) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
 /// Turn a span into a snippet of code
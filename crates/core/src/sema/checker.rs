@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+
+use derive_new::new;
+
+use super::symbols::{Symbol, SymbolTable};
+use super::value::{Instance, Value};
+use crate::ast::node::{
+    AliasDecl, Ast, BlockDecl, FieldDecl, FieldType, InstanceDecl, MapDecl, Node, ObjectExpr,
+    StructDecl, UnionDecl, ValueExpr,
+};
+use crate::diagnostics::codes::{self, Code};
+use crate::diagnostics::{Diagnostic, Diagnostics, Level};
+use crate::span::Span;
+use crate::syntax::Token;
+use crate::Db;
+
+/// Checks declarations against the symbol table, and instances against the
+/// declarations of their constructors. Every error is pushed as a diagnostic,
+/// so checking carries on after the first one.
+#[derive(new)]
+pub struct Checker<'db> {
+    db: &'db dyn Db,
+    symbols: &'db SymbolTable,
+}
+
+impl<'db> Checker<'db> {
+    /// Check every declaration in the file, and evaluate its instances
+    pub fn check_ast(&self, ast: &Ast) -> Option<Vec<Instance>> {
+        let mut instances = vec![];
+        let mut ok = true;
+        for node in &ast.nodes {
+            match node {
+                Node::StructDecl(decl) => ok &= self.check_block_types(&decl.content),
+                Node::ConstructorDecl(decl) => ok &= self.check_block_types(&decl.content),
+                Node::InstanceDecl(decl) => match self.check_instance(decl) {
+                    Some(instance) => instances.push(instance),
+                    None => ok = false,
+                },
+                _ => {}
+            }
+        }
+        ok.then_some(instances)
+    }
+
+    pub fn check_instance(&self, decl: &InstanceDecl) -> Option<Instance> {
+        let constructor = decl.constructor.text(self.db);
+        let content = match self.symbols.get(constructor) {
+            Some(Symbol::Constructor(ctor)) => &ctor.content,
+            _ => {
+                self.error(
+                    codes::UNKNOWN_CONSTRUCTOR,
+                    decl.constructor.span,
+                    format!("cannot find constructor `{constructor}`"),
+                    "not found in this scope",
+                );
+                return None;
+            }
+        };
+        let value = self.check_block(constructor, content, &decl.value)?;
+        Some(Instance {
+            constructor: constructor.to_string(),
+            name: decl.name.text(self.db).to_string(),
+            value,
+            span: decl.name.span,
+        })
+    }
+
+    fn check_block_types(&self, block: &BlockDecl) -> bool {
+        match block {
+            // Every field is checked, rather than stopping at the first error
+            BlockDecl::FieldSet(fields) | BlockDecl::Repeatable(fields) => {
+                let mut ok = true;
+                for field in &fields.fields {
+                    ok &= self.check_type(&field.typ);
+                }
+                ok
+            }
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => {
+                self.check_type(&map.key) & self.check_type(&map.value)
+            }
+            BlockDecl::Union(union) => {
+                let mut ok = true;
+                for variant in &union.variants.fields {
+                    ok &= self.check_type(&variant.typ);
+                    if union.discriminator.is_some() && self.variant_struct(variant).is_none() {
+                        self.non_struct_variant(variant);
+                        ok = false;
+                    }
+                }
+                ok
+            }
+        }
+    }
+
+    fn check_type(&self, typ: &FieldType) -> bool {
+        match typ {
+            FieldType::Ident(token) => {
+                let name = token.text(self.db);
+                if self.symbols.resolve_type(self.db, name).is_some() {
+                    return true;
+                }
+                let names = self.symbols.type_names(self.db);
+                self.unknown(
+                    codes::UNKNOWN_TYPE,
+                    *token,
+                    format!("cannot find type `{name}`"),
+                    names.iter().map(String::as_str),
+                );
+                false
+            }
+            FieldType::Map(map) => self.check_type(&map.key) & self.check_type(&map.value),
+            _ => true,
+        }
+    }
+
+    fn check_block(&self, owner: &str, block: &BlockDecl, object: &ObjectExpr) -> Option<Value> {
+        match block {
+            BlockDecl::FieldSet(fields) => self.check_fields(owner, &fields.fields, object),
+            // Each entry of a repeatable block is a set of the declared fields
+            BlockDecl::Repeatable(fields) => {
+                let mut entries = vec![];
+                let mut ok = true;
+                for entry in &object.entries {
+                    let value = match &entry.value {
+                        ValueExpr::Object(inner) => self.check_fields(owner, &fields.fields, inner),
+                        value => self.mismatch(value, "an object"),
+                    };
+                    match value {
+                        Some(value) => entries.push((self.key(entry.key), value)),
+                        None => ok = false,
+                    }
+                }
+                ok.then_some(Value::Object(entries))
+            }
+            BlockDecl::Alias(AliasDecl::MapDecl(map)) => self.check_map(map, object),
+            BlockDecl::Union(union) => self.check_union(owner, union, object),
+        }
+    }
+
+    fn check_union(&self, owner: &str, union: &UnionDecl, object: &ObjectExpr) -> Option<Value> {
+        let variants = &union.variants.fields;
+        let names: Vec<_> = variants
+            .iter()
+            .map(|variant| format!("`{}`", variant.name.text(self.db)))
+            .collect();
+        let expected = format!("expected one of {}", names.join(", "));
+
+        let Some(discriminator) = union.discriminator else {
+            // Without a discriminator the value is wrapped in the variant name
+            let [entry] = object.entries.as_slice() else {
+                self.error(
+                    codes::NOT_A_SINGLE_VARIANT,
+                    object.open.span,
+                    format!("expected a single variant of `{owner}`"),
+                    &expected,
+                );
+                return None;
+            };
+            let key = self.key(entry.key);
+            let Some(variant) = variants
+                .iter()
+                .find(|variant| variant.name.text(self.db) == key)
+            else {
+                self.error(
+                    codes::UNKNOWN_VARIANT,
+                    entry.key.span,
+                    format!("`{key}` is not a variant of `{owner}`"),
+                    &expected,
+                );
+                return None;
+            };
+            let value = self.check_value(&variant.typ, &entry.value)?;
+            return Some(Value::Object(vec![(key, value)]));
+        };
+
+        let field = discriminator.text(self.db);
+        let Some(tag) = object
+            .entries
+            .iter()
+            .find(|entry| self.key(entry.key) == field)
+        else {
+            self.error(
+                codes::MISSING_FIELD,
+                object.open.span,
+                format!("missing field `{field}` in `{owner}`"),
+                &expected,
+            );
+            return None;
+        };
+        let ValueExpr::String(token) = &tag.value else {
+            return self.mismatch(&tag.value, "the name of a variant");
+        };
+        let name = token.unquoted(self.db);
+        let Some(variant) = variants
+            .iter()
+            .find(|variant| variant.name.text(self.db) == name)
+        else {
+            self.error(
+                codes::UNKNOWN_VARIANT,
+                token.span,
+                format!("`{name}` is not a variant of `{owner}`"),
+                &expected,
+            );
+            return None;
+        };
+        // The declaration reports this too, as the same diagnostic, but the
+        // instance says why it can't be checked rather than being dropped
+        let Some(decl) = self.variant_struct(variant) else {
+            self.non_struct_variant(variant);
+            return None;
+        };
+
+        // The rest of the fields belong to the variant
+        let rest = ObjectExpr {
+            open: object.open,
+            entries: object
+                .entries
+                .iter()
+                .filter(|entry| self.key(entry.key) != field)
+                .cloned()
+                .collect(),
+        };
+        let variant_name = decl.name.text(self.db);
+        let Value::Object(fields) = self.check_block(variant_name, &decl.content, &rest)? else {
+            return None;
+        };
+        let mut values = vec![(field.to_string(), Value::String(name.to_string()))];
+        values.extend(fields);
+        Some(Value::Object(values))
+    }
+
+    // The struct of a variant of a union with a discriminator, which has to be
+    // a set of fields for the discriminator to sit next to
+    fn variant_struct(&self, variant: &FieldDecl) -> Option<&'db StructDecl> {
+        let FieldType::Ident(typ) = &variant.typ else {
+            return None;
+        };
+        match self.symbols.resolve_type(self.db, typ.text(self.db)) {
+            Some(Symbol::Struct(decl)) if matches!(decl.content, BlockDecl::FieldSet(_)) => {
+                Some(decl)
+            }
+            _ => None,
+        }
+    }
+
+    fn non_struct_variant(&self, variant: &FieldDecl) {
+        self.error(
+            codes::NON_STRUCT_VARIANT,
+            variant.typ.token().span,
+            "variants of a union with a discriminator must be structs with fields".to_string(),
+            "not a struct with fields",
+        );
+    }
+
+    fn check_fields(
+        &self,
+        owner: &str,
+        fields: &[FieldDecl],
+        object: &ObjectExpr,
+    ) -> Option<Value> {
+        let mut ok = true;
+        let mut seen = HashMap::new();
+        for entry in &object.entries {
+            let key = self.key(entry.key);
+            if let Some(&first) = seen.get(&key) {
+                let diagnostic = self
+                    .diagnostic(
+                        codes::DUPLICATE_FIELD,
+                        entry.key.span,
+                        format!("field `{key}` is specified more than once"),
+                        "used more than once",
+                    )
+                    .with_label(first, format!("`{key}` first specified here"));
+                Diagnostics::push(self.db, diagnostic);
+                ok = false;
+                continue;
+            }
+            seen.insert(key.clone(), entry.key.span);
+            if !fields.iter().any(|field| field.name.text(self.db) == key) {
+                let names = fields.iter().map(|field| field.name.text(self.db));
+                let diagnostic = self
+                    .diagnostic(
+                        codes::UNKNOWN_FIELD,
+                        entry.key.span,
+                        format!("`{owner}` has no field named `{key}`"),
+                        "unknown field",
+                    )
+                    .with_similar_name(entry.key.span, &key, names);
+                Diagnostics::push(self.db, diagnostic);
+                ok = false;
+            }
+        }
+
+        let mut values = vec![];
+        let mut missing = vec![];
+        for field in fields {
+            let name = field.name.text(self.db);
+            let entry = object
+                .entries
+                .iter()
+                .find(|entry| self.key(entry.key) == name);
+            match entry {
+                Some(entry) => match self.check_value(&field.typ, &entry.value) {
+                    Some(value) => values.push((name.to_string(), value)),
+                    None => ok = false,
+                },
+                None if field.optional => {}
+                None => missing.push(format!("`{name}`")),
+            }
+        }
+
+        if !missing.is_empty() {
+            let plural = if missing.len() == 1 { "" } else { "s" };
+            self.error(
+                codes::MISSING_FIELD,
+                object.open.span,
+                format!("missing field{plural} {} in `{owner}`", missing.join(", ")),
+                &format!("missing {}", missing.join(", ")),
+            );
+            ok = false;
+        }
+        ok.then_some(Value::Object(values))
+    }
+
+    fn check_map(&self, map: &MapDecl, object: &ObjectExpr) -> Option<Value> {
+        let mut entries = vec![];
+        let mut ok = true;
+        for entry in &object.entries {
+            let key = self.key(entry.key);
+            let key_ok = match map.key {
+                FieldType::String(_) => true,
+                FieldType::Uint32(_) | FieldType::Uint64(_) => key.parse::<u64>().is_ok(),
+                FieldType::Int32(_) | FieldType::Int64(_) => key.parse::<i64>().is_ok(),
+                FieldType::Float32(_) | FieldType::Float64(_) => key.parse::<f64>().is_ok(),
+                _ => true,
+            };
+            if !key_ok {
+                self.error(
+                    codes::MISMATCHED_TYPES,
+                    entry.key.span,
+                    "mismatched types".to_string(),
+                    "expected a numeric key",
+                );
+                ok = false;
+                continue;
+            }
+            match self.check_value(&map.value, &entry.value) {
+                Some(value) => entries.push((key, value)),
+                None => ok = false,
+            }
+        }
+        ok.then_some(Value::Object(entries))
+    }
+
+    fn check_value(&self, typ: &FieldType, value: &ValueExpr) -> Option<Value> {
+        match (typ, value) {
+            (FieldType::String(_), ValueExpr::String(token)) => {
+                Some(Value::String(token.unquoted(self.db).to_string()))
+            }
+            (FieldType::String(_), value) => self.mismatch(value, "a string"),
+            (FieldType::Uint32(_), ValueExpr::Int(token)) => self.int(token, "uint32", |text| {
+                text.parse::<u32>().ok().map(u64::from).map(Value::Uint)
+            }),
+            (FieldType::Uint64(_), ValueExpr::Int(token)) => self.int(token, "uint64", |text| {
+                text.parse::<u64>().ok().map(Value::Uint)
+            }),
+            (FieldType::Int32(_), ValueExpr::Int(token)) => self.int(token, "int32", |text| {
+                text.parse::<i32>().ok().map(i64::from).map(Value::Int)
+            }),
+            (FieldType::Int64(_), ValueExpr::Int(token)) => self.int(token, "int64", |text| {
+                text.parse::<i64>().ok().map(Value::Int)
+            }),
+            (
+                FieldType::Uint32(_)
+                | FieldType::Uint64(_)
+                | FieldType::Int32(_)
+                | FieldType::Int64(_),
+                value,
+            ) => self.mismatch(value, "an integer"),
+            (
+                FieldType::Float32(_) | FieldType::Float64(_),
+                ValueExpr::Int(token) | ValueExpr::Float(token),
+            ) => match typ {
+                FieldType::Float32(_) => {
+                    self.float(token, "float32", |value| (value as f32).is_finite())
+                }
+                _ => self.float(token, "float64", f64::is_finite),
+            },
+            (FieldType::Float32(_) | FieldType::Float64(_), value) => {
+                self.mismatch(value, "a number")
+            }
+            (FieldType::Unknown(_), value) => self.check_untyped(value),
+            (FieldType::Struct(_), ValueExpr::Ident(token)) => {
+                let name = token.text(self.db);
+                match self.symbols.get(name) {
+                    Some(Symbol::Struct(_)) => Some(Value::Type(name.to_string())),
+                    _ => {
+                        let structs = self.names(|symbol| matches!(symbol, Symbol::Struct(_)));
+                        self.unknown(
+                            codes::UNKNOWN_STRUCT,
+                            *token,
+                            format!("cannot find struct `{name}`"),
+                            structs,
+                        );
+                        None
+                    }
+                }
+            }
+            (FieldType::Struct(_), value) => self.mismatch(value, "the name of a struct"),
+            (FieldType::Map(map), ValueExpr::Object(object)) => self.check_map(map, object),
+            (FieldType::Map(_), value) => self.mismatch(value, "an object"),
+            (FieldType::Ident(typ), value) => self.check_named(*typ, value),
+        }
+    }
+
+    // Values of a struct type are written inline, while values of a
+    // constructor type can also refer to an instance by name
+    fn check_named(&self, typ: Token, value: &ValueExpr) -> Option<Value> {
+        let type_name = typ.text(self.db);
+        // Unknown types are reported once, where they are declared
+        let symbol = self.symbols.resolve_type(self.db, type_name)?;
+        match (symbol, value) {
+            (Symbol::Struct(decl), ValueExpr::Object(object)) => {
+                self.check_block(type_name, &decl.content, object)
+            }
+            (Symbol::Constructor(decl), ValueExpr::Object(object)) => {
+                self.check_block(type_name, &decl.content, object)
+            }
+            (Symbol::Constructor(decl), ValueExpr::Ident(token)) => {
+                let name = token.text(self.db);
+                match self.symbols.get(name) {
+                    Some(Symbol::Instance(instance))
+                        if instance.constructor.text(self.db) == decl.name.text(self.db) =>
+                    {
+                        Some(Value::Ref(name.to_string()))
+                    }
+                    Some(Symbol::Instance(instance)) => {
+                        let constructor = instance.constructor.text(self.db);
+                        let diagnostic = self
+                            .diagnostic(
+                                codes::MISMATCHED_TYPES,
+                                token.span,
+                                "mismatched types".to_string(),
+                                &format!("expected a `{type_name}`, found a `{constructor}`"),
+                            )
+                            .with_label(
+                                instance.name.span,
+                                format!("`{name}` is declared as a `{constructor}` here"),
+                            );
+                        Diagnostics::push(self.db, diagnostic);
+                        None
+                    }
+                    _ => {
+                        let instances = self.names(|symbol| match symbol {
+                            Symbol::Instance(instance) => {
+                                instance.constructor.text(self.db) == decl.name.text(self.db)
+                            }
+                            _ => false,
+                        });
+                        self.unknown(
+                            codes::UNKNOWN_NAME,
+                            *token,
+                            format!("cannot find `{name}`"),
+                            instances,
+                        );
+                        None
+                    }
+                }
+            }
+            (_, value) => self.mismatch(value, &format!("a `{type_name}` object")),
+        }
+    }
+
+    // Values of fields declared as `unknown` are taken as they are written
+    fn check_untyped(&self, value: &ValueExpr) -> Option<Value> {
+        match value {
+            ValueExpr::String(token) => Some(Value::String(token.unquoted(self.db).to_string())),
+            ValueExpr::Int(token) => self.int(token, "uint64", |text| {
+                text.parse::<u64>().ok().map(Value::Uint)
+            }),
+            ValueExpr::Float(token) => self.float(token, "float64", f64::is_finite),
+            ValueExpr::Ident(token) => {
+                let name = token.text(self.db);
+                match self.symbols.get(name) {
+                    Some(Symbol::Instance(_)) => Some(Value::Ref(name.to_string())),
+                    Some(Symbol::Struct(_)) => Some(Value::Type(name.to_string())),
+                    _ => {
+                        let names = self.names(|symbol| {
+                            matches!(symbol, Symbol::Instance(_) | Symbol::Struct(_))
+                        });
+                        self.unknown(
+                            codes::UNKNOWN_NAME,
+                            *token,
+                            format!("cannot find `{name}`"),
+                            names,
+                        );
+                        None
+                    }
+                }
+            }
+            ValueExpr::Object(object) => {
+                let mut entries = vec![];
+                let mut ok = true;
+                for entry in &object.entries {
+                    match self.check_untyped(&entry.value) {
+                        Some(value) => entries.push((self.key(entry.key), value)),
+                        None => ok = false,
+                    }
+                }
+                ok.then_some(Value::Object(entries))
+            }
+        }
+    }
+
+    fn int(
+        &self,
+        token: &Token,
+        typ: &str,
+        parse: impl Fn(&str) -> Option<Value>,
+    ) -> Option<Value> {
+        let value = parse(token.text(self.db));
+        if value.is_none() {
+            self.out_of_range(token, typ);
+        }
+        value
+    }
+
+    // Floats that don't parse are as out of range as ones that overflow
+    fn float(&self, token: &Token, typ: &str, fits: impl Fn(f64) -> bool) -> Option<Value> {
+        let value = token
+            .text(self.db)
+            .parse::<f64>()
+            .ok()
+            .filter(|&value| fits(value));
+        if value.is_none() {
+            self.out_of_range(token, typ);
+        }
+        value.map(Value::Float)
+    }
+
+    fn key(&self, token: Token) -> String {
+        token.unquoted(self.db).to_string()
+    }
+
+    fn mismatch(&self, value: &ValueExpr, expected: &str) -> Option<Value> {
+        let found = match value {
+            ValueExpr::String(_) => "a string",
+            ValueExpr::Int(_) | ValueExpr::Float(_) => "a number",
+            ValueExpr::Ident(_) => "a name",
+            ValueExpr::Object(_) => "an object",
+        };
+        self.error(
+            codes::MISMATCHED_TYPES,
+            value.token().span,
+            "mismatched types".to_string(),
+            &format!("expected {expected}, found {found}"),
+        );
+        None
+    }
+
+    fn out_of_range(&self, token: &Token, typ: &str) {
+        self.error(
+            codes::OUT_OF_RANGE,
+            token.span,
+            format!("`{}` is out of range for {typ}", token.text(self.db)),
+            "value does not fit",
+        );
+    }
+
+    // Report a name that doesn't resolve, suggesting the most similar of the
+    // names it could have been
+    fn unknown<'b>(
+        &self,
+        code: Code,
+        token: Token,
+        message: String,
+        candidates: impl IntoIterator<Item = &'b str>,
+    ) {
+        let name = token.text(self.db);
+        let diagnostic = self
+            .diagnostic(code, token.span, message, "not found in this scope")
+            .with_similar_name(token.span, name, candidates);
+        Diagnostics::push(self.db, diagnostic);
+    }
+
+    // The names of the symbols that match `filter`
+    fn names(&self, filter: impl Fn(&Symbol) -> bool) -> impl Iterator<Item = &str> {
+        self.symbols
+            .iter()
+            .filter(move |(_, symbol)| filter(symbol))
+            .map(|(name, _)| name)
+    }
+
+    fn error(&self, code: Code, span: Span, message: String, label: &str) {
+        Diagnostics::push(self.db, self.diagnostic(code, span, message, label));
+    }
+
+    fn diagnostic(&self, code: Code, span: Span, message: String, label: &str) -> Diagnostic {
+        Diagnostic::new(Level::Error, message, Some(span), Some(label.to_string())).with_code(code)
+    }
+}
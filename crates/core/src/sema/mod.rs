@@ -0,0 +1,9 @@
+mod checker;
+mod symbols;
+#[cfg(test)]
+mod test;
+mod value;
+
+pub use checker::Checker;
+pub use symbols::{type_name, Symbol, SymbolTable};
+pub use value::{Instance, Value};
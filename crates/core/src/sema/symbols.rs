@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use crate::ast::node::{AnnotationDecl, ConstructorDecl, InstanceDecl, StructDecl};
+use crate::syntax::Token;
+use crate::Db;
+
+/// Every named declaration visible from a file, including the declarations
+/// of the files it imports.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    symbols: BTreeMap<String, Symbol>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Symbol {
+    Struct(StructDecl),
+    Constructor(ConstructorDecl),
+    Annotation(AnnotationDecl),
+    Instance(InstanceDecl),
+}
+
+impl Symbol {
+    pub fn name(&self) -> Token {
+        match self {
+            Symbol::Struct(decl) => decl.name,
+            Symbol::Constructor(decl) => decl.name,
+            Symbol::Annotation(decl) => decl.name,
+            Symbol::Instance(decl) => decl.name,
+        }
+    }
+}
+
+impl SymbolTable {
+    /// Add a symbol. If the name is already taken the table is left unchanged
+    /// and the existing symbol is returned.
+    pub fn insert(&mut self, db: &dyn Db, symbol: Symbol) -> Option<&Symbol> {
+        let name = symbol.name().text(db).to_string();
+        if self.symbols.contains_key(&name) {
+            return self.symbols.get(&name);
+        }
+        self.symbols.insert(name, symbol);
+        None
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(name)
+    }
+
+    /// Look up the declaration a field type refers to. Constructors are
+    /// declared in lower case but are referred to as types in Pascal case, so
+    /// `Endpoint` resolves to `constructor endpoint`.
+    pub fn resolve_type(&self, db: &dyn Db, name: &str) -> Option<&Symbol> {
+        match self.symbols.get(name) {
+            Some(symbol @ (Symbol::Struct(_) | Symbol::Constructor(_))) => Some(symbol),
+            Some(_) => None,
+            None => self.symbols.values().find(|symbol| match symbol {
+                Symbol::Constructor(decl) => type_name(decl.name.text(db)) == name,
+                _ => false,
+            }),
+        }
+    }
+
+    /// The names field types can refer to, which are the structs and the
+    /// constructors in Pascal case
+    pub fn type_names(&self, db: &dyn Db) -> Vec<String> {
+        self.symbols
+            .iter()
+            .filter_map(|(name, symbol)| match symbol {
+                Symbol::Struct(_) => Some(name.clone()),
+                Symbol::Constructor(decl) => Some(type_name(decl.name.text(db))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The names of the declared annotations
+    pub fn annotation_names(&self) -> impl Iterator<Item = &str> {
+        self.iter()
+            .filter(|(_, symbol)| matches!(symbol, Symbol::Annotation(_)))
+            .map(|(name, _)| name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Symbol)> {
+        self.symbols
+            .iter()
+            .map(|(name, symbol)| (name.as_str(), symbol))
+    }
+}
+
+/// The name a constructor is referred to by when it is used as a type
+pub fn type_name(constructor: &str) -> String {
+    let mut chars = constructor.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
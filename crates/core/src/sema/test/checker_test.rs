@@ -0,0 +1,385 @@
+use std::path::PathBuf;
+
+use crate::db::Database;
+use crate::diagnostics::{codes, diagnostics_for, Diagnostics};
+use crate::lint::Config;
+use crate::queries::{check_file, SourceFile, TrackedConfig};
+use crate::sema::{Instance, Value};
+
+const SPEC: &str = r#"
+constructor endpoint {
+    name: string;
+    port?: uint32;
+    request: struct;
+}
+
+constructor api {
+    name: string;
+    endpoints: map<string, Endpoint>;
+}
+
+struct GetFolderRequest {
+    folder_id: string;
+}
+"#;
+
+fn check(text: &str) -> (Option<Vec<Instance>>, Vec<String>) {
+    let db = Database::default();
+    let text = format!("{SPEC}{text}");
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text);
+    let instances = check_file(&db, file);
+    let messages = check_file::accumulated::<Diagnostics>(&db, file)
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+    (instances, messages)
+}
+
+// The suggestions made by the diagnostics in `text`, as `message => replacement`
+fn suggestions(text: &str) -> Vec<String> {
+    let db = Database::default();
+    let text = format!("{SPEC}{text}");
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text);
+    check_file(&db, file);
+    check_file::accumulated::<Diagnostics>(&db, file)
+        .into_iter()
+        .flat_map(|diagnostic| diagnostic.suggestions)
+        .map(|suggestion| {
+            let edits: Vec<_> = suggestion
+                .edits
+                .iter()
+                .map(|edit| format!("{} => {}", edit.span.text(&db), edit.replacement))
+                .collect();
+            format!("{}: {}", suggestion.message, edits.join(", "))
+        })
+        .collect()
+}
+
+fn object(fields: &[(&str, Value)]) -> Value {
+    let fields = fields
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    Value::Object(fields)
+}
+
+#[test]
+fn test_evaluates_instances_in_declaration_order() {
+    let (instances, messages) = check(
+        r#"
+        endpoint GetFolder {
+            request: GetFolderRequest,
+            name: "get_folder",
+        }
+
+        api FolderApi {
+            name: "Folders",
+            endpoints: {
+                "/folders/{folder_id}": GetFolder,
+            },
+        }
+        "#,
+    );
+    assert_eq!(messages, Vec::<String>::new());
+
+    let instances = instances.unwrap();
+    assert_eq!(instances.len(), 2);
+    assert_eq!(instances[0].name, "GetFolder");
+    assert_eq!(instances[0].constructor, "endpoint");
+    assert_eq!(
+        instances[0].value,
+        object(&[
+            ("name", Value::String("get_folder".to_string())),
+            ("request", Value::Type("GetFolderRequest".to_string())),
+        ])
+    );
+    assert_eq!(
+        instances[1].value,
+        object(&[
+            ("name", Value::String("Folders".to_string())),
+            (
+                "endpoints",
+                object(&[("/folders/{folder_id}", Value::Ref("GetFolder".to_string()))])
+            ),
+        ])
+    );
+}
+
+#[test]
+fn test_reports_missing_and_unknown_fields() {
+    let (instances, messages) = check(
+        r#"
+        endpoint GetFolder {
+            nmae: "get_folder",
+        }
+        "#,
+    );
+    assert_eq!(instances, None);
+    assert_eq!(
+        messages,
+        vec![
+            "`endpoint` has no field named `nmae`",
+            "missing fields `name`, `request` in `endpoint`",
+        ]
+    );
+}
+
+#[test]
+fn test_suggests_similar_names() {
+    let suggestions = suggestions(
+        r#"
+        struct Folder {
+            owner: Endpiont;
+            parent: folder;
+            children: Sheep;
+        }
+
+        endpoint GetFolder {
+            nmae: "get_folder",
+            reqeust: GetFolderRequets,
+        }
+
+        api FolderApi {
+            name: "Folders",
+            endpoints: {
+                "/folders": GetFoldr,
+            },
+        }
+        "#,
+    );
+    assert_eq!(
+        suggestions,
+        vec![
+            "did you mean `Endpoint`?: Endpiont => Endpoint",
+            "did you mean `Folder`?: folder => Folder",
+            "did you mean `name`?: nmae => name",
+            "did you mean `request`?: reqeust => request",
+            "did you mean `GetFolder`?: GetFoldr => GetFolder",
+        ]
+    );
+}
+
+#[test]
+fn test_reports_mismatched_and_out_of_range_values() {
+    let (instances, messages) = check(
+        r#"
+        endpoint GetFolder {
+            name: 12,
+            port: 4294967296,
+            request: "GetFolderRequest",
+        }
+        "#,
+    );
+    assert_eq!(instances, None);
+    assert_eq!(
+        messages,
+        vec![
+            "mismatched types",
+            "`4294967296` is out of range for uint32",
+            "mismatched types",
+        ]
+    );
+}
+
+#[test]
+fn test_reports_floats_out_of_range() {
+    let big = format!("1{}.0", "0".repeat(40));
+    let (instances, messages) = check(&format!(
+        r#"
+        constructor gauge {{
+            small: float32;
+            large: float64;
+        }}
+
+        gauge Pressure {{
+            small: {big},
+            large: {big},
+        }}
+        "#
+    ));
+    assert_eq!(instances, None);
+    assert_eq!(
+        messages,
+        vec![format!("`{big}` is out of range for float32")]
+    );
+}
+
+#[test]
+fn test_reports_unknown_types_and_references() {
+    let (instances, messages) = check(
+        r#"
+        struct Folder {
+            owner: User;
+        }
+
+        api FolderApi {
+            name: "Folders",
+            endpoints: {
+                "/folders": FindFolders,
+            },
+        }
+        "#,
+    );
+    assert_eq!(instances, None);
+    assert_eq!(
+        messages,
+        vec!["cannot find type `User`", "cannot find `FindFolders`"]
+    );
+}
+
+#[test]
+fn test_reports_duplicate_declarations() {
+    let (_, messages) = check("struct GetFolderRequest {}");
+    assert_eq!(
+        messages,
+        vec!["`GetFolderRequest` is defined multiple times"]
+    );
+}
+
+#[test]
+fn test_duplicates_point_at_both_sites() {
+    let db = Database::default();
+    let text =
+        format!("{SPEC}struct GetFolderRequest {{}}\nendpoint A {{ name: \"a\", name: \"b\" }}");
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text);
+    check_file(&db, file);
+    let diagnostics = check_file::accumulated::<Diagnostics>(&db, file);
+
+    let duplicate = &diagnostics[0];
+    let first = &duplicate.labels[0];
+    assert_eq!(first.span.text(&db), "GetFolderRequest");
+    assert!(first.span.start < duplicate.span.unwrap().start);
+    assert_eq!(
+        first.message,
+        "previous definition of `GetFolderRequest` here"
+    );
+    assert_eq!(duplicate.notes.len(), 1);
+
+    let field = diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.message == "field `name` is specified more than once")
+        .unwrap();
+    assert_eq!(field.labels[0].message, "`name` first specified here");
+    assert!(field.labels[0].span.start < field.span.unwrap().start);
+}
+
+const UNIONS: &str = r#"
+struct Folder {
+    name: string;
+}
+
+struct Link {
+    url: string;
+}
+
+struct Item {
+    union on kind {
+        folder: Folder;
+        link: Link;
+    }
+}
+
+struct Wrapped {
+    union {
+        folder: Folder;
+        link: Link;
+    }
+}
+
+constructor page {
+    item: Item;
+    wrapped?: Wrapped;
+}
+"#;
+
+#[test]
+fn test_evaluates_unions() {
+    let (instances, messages) = check(&format!(
+        r#"{UNIONS}
+        page Home {{
+            item: {{ kind: "link", url: "/home" }},
+            wrapped: {{ folder: {{ name: "Home" }} }},
+        }}
+        "#
+    ));
+    assert_eq!(messages, Vec::<String>::new());
+    assert_eq!(
+        instances.unwrap()[0].value,
+        object(&[
+            (
+                "item",
+                object(&[
+                    ("kind", Value::String("link".to_string())),
+                    ("url", Value::String("/home".to_string())),
+                ])
+            ),
+            (
+                "wrapped",
+                object(&[(
+                    "folder",
+                    object(&[("name", Value::String("Home".to_string()))])
+                )])
+            ),
+        ])
+    );
+}
+
+#[test]
+fn test_reports_union_variants_without_fields() {
+    let items = r#"
+        struct Tags { map<string, string> }
+        struct Item {
+            union on kind {
+                tags: Tags;
+                name: string;
+            }
+        }
+    "#;
+    let (instances, messages) = check(items);
+    assert_eq!(instances, None);
+    assert_eq!(
+        messages,
+        vec!["variants of a union with a discriminator must be structs with fields"; 2]
+    );
+
+    // An instance of the union can't be checked either, and says why rather
+    // than being dropped. It's the same diagnostic as the declaration's, so
+    // it's only collected once.
+    let db = Database::default();
+    let text = format!(
+        "{items}constructor page {{ item: Item; }}\npage Home {{ item: {{ kind: \"tags\" }} }}\n"
+    );
+    let file = SourceFile::new(&db, PathBuf::from("file.haml"), text);
+    assert_eq!(check_file(&db, file), None);
+    let spans: Vec<_> = check_file::accumulated::<Diagnostics>(&db, file)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.code == Some(codes::NON_STRUCT_VARIANT))
+        .map(|diagnostic| diagnostic.span.unwrap().text(&db))
+        .collect();
+    assert_eq!(spans, ["Tags", "string", "Tags"]);
+    let config = TrackedConfig::new(&db, Config::default());
+    let [(_, diagnostics)] = &diagnostics_for(&db, file, config)[..] else {
+        panic!("expected diagnostics in one file");
+    };
+    assert_eq!(diagnostics.len(), 2);
+}
+
+#[test]
+fn test_reports_unknown_union_variants() {
+    let (instances, messages) = check(&format!(
+        r#"{UNIONS}
+        page Home {{
+            item: {{ kind: "file", url: "/home" }},
+            wrapped: {{ link: {{ url: "/" }}, folder: {{ name: "Home" }} }},
+        }}
+        "#
+    ));
+    assert_eq!(instances, None);
+    assert_eq!(
+        messages,
+        vec![
+            "`file` is not a variant of `Item`",
+            "expected a single variant of `Wrapped`",
+        ]
+    );
+}
@@ -0,0 +1 @@
+pub mod checker_test;
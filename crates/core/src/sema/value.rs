@@ -0,0 +1,29 @@
+use crate::span::Span;
+
+/// The evaluated value of an instance, after it has been checked against the
+/// declaration of its constructor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    /// Fields in the order they are declared, or map entries in the order
+    /// they are written
+    Object(Vec<(String, Value)>),
+    /// A reference to another instance by name
+    Ref(String),
+    /// The name of a struct, for fields declared with the `struct` type
+    Type(String),
+}
+
+// The lexer never produces a NaN, so floats are always comparable
+impl Eq for Value {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instance {
+    pub constructor: String,
+    pub name: String,
+    pub value: Value,
+    pub span: Span,
+}
@@ -1,4 +1,5 @@
 use crate::queries::SourceFile;
+use crate::Db;
 use derive_new::new;
 
 #[derive(new, Debug, Copy, Clone, PartialEq, Eq)]
@@ -7,3 +8,10 @@ pub struct Span {
     pub end: usize,
     pub file: SourceFile,
 }
+
+impl Span {
+    /// The source text covered by this span
+    pub fn text<'db>(&self, db: &'db dyn Db) -> &'db str {
+        &self.file.text(db)[self.start..self.end]
+    }
+}
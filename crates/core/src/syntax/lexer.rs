@@ -8,6 +8,12 @@ pub struct Lexer<'db> {
     emitter: &'db dyn Emitter,
     chars: Chars<'db>,
     len_remaining: usize,
+    pending_docs: Vec<Token>,
+    docs: Vec<Token>,
+    // Braces that haven't been closed yet, innermost last
+    open_braces: Vec<Token>,
+    // Whether lexing stopped on an error rather than at the end of the input
+    failed: bool,
 }
 
 impl<'db> Lexer<'db> {
@@ -17,15 +23,87 @@ impl<'db> Lexer<'db> {
             emitter,
             chars: sess.text().chars(),
             len_remaining: sess.text().len(),
+            pending_docs: vec![],
+            docs: vec![],
+            open_braces: vec![],
+            failed: false,
         }
     }
 
     pub fn advance(&mut self) -> Option<Token> {
-        match self.bump_ignoring_whitespace()? {
-            '"' => self.string_literal(),
-            '0'..='9' => self.numeric_literal(),
-            ch if is_id_head(ch) => self.ident_or_keyword(),
-            _ => self.reserved_char(),
+        loop {
+            let token = match self.bump_ignoring_whitespace()? {
+                '/' if self.peek() == Some('/') => {
+                    self.line_comment();
+                    continue;
+                }
+                '/' if self.peek() == Some('*') => {
+                    self.block_comment()?;
+                    continue;
+                }
+                '"' => self.string_literal(),
+                '0'..='9' => self.numeric_literal(),
+                ch if is_id_head(ch) => self.ident_or_keyword(),
+                _ => self.reserved_char(),
+            };
+            self.docs = std::mem::take(&mut self.pending_docs);
+            return token;
+        }
+    }
+
+    /// Whether all of the input has been read without an error, so `advance`
+    /// returns `None` only because the file ended
+    pub fn at_eof(&self) -> bool {
+        !self.failed && self.chars.as_str().is_empty()
+    }
+
+    /// An empty span at the end of the input
+    pub fn eof(&self) -> Span {
+        let len = self.sess.text().len();
+        Span::new(len, len, self.sess.file)
+    }
+
+    /// The braces that have been opened but not closed, innermost last
+    pub fn unclosed_braces(&self) -> &[Token] {
+        &self.open_braces
+    }
+
+    /// The `///` doc comment lines directly before the last token returned by
+    /// `advance`
+    pub fn docs(&mut self) -> Vec<Token> {
+        std::mem::take(&mut self.docs)
+    }
+
+    // Comments are skipped, so the parser never sees them. Doc comments are
+    // kept aside until the next token, which they document.
+    fn line_comment(&mut self) {
+        self.bump_while(|ch| ch != '\n');
+        let span = self.span();
+        let text = &self.sess.text()[span.start..span.end];
+        if text.starts_with("///") && !text.starts_with("////") {
+            self.pending_docs
+                .push(Token::new(TokenKind::DocComment, span));
+        }
+        self.eat();
+    }
+
+    fn block_comment(&mut self) -> Option<()> {
+        self.bump();
+        loop {
+            match self.bump() {
+                Some('*') if self.peek() == Some('/') => {
+                    self.bump();
+                    self.eat();
+                    return Some(());
+                }
+                Some(_) => continue,
+                None => {
+                    let token = self.eat_and_advance(TokenKind::Invalid);
+                    self.failed = true;
+                    self.emitter.emit_unterminated_comment(token);
+                    return None;
+                }
+            }
         }
     }
 
@@ -34,6 +112,7 @@ impl<'db> Lexer<'db> {
 
         if let None = self.peek() {
             let token = self.eat_and_advance(TokenKind::StringLiteral);
+            self.failed = true;
             self.emitter.emit_unterminated_string(token);
             return None;
         }
@@ -106,11 +185,20 @@ impl<'db> Lexer<'db> {
             "," => TokenKind::Comma,
             "@" => TokenKind::At,
             "." => TokenKind::Period,
+            "=" => TokenKind::Equals,
             "?" => TokenKind::QuestionMark,
             _ => TokenKind::Invalid,
         };
 
-        Some(self.eat_and_advance(kind))
+        let token = self.eat_and_advance(kind);
+        match kind {
+            TokenKind::OpenBrace => self.open_braces.push(token),
+            TokenKind::CloseBrace => {
+                self.open_braces.pop();
+            }
+            _ => {}
+        }
+        Some(token)
     }
 
     fn eat_and_advance(&mut self, kind: TokenKind) -> Token {
@@ -6,4 +6,3 @@ mod token;
 pub use lexer::Lexer;
 pub use parser::{ParseSession, Parser};
 pub use token::{Token, TokenKind};
-
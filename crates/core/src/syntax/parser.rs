@@ -8,9 +8,10 @@ use crate::Db;
 use super::lexer::Lexer;
 use super::token::{Token, TokenKind};
 use crate::ast::node::{
-    AliasDecl, AnnotationDecl, AnnotationFieldDecl, AnnotationFieldValue, Ast, BlockDecl,
-    ConstructorDecl, FieldDecl, FieldSetDecl, FieldType, ImportStmt, MapDecl, Node, PackageStmt,
-    StructDecl,
+    AliasDecl, Annotation, AnnotationDecl, AnnotationFieldDecl, AnnotationFieldValue, Ast,
+    BlockDecl, ConstructorDecl, DocComment, FieldDecl, FieldSetDecl, FieldType, ImportStmt,
+    InstanceDecl, MapDecl, Node, ObjectEntry, ObjectExpr, PackageStmt, StructDecl, UnionDecl,
+    ValueExpr,
 };
 
 #[derive(new)]
@@ -32,6 +33,7 @@ impl<'db> ParseSession<'db> {
 
 /// Turns tokens into statements.
 pub struct Parser<'i> {
+    sess: &'i ParseSession<'i>,
     emitter: &'i dyn Emitter,
     lexer: Lexer<'i>,
 }
@@ -39,6 +41,7 @@ pub struct Parser<'i> {
 impl<'i> Parser<'i> {
     pub fn new(sess: &'i ParseSession<'i>, emitter: &'i dyn Emitter) -> Parser<'i> {
         Parser {
+            sess,
             emitter,
             lexer: Lexer::new(sess, emitter),
         }
@@ -57,17 +60,20 @@ impl<'i> Parser<'i> {
     }
 
     pub fn advance(&mut self) -> Option<Node> {
-        let token = match self.advance_token() {
+        // The file can only end cleanly between declarations
+        let token = match self.lexer.advance() {
             Some(token) => token,
             None => return Some(Node::Eof),
         };
+        let docs = self.docs();
         match token.kind {
             TokenKind::Package => self.package_stmt(),
-            TokenKind::Import => self.import_stmt(),
-            TokenKind::At => self.annotation_def(),
-            TokenKind::Struct => self.struct_decl(vec![]),
-            TokenKind::Constructor => self.constructor_decl(vec![]),
-            TokenKind::Annotation => self.annotation_decl(vec![]),
+            TokenKind::Import => self.import_stmt(vec![]),
+            TokenKind::At => self.annotation_def(docs),
+            TokenKind::Struct => self.struct_decl(docs, vec![]),
+            TokenKind::Constructor => self.constructor_decl(docs, vec![]),
+            TokenKind::Annotation => self.annotation_decl(docs, vec![]),
+            TokenKind::Ident => self.instance_decl(docs, vec![], token),
             _ => {
                 self.emitter
                     .emit_unexpected_token(token, "a package, import or declaration");
@@ -97,38 +103,100 @@ impl<'i> Parser<'i> {
         Some(Node::PackageStmt(stmt))
     }
 
-    fn import_stmt(&mut self) -> Option<Node> {
+    fn import_stmt(&mut self, annotations: Vec<Annotation>) -> Option<Node> {
         let path = self.pop(TokenKind::StringLiteral)?;
         self.pop(TokenKind::Semi)?;
-        let stmt = ImportStmt { path };
+        let stmt = ImportStmt { annotations, path };
         Some(Node::ImportStmt(stmt))
     }
 
-    fn annotation_def(&mut self) -> Option<Node> {
+    fn annotation_def(&mut self, mut docs: DocComment) -> Option<Node> {
+        // Doc comments can go before or after the annotations
+        let (annotations, token) = self.annotations()?;
+        docs.lines.extend(self.docs().lines);
+        match token.kind {
+            TokenKind::Constructor => self.constructor_decl(docs, annotations),
+            TokenKind::Struct => self.struct_decl(docs, annotations),
+            TokenKind::Annotation => self.annotation_decl(docs, annotations),
+            TokenKind::Ident => self.instance_decl(docs, annotations, token),
+            TokenKind::Import => self.import_stmt(annotations),
+            _ => {
+                self.emitter
+                    .emit_unexpected_token(token, "an annotation, constructor or struct");
+                None
+            }
+        }
+    }
+
+    // Annotations after an `@` that has already been read, like
+    // `pack(index = 1) @deprecated`. Returns them with the token after them.
+    fn annotations(&mut self) -> Option<(Vec<Annotation>, Token)> {
         let mut annotations = vec![];
         loop {
             let name = self.pop(TokenKind::Ident)?;
-            annotations.push(name);
+            let mut token = self.advance_token()?;
+            let mut args = vec![];
+            if token.kind == TokenKind::OpenParen {
+                args = self.annotation_args()?;
+                token = self.advance_token()?;
+            }
+            annotations.push(Annotation { name, args });
+            if token.kind != TokenKind::At {
+                return Some((annotations, token));
+            }
+        }
+    }
 
+    // Comma-separated `key = value` pairs, up to and including the closing
+    // paren. A trailing comma is allowed. An argument without a value, like
+    // `@allow(unused_struct)`, has its name as its value.
+    fn annotation_args(&mut self) -> Option<Vec<ObjectEntry>> {
+        let mut args = vec![];
+        loop {
             let token = self.advance_token()?;
-            match token.kind {
-                TokenKind::At => continue,
-                TokenKind::Constructor => return self.constructor_decl(annotations),
-                TokenKind::Struct => return self.struct_decl(annotations),
-                TokenKind::Annotation => return self.annotation_decl(annotations),
+            let key = match token.kind {
+                TokenKind::CloseParen => break,
+                TokenKind::Ident => token,
                 _ => {
                     self.emitter
-                        .emit_unexpected_token(token, "an annotation, constructor or struct");
+                        .emit_unexpected_token(token, "a closing paren or argument name");
                     return None;
                 }
             };
+            let mut token = self.advance_token()?;
+            let value = match token.kind {
+                TokenKind::Equals => {
+                    let value = self.value_expr()?;
+                    token = self.advance_token()?;
+                    value
+                }
+                _ => ValueExpr::Ident(key),
+            };
+            args.push(ObjectEntry { key, value });
+
+            match token.kind {
+                TokenKind::Comma => continue,
+                TokenKind::CloseParen => break,
+                TokenKind::Semi => {
+                    self.emitter
+                        .emit_unexpected_separator(token, TokenKind::Comma);
+                    continue;
+                }
+                _ => {
+                    self.emitter
+                        .emit_unexpected_token(token, "a comma or closing paren");
+                    return None;
+                }
+            }
         }
+        Some(args)
     }
 
-    fn struct_decl(&mut self, annotations: Vec<Token>) -> Option<Node> {
+    fn struct_decl(&mut self, docs: DocComment, annotations: Vec<Annotation>) -> Option<Node> {
         let name = self.pop(TokenKind::Ident)?;
         let content = self.block_decl()?;
         let stmt = StructDecl {
+            docs,
             annotations,
             name,
             content,
@@ -136,10 +204,11 @@ impl<'i> Parser<'i> {
         Some(Node::StructDecl(stmt))
     }
 
-    fn constructor_decl(&mut self, annotations: Vec<Token>) -> Option<Node> {
+    fn constructor_decl(&mut self, docs: DocComment, annotations: Vec<Annotation>) -> Option<Node> {
         let name = self.pop(TokenKind::Ident)?;
         let content = self.block_decl()?;
         let stmt = ConstructorDecl {
+            docs,
             annotations,
             name,
             content,
@@ -159,8 +228,9 @@ impl<'i> Parser<'i> {
                 let decl = AliasDecl::MapDecl(map);
                 Some(BlockDecl::Alias(decl))
             }
-            TokenKind::Ident => {
-                let fields = self.field_set_decl(Some(discriminator))?;
+            TokenKind::Ident | TokenKind::At => {
+                let docs = self.docs();
+                let fields = self.field_set_decl(Some((docs, discriminator)))?;
                 Some(BlockDecl::FieldSet(fields))
             }
             TokenKind::CloseBrace => {
@@ -176,10 +246,23 @@ impl<'i> Parser<'i> {
     }
 
     fn union_decl(&mut self) -> Option<BlockDecl> {
-        self.pop(TokenKind::OpenBrace)?;
-        let fields = self.field_set_decl(None)?;
+        let mut token = self.advance_token()?;
+        let mut discriminator = None;
+        if token.kind == TokenKind::Ident && self.sess.span_text(&token.span) == "on" {
+            discriminator = Some(self.pop(TokenKind::Ident)?);
+            token = self.advance_token()?;
+        }
+        if token.kind != TokenKind::OpenBrace {
+            self.emitter.emit_unexpected_token(token, "`on` or {");
+            return None;
+        }
+        let variants = self.field_set_decl(None)?;
         self.pop(TokenKind::CloseBrace)?;
-        Some(BlockDecl::FieldSet(fields))
+        let decl = UnionDecl {
+            discriminator,
+            variants,
+        };
+        Some(BlockDecl::Union(decl))
     }
 
     fn repeatable_decl(&mut self) -> Option<BlockDecl> {
@@ -189,10 +272,11 @@ impl<'i> Parser<'i> {
         Some(BlockDecl::Repeatable(fields))
     }
 
-    fn annotation_decl(&mut self, annotations: Vec<Token>) -> Option<Node> {
+    fn annotation_decl(&mut self, docs: DocComment, annotations: Vec<Annotation>) -> Option<Node> {
         let name = self.pop(TokenKind::Ident)?;
         let fields = self.annotation_field_set_decl()?;
         let stmt = AnnotationDecl {
+            docs,
             annotations,
             name,
             fields,
@@ -200,76 +284,143 @@ impl<'i> Parser<'i> {
         Some(Node::AnnotationDecl(stmt))
     }
 
-    // Set of nested key-value pairs inside two braces. `leading_ident` is provided
-    // so block_decl can call field_set_decl if it encounters a identifier. The
-    // lexer doesn't support peeking so we have to work without this lookahead.
-    fn field_set_decl(&mut self, leading_ident: Option<Token>) -> Option<FieldSetDecl> {
-        let mut fields = vec![];
+    fn instance_decl(
+        &mut self,
+        docs: DocComment,
+        annotations: Vec<Annotation>,
+        constructor: Token,
+    ) -> Option<Node> {
+        let name = self.pop(TokenKind::Ident)?;
+        let open = self.pop(TokenKind::OpenBrace)?;
+        let value = self.object_expr(open)?;
+        let stmt = InstanceDecl {
+            docs,
+            annotations,
+            constructor,
+            name,
+            value,
+        };
+        Some(Node::InstanceDecl(stmt))
+    }
 
-        if let Some(name) = leading_ident {
-            let mut optional = false;
+    // Comma-separated key-value pairs, where `open` is the opening brace that
+    // has already been consumed. A trailing comma is allowed.
+    fn object_expr(&mut self, open: Token) -> Option<ObjectExpr> {
+        let mut entries = vec![];
+        loop {
             let token = self.advance_token()?;
-            match token.kind {
-                TokenKind::QuestionMark => {
-                    optional = true;
-                    self.pop(TokenKind::Colon)?;
-                }
-                TokenKind::Colon => {}
+            let key = match token.kind {
+                TokenKind::CloseBrace => break,
+                TokenKind::Ident | TokenKind::StringLiteral => token,
                 _ => {
                     self.emitter
-                        .emit_unexpected_token(token, "a question mark or colon");
+                        .emit_unexpected_token(token, "a closing brace or key");
                     return None;
                 }
             };
-            let value = self.field_value()?;
-            self.pop(TokenKind::Semi)?;
-            fields.push(FieldDecl {
-                name,
-                typ: value,
-                optional,
-            })
-        }
+            self.pop(TokenKind::Colon)?;
+            let value = self.value_expr()?;
+            entries.push(ObjectEntry { key, value });
 
-        loop {
-            let mut optional = false;
             let token = self.advance_token()?;
             match token.kind {
+                TokenKind::Comma => continue,
                 TokenKind::CloseBrace => break,
-                TokenKind::Ident => {}
+                TokenKind::Semi => {
+                    self.emitter
+                        .emit_unexpected_separator(token, TokenKind::Comma);
+                    continue;
+                }
                 _ => {
                     self.emitter
-                        .emit_unexpected_token(token, "a closing brace or identifier");
+                        .emit_unexpected_token(token, "a comma or closing brace");
                     return None;
                 }
-            };
-            let name = token;
+            }
+        }
+        Some(ObjectExpr { open, entries })
+    }
 
-            let token = self.advance_token()?;
-            match token.kind {
-                TokenKind::QuestionMark => {
-                    optional = true;
-                    self.pop(TokenKind::Colon)?;
+    fn value_expr(&mut self) -> Option<ValueExpr> {
+        let token = self.advance_token()?;
+        let value = match token.kind {
+            TokenKind::StringLiteral => ValueExpr::String(token),
+            TokenKind::IntLiteral => ValueExpr::Int(token),
+            TokenKind::FloatLiteral => ValueExpr::Float(token),
+            TokenKind::Ident => ValueExpr::Ident(token),
+            TokenKind::OpenBrace => ValueExpr::Object(self.object_expr(token)?),
+            _ => {
+                self.emitter
+                    .emit_unexpected_token(token, "a string, number, name or object");
+                return None;
+            }
+        };
+        Some(value)
+    }
+
+    // Fields up to and including the closing brace. `leading` is the first
+    // token of the first field and the doc comment before it, so block_decl can
+    // call field_set_decl if it encounters a field. The lexer doesn't support
+    // peeking so we have to work without this lookahead.
+    fn field_set_decl(&mut self, mut leading: Option<(DocComment, Token)>) -> Option<FieldSetDecl> {
+        let mut fields = vec![];
+        loop {
+            let (mut docs, mut token) = match leading.take() {
+                Some(leading) => leading,
+                None => {
+                    let token = self.advance_token()?;
+                    (self.docs(), token)
                 }
-                TokenKind::Colon => {}
+            };
+            let mut annotations = vec![];
+            if token.kind == TokenKind::At {
+                (annotations, token) = self.annotations()?;
+                docs.lines.extend(self.docs().lines);
+            }
+            match token.kind {
+                TokenKind::CloseBrace if annotations.is_empty() => break,
+                TokenKind::Ident => {}
                 _ => {
                     self.emitter
-                        .emit_unexpected_token(token, "question mark or colon");
+                        .emit_unexpected_token(token, "a closing brace or identifier");
                     return None;
                 }
             };
-
-            let value = self.field_value()?;
-            self.pop(TokenKind::Semi)?;
-
-            fields.push(FieldDecl {
-                name,
-                typ: value,
-                optional,
-            })
+            fields.push(self.field_decl(docs, annotations, token)?);
         }
+        Some(FieldSetDecl { fields })
+    }
 
-        let decl = FieldSetDecl { fields };
-        Some(decl)
+    // The rest of a field after its name, like `?: string;`
+    fn field_decl(
+        &mut self,
+        docs: DocComment,
+        annotations: Vec<Annotation>,
+        name: Token,
+    ) -> Option<FieldDecl> {
+        let mut optional = false;
+        let token = self.advance_token()?;
+        match token.kind {
+            TokenKind::QuestionMark => {
+                optional = true;
+                self.pop(TokenKind::Colon)?;
+            }
+            TokenKind::Colon => {}
+            _ => {
+                self.emitter
+                    .emit_unexpected_token(token, "a question mark or colon");
+                return None;
+            }
+        };
+        let typ = self.field_value()?;
+        self.pop(TokenKind::Semi)?;
+        Some(FieldDecl {
+            docs,
+            annotations,
+            name,
+            typ,
+            optional,
+        })
     }
 
     fn field_value(&mut self) -> Option<FieldType> {
@@ -377,6 +528,11 @@ impl<'i> Parser<'i> {
         let token = self.advance_token()?;
         if token.kind == kind {
             Some(token)
+        } else if is_separator(kind) && is_separator(token.kind) {
+            // Mixing up separators is common enough to carry on as if the
+            // right one was used, so the rest of them are found too
+            self.emitter.emit_unexpected_separator(token, kind);
+            Some(token)
         } else {
             self.emitter
                 .emit_unexpected_token(token, &format!("{kind}"));
@@ -384,7 +540,25 @@ impl<'i> Parser<'i> {
         }
     }
 
+    // The next token, part way through a declaration, where the file ending is
+    // an error
     fn advance_token(&mut self) -> Option<Token> {
-        self.lexer.advance()
+        let token = self.lexer.advance();
+        if token.is_none() && self.lexer.at_eof() {
+            self.emitter
+                .emit_unexpected_eof(self.lexer.eof(), self.lexer.unclosed_braces());
+        }
+        token
+    }
+
+    // The doc comment before the last token
+    fn docs(&mut self) -> DocComment {
+        DocComment {
+            lines: self.lexer.docs(),
+        }
     }
 }
+
+fn is_separator(kind: TokenKind) -> bool {
+    matches!(kind, TokenKind::Semi | TokenKind::Comma)
+}
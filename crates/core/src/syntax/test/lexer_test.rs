@@ -128,3 +128,67 @@ fn test_lexes_reserved_characters() {
         assert_eq!(token, Some(expected));
     });
 }
+
+#[test]
+fn test_skips_comments() {
+    let cases = vec![
+        ("// comment\nfoo", 11),
+        ("/* comment */foo", 13),
+        ("/**\n * doc comment\n */ foo", 23),
+        ("// one\n// two\nfoo", 14),
+    ];
+
+    cases.iter().for_each(|(text, start)| {
+        let db = Database::default();
+        let ctx = TestContext::new(&db, text);
+        let mut lexer = ctx.lexer();
+
+        let span = ctx.span(*start, start + 3);
+        let expected = Token::new(TokenKind::Ident, span);
+
+        let token = lexer.advance();
+        assert_eq!(token, Some(expected));
+    });
+}
+
+#[test]
+fn test_unterminated_comment_ends_input() {
+    let db = Database::default();
+    let ctx = TestContext::new(&db, "/* comment");
+    let mut lexer = ctx.lexer();
+    assert_eq!(lexer.advance(), None);
+}
+
+#[test]
+fn test_lexes_lone_slash_as_invalid() {
+    let db = Database::default();
+    let ctx = TestContext::new(&db, "/");
+    let mut lexer = ctx.lexer();
+
+    let span = ctx.span(0, 1);
+    let expected = Token::new(TokenKind::Invalid, span);
+    assert_eq!(lexer.advance(), Some(expected));
+}
+
+#[test]
+fn test_keeps_doc_comments_for_next_token() {
+    let text = "// skipped\n/// first\n/// second\nfoo bar";
+
+    let db = Database::default();
+    let ctx = TestContext::new(&db, text);
+    let mut lexer = ctx.lexer();
+
+    let span = ctx.span(32, 35);
+    let token = Token::new(TokenKind::Ident, span);
+    assert_eq!(lexer.advance(), Some(token));
+    assert_eq!(
+        lexer.docs(),
+        vec![
+            Token::new(TokenKind::DocComment, ctx.span(11, 20)),
+            Token::new(TokenKind::DocComment, ctx.span(21, 31)),
+        ]
+    );
+
+    lexer.advance();
+    assert_eq!(lexer.docs(), vec![]);
+}
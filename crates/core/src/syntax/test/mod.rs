@@ -1,2 +1,3 @@
 pub mod lexer_test;
+pub mod parser_test;
 pub mod support;
@@ -0,0 +1,60 @@
+use crate::ast::node::{BlockDecl, Node, ValueExpr};
+use crate::db::Database;
+use crate::syntax::test::support::TestContext;
+
+#[test]
+fn test_parses_annotations_on_structs_and_fields() {
+    let text = r#"
+        /// A request
+        @deprecated(expiry = "2024-06-03", version = 2)
+        struct GetUserRequest {
+            /// The user
+            @pack(index = 1)
+            @required
+            id: string;
+        }
+    "#;
+
+    let db = Database::default();
+    let ctx = TestContext::new(&db, text);
+    let ast = ctx.parser().parse().unwrap();
+
+    let Node::StructDecl(decl) = &ast.nodes[0] else {
+        panic!("expected a struct, found {:?}", ast.nodes[0]);
+    };
+    assert_eq!(decl.docs.text(&db).as_deref(), Some("A request"));
+    let deprecated = &decl.annotations[0];
+    assert_eq!(deprecated.name.text(&db), "deprecated");
+    let expiry = deprecated.arg(&db, "expiry").unwrap();
+    assert!(matches!(expiry, ValueExpr::String(token) if token.unquoted(&db) == "2024-06-03"));
+    assert!(matches!(
+        deprecated.arg(&db, "version"),
+        Some(ValueExpr::Int(_))
+    ));
+
+    let BlockDecl::FieldSet(fields) = &decl.content else {
+        panic!("expected fields, found {:?}", decl.content);
+    };
+    let field = &fields.fields[0];
+    assert_eq!(field.name.text(&db), "id");
+    assert_eq!(field.docs.text(&db).as_deref(), Some("The user"));
+    let names: Vec<_> = field
+        .annotations
+        .iter()
+        .map(|annotation| annotation.name.text(&db))
+        .collect();
+    assert_eq!(names, ["pack", "required"]);
+    assert!(matches!(
+        field.annotations[0].arg(&db, "index"),
+        Some(ValueExpr::Int(_))
+    ));
+}
+
+#[test]
+fn test_annotations_without_a_field_are_an_error() {
+    let text = "struct Empty { @pack(index = 1) }";
+
+    let db = Database::default();
+    let ctx = TestContext::new(&db, text);
+    assert_eq!(ctx.parser().parse(), None);
+}
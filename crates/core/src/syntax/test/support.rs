@@ -4,7 +4,7 @@ use crate::{
     diagnostics::Emitter,
     queries::SourceFile,
     span::Span,
-    syntax::{Lexer, ParseSession},
+    syntax::{Lexer, ParseSession, Parser},
     Db,
 };
 
@@ -31,6 +31,10 @@ impl<'db> TestContext<'db> {
         Lexer::new(&self.sess, &self.emitter)
     }
 
+    pub fn parser(&self) -> Parser {
+        Parser::new(&self.sess, &self.emitter)
+    }
+
     pub fn span(&self, start: usize, end: usize) -> Span {
         Span::new(start, end, self.file)
     }
@@ -43,11 +47,15 @@ impl Emitter for MockEmitter {
         // pass
     }
 
-    fn emit_unexpected_eof(&self, token: crate::syntax::Token) {
+    fn emit_unexpected_eof(&self, eof: Span, unclosed: &[crate::syntax::Token]) {
         // pass
     }
 
-    fn emit_duplicate_identifier(&self, token: crate::syntax::Token) {
+    fn emit_duplicate_identifier(
+        &self,
+        token: crate::syntax::Token,
+        previous: crate::syntax::Token,
+    ) {
         // pass
     }
 
@@ -55,6 +63,14 @@ impl Emitter for MockEmitter {
         // pass
     }
 
+    fn emit_unexpected_separator(
+        &self,
+        token: crate::syntax::Token,
+        expected: crate::syntax::TokenKind,
+    ) {
+        // pass
+    }
+
     fn emit_unterminated_comment(&self, token: crate::syntax::Token) {
         // pass
     }
@@ -1,4 +1,5 @@
 use crate::span::Span;
+use crate::Db;
 use derive_new::new;
 use std::fmt::Display;
 
@@ -8,6 +9,19 @@ pub struct Token {
     pub span: Span,
 }
 
+impl Token {
+    pub fn text<'db>(&self, db: &'db dyn Db) -> &'db str {
+        self.span.text(db)
+    }
+
+    /// The text of the token without surrounding quotes, for string literals
+    pub fn unquoted<'db>(&self, db: &'db dyn Db) -> &'db str {
+        let text = self.text(db);
+        let text = text.strip_prefix('"').unwrap_or(text);
+        text.strip_suffix('"').unwrap_or(text)
+    }
+}
+
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum TokenKind {
     // package
@@ -76,6 +90,10 @@ pub enum TokenKind {
     Comma,
     // Period
     Period,
+    // =
+    Equals,
+    // /// The line of a doc comment
+    DocComment,
     // Any charactor in the wrong place, or bad character
     Invalid,
 }
@@ -116,6 +134,8 @@ impl Display for TokenKind {
             TokenKind::Semi => ";",
             TokenKind::Comma => ",",
             TokenKind::Period => ".",
+            TokenKind::Equals => "=",
+            TokenKind::DocComment => "doc comment",
             TokenKind::Invalid => "invalid token",
         };
         write!(f, "{str}")
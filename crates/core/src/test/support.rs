@@ -0,0 +1,21 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::Database;
+use crate::queries::SourceFile;
+
+/// A fresh directory for specs that import each other
+pub fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("haml-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (path, text) in files {
+        fs::write(dir.join(path), text).unwrap();
+    }
+    dir
+}
+
+/// The spec at `path`, as it is on disk
+pub fn read(db: &Database, path: &Path) -> SourceFile {
+    SourceFile::new(db, path.to_path_buf(), fs::read_to_string(path).unwrap())
+}
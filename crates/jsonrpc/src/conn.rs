@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+use tokio::sync::{mpsc, oneshot, Notify};
+
+use crate::method::Method;
+use crate::msg::{self, Message, MessageId, Notification, Packet, Request, Response, WireMessage};
+use crate::record::{Direction, Recorder};
+use crate::router::Router;
+use crate::transport::{read_frame, write_frame};
+
+type PendingRequests = Arc<Mutex<HashMap<MessageId, oneshot::Sender<Response>>>>;
+
+/// A bidirectional JSON-RPC connection. Incoming requests and notifications
+/// are dispatched to a `Router`, while a `Client` handle can be used to send
+/// requests to the other side and wait for the replies.
+pub struct Connection<R, W> {
+    reader: R,
+    writer: W,
+    client: Client,
+    outgoing: mpsc::UnboundedReceiver<WireMessage>,
+    recorder: Option<Recorder>,
+}
+
+impl<R, W> Connection<R, W>
+where
+    R: AsyncRead + Send + Unpin + 'static,
+    W: AsyncWrite + Unpin,
+{
+    pub fn new(reader: R, writer: W) -> Connection<R, W> {
+        let (sender, outgoing) = mpsc::unbounded_channel();
+        let client = Client {
+            sender,
+            pending: Arc::default(),
+            next_id: Arc::new(AtomicI64::new(0)),
+            closing: Arc::default(),
+        };
+        Connection {
+            reader,
+            writer,
+            client,
+            outgoing,
+            recorder: None,
+        }
+    }
+
+    /// Record every message sent or received over this connection
+    pub fn record(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Handle for sending messages to the other side of the connection. This
+    /// can be cloned and moved into request handlers.
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    /// Serve the connection until the other side closes it. Any requests that
+    /// are still waiting on a response fail with `ClientError::Disconnected`.
+    pub async fn run(self, router: &Router) -> io::Result<()> {
+        self.run_until(router, std::future::pending()).await
+    }
+
+    /// Serve the connection until the other side closes it, until a handler
+    /// calls `Client::close`, or until `shutdown` resolves. A message that is
+    /// being handled when `shutdown` resolves is allowed to finish and have its
+    /// reply written.
+    pub async fn run_until<S>(self, router: &Router, shutdown: S) -> io::Result<()>
+    where
+        S: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        let Connection {
+            reader,
+            mut writer,
+            client,
+            mut outgoing,
+            mut recorder,
+        } = self;
+
+        // Reading a frame is not cancel-safe, so it happens on its own task
+        // rather than inside the select below.
+        let (frames_tx, mut frames) = mpsc::channel(16);
+        let reader_task = tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            loop {
+                let frame = read_frame(&mut reader).await.transpose();
+                let done = !matches!(frame, Some(Ok(_)));
+                if frames_tx.send(frame).await.is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        let result = loop {
+            tokio::select! {
+                frame = frames.recv() => {
+                    let frame = match frame.flatten() {
+                        Some(Ok(frame)) => frame,
+                        Some(Err(err)) => break Err(err),
+                        None => break Ok(()),
+                    };
+                    record(&mut recorder, Direction::Incoming, &frame);
+                    if let Some(reply) = handle_frame(router, &client, &frame) {
+                        if let Err(err) = send(&mut writer, &mut recorder, &reply).await {
+                            break Err(err);
+                        }
+                    }
+                }
+                Some(message) = outgoing.recv() => {
+                    if let Err(err) = send(&mut writer, &mut recorder, &message).await {
+                        break Err(err);
+                    }
+                }
+                _ = client.closing.notified() => break Ok(()),
+                _ = &mut shutdown => break Ok(()),
+            }
+        };
+
+        // Handlers may have queued messages while handling the last frame, so
+        // send those before closing if the other side is still listening.
+        if result.is_ok() {
+            while let Ok(message) = outgoing.try_recv() {
+                if send(&mut writer, &mut recorder, &message).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        reader_task.abort();
+        client.pending.lock().unwrap().clear();
+        result
+    }
+}
+
+async fn send<W, M>(writer: &mut W, recorder: &mut Option<Recorder>, message: &M) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    M: Serialize,
+{
+    let body = serde_json::to_string(message)?;
+    record(recorder, Direction::Outgoing, &body);
+    write_frame(writer, &body).await
+}
+
+// A failure to record shouldn't take down the connection being recorded.
+fn record(recorder: &mut Option<Recorder>, direction: Direction, body: &str) {
+    if let Some(active) = recorder {
+        if let Err(err) = active.record(direction, body) {
+            eprintln!("Failed to record message, recording stopped: {err}");
+            *recorder = None;
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum Reply {
+    Single(WireMessage),
+    Batch(Vec<WireMessage>),
+}
+
+pub(crate) fn handle_frame(router: &Router, client: &Client, frame: &str) -> Option<Reply> {
+    match Packet::parse(frame) {
+        Err(response) => Some(Reply::Single(WireMessage::response(response))),
+        Ok(Packet::Single(message)) => dispatch(router, client, message).map(Reply::Single),
+        Ok(Packet::Batch(messages)) => {
+            let replies: Vec<_> = messages
+                .into_iter()
+                .filter_map(|message| match message {
+                    Ok(message) => dispatch(router, client, message),
+                    Err(response) => Some(WireMessage::response(response)),
+                })
+                .collect();
+            // A batch of only notifications doesn't get a reply at all
+            (!replies.is_empty()).then_some(Reply::Batch(replies))
+        }
+    }
+}
+
+fn dispatch(router: &Router, client: &Client, message: WireMessage) -> Option<WireMessage> {
+    match message.into_message() {
+        Message::Request(request) => {
+            let method = request.method.clone();
+            let response = router.run(&method, request);
+            Some(WireMessage::response(response))
+        }
+        Message::Notification(notification) => {
+            router.notify(notification);
+            None
+        }
+        Message::Response(response) => {
+            client.resolve(response);
+            None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    sender: mpsc::UnboundedSender<WireMessage>,
+    pending: PendingRequests,
+    next_id: Arc<AtomicI64>,
+    closing: Arc<Notify>,
+}
+
+impl Client {
+    /// Send a request to the other side of the connection. The request is
+    /// queued immediately, and the returned future resolves once the matching
+    /// response arrives.
+    pub fn request<P>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> impl Future<Output = Result<Value, ClientError>>
+    where
+        P: Into<Option<Value>>,
+    {
+        let id = MessageId::from(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), tx);
+
+        let request = Request::new(id.clone(), method, params);
+        if self.sender.send(WireMessage::request(request)).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+        }
+
+        async move {
+            let response = rx.await.map_err(|_| ClientError::Disconnected)?;
+            match response.error {
+                Some(error) => Err(ClientError::Rpc(error)),
+                None => Ok(response.result.unwrap_or(Value::Null)),
+            }
+        }
+    }
+
+    /// Send a request for a method declared with the `Method` trait
+    pub fn call<M>(&self, params: M::Params) -> impl Future<Output = Result<M::Result, ClientError>>
+    where
+        M: Method,
+    {
+        let params = serde_json::to_value(params);
+        let request = params.map(|params| self.request(M::METHOD, params));
+        async move {
+            let result = request.map_err(ClientError::Serde)?.await?;
+            serde_json::from_value(result).map_err(ClientError::Serde)
+        }
+    }
+
+    pub fn notify<P>(&self, method: &str, params: P) -> Result<(), ClientError>
+    where
+        P: Into<Option<Value>>,
+    {
+        let notification = Notification::new(method, params);
+        self.sender
+            .send(WireMessage::notification(notification))
+            .map_err(|_| ClientError::Disconnected)
+    }
+
+    /// Close the connection once the message being handled has been replied
+    /// to. Messages that are already queued are still sent.
+    pub fn close(&self) {
+        self.closing.notify_one();
+    }
+
+    fn resolve(&self, response: Response) {
+        let sender = self.pending.lock().unwrap().remove(&response.id);
+        match sender {
+            Some(sender) => {
+                let _ = sender.send(response);
+            }
+            None => eprintln!("Received response for unknown request {:?}", response.id),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The connection closed before a response was received.
+    Disconnected,
+    /// The other side replied with an error.
+    Rpc(msg::Error),
+    /// The params or the result didn't match the declared `Method` types.
+    Serde(serde_json::Error),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Disconnected => write!(f, "connection closed"),
+            ClientError::Rpc(err) => write!(f, "error {}: {}", err.code, err.message),
+            ClientError::Serde(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::io::{duplex, split, DuplexStream, ReadHalf, WriteHalf};
+    use tokio::sync::mpsc;
+    use tokio::task::{spawn_local, LocalSet};
+    use tokio::time::timeout;
+
+    type TestConnection = Connection<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>>;
+
+    fn connected_pair() -> (TestConnection, TestConnection) {
+        let (left, right) = duplex(1024);
+        let (left_read, left_write) = split(left);
+        let (right_read, right_write) = split(right);
+        (
+            Connection::new(left_read, left_write),
+            Connection::new(right_read, right_write),
+        )
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct GetFooRequest {
+        pub foo_id: String,
+    }
+
+    pub enum GetFoo {}
+
+    impl Method for GetFoo {
+        const METHOD: &'static str = "getFoo";
+        type Params = GetFooRequest;
+        type Result = String;
+    }
+
+    // Routers aren't `Send`, so connections are served on a local task set.
+    fn serve(conn: TestConnection, router: Router) {
+        spawn_local(async move { conn.run(&router).await });
+    }
+
+    #[tokio::test]
+    async fn test_request_resolves_with_matching_response() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let (local, remote) = connected_pair();
+                let client = local.client();
+
+                let remote_router =
+                    Router::new().register("getFoo", |req: GetFooRequest| req.foo_id);
+                serve(local, Router::new());
+                serve(remote, remote_router);
+
+                let first = client.request("getFoo", json!({ "foo_id": "first" }));
+                let second = client.request("getFoo", json!({ "foo_id": "second" }));
+
+                assert_eq!(second.await.unwrap(), json!("second"));
+                assert_eq!(first.await.unwrap(), json!("first"));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_typed_call_resolves_with_typed_result() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let (local, remote) = connected_pair();
+                let client = local.client();
+
+                serve(local, Router::new());
+                serve(remote, Router::new().method::<GetFoo>(|req| req.foo_id));
+
+                let params = GetFooRequest {
+                    foo_id: "my-foo".to_string(),
+                };
+                let result: String = client.call::<GetFoo>(params).await.unwrap();
+                assert_eq!(result, "my-foo");
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_request_resolves_with_error_response() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let (local, remote) = connected_pair();
+                let client = local.client();
+
+                serve(local, Router::new());
+                serve(remote, Router::new());
+
+                let result = client.request("getFoo", None).await;
+                assert!(
+                    matches!(result, Err(ClientError::Rpc(err)) if err.code == msg::METHOD_NOT_FOUND)
+                );
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_incoming_requests_are_routed_while_waiting_on_a_response() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let (local, remote) = connected_pair();
+                let client = local.client();
+                let remote_client = remote.client();
+
+                // The remote side asks for configuration before it answers.
+                let (configs, mut received) = mpsc::unbounded_channel();
+                let local_router = Router::new().register("getConfig", |_: Value| "config");
+                let remote_router = Router::new().register("getFoo", move |_: Value| {
+                    let config = remote_client.request("getConfig", None);
+                    let configs = configs.clone();
+                    spawn_local(async move { configs.send(config.await.unwrap()).unwrap() });
+                    "foo"
+                });
+                serve(local, local_router);
+                serve(remote, remote_router);
+
+                assert_eq!(client.request("getFoo", None).await.unwrap(), json!("foo"));
+                let config = timeout(Duration::from_secs(5), received.recv())
+                    .await
+                    .expect("the nested request was never answered");
+                assert_eq!(config, Some(json!("config")));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_handler_can_close_the_connection() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let (local, remote) = connected_pair();
+                let client = local.client();
+                let remote_client = remote.client();
+
+                serve(local, Router::new());
+                let router = Router::new().register("exit", move |_: Value| remote_client.close());
+                let remote = spawn_local(async move { remote.run(&router).await });
+
+                client.notify("exit", None).unwrap();
+                let closed = timeout(Duration::from_secs(5), remote).await;
+                assert!(matches!(closed, Ok(Ok(Ok(())))));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_pending_request_fails_when_connection_closes() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let (local, remote) = connected_pair();
+                let client = local.client();
+                serve(local, Router::new());
+
+                let result = client.request("getFoo", None);
+                drop(remote);
+
+                assert!(matches!(result.await, Err(ClientError::Disconnected)));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_recorder_sees_both_directions() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let path = std::env::temp_dir()
+                    .join(format!("jsonrpc-record-{}.jsonl", std::process::id()));
+                let (local, remote) = connected_pair();
+                let client = local.client();
+                let remote = remote.record(Recorder::create(&path).unwrap());
+
+                serve(local, Router::new());
+                serve(remote, Router::new().register("getFoo", |_: Value| "foo"));
+                client.request("getFoo", None).await.unwrap();
+
+                let entries = crate::record::read_session(&path).unwrap();
+                std::fs::remove_file(&path).unwrap();
+                let directions: Vec<_> = entries.iter().map(|entry| entry.direction).collect();
+                assert_eq!(directions, vec![Direction::Incoming, Direction::Outgoing]);
+                assert_eq!(entries[0].message["method"], json!("getFoo"));
+                assert_eq!(entries[1].message["result"], json!("foo"));
+            })
+            .await;
+    }
+}
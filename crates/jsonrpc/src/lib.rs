@@ -1,2 +1,11 @@
+pub mod conn;
+pub mod method;
+pub mod middleware;
 pub mod msg;
-pub mod router;
\ No newline at end of file
+pub mod record;
+pub mod router;
+pub mod server;
+pub mod transport;
+
+#[cfg(test)]
+mod test;
@@ -1,11 +1,8 @@
 use serde::Deserialize;
 use serde_json::json;
 
-pub mod msg;
-pub mod router;
-
-use msg::Request;
-use router::Router;
+use jsonrpc::msg::Request;
+use jsonrpc::router::Router;
 
 fn main() {
     let router = Router::new()
@@ -0,0 +1,29 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A JSON-RPC method declared as a type. Both `Router::method` and
+/// `Client::call` are driven by the same declaration, so the method name and
+/// the shapes of its params and result can't drift apart.
+///
+/// ```
+/// use jsonrpc::method::Method;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct GetFooParams {
+///     pub foo_id: String,
+/// }
+///
+/// pub enum GetFoo {}
+///
+/// impl Method for GetFoo {
+///     const METHOD: &'static str = "getFoo";
+///     type Params = GetFooParams;
+///     type Result = String;
+/// }
+/// ```
+pub trait Method {
+    const METHOD: &'static str;
+    type Params: Serialize + DeserializeOwned + 'static;
+    type Result: Serialize + DeserializeOwned + 'static;
+}
@@ -0,0 +1,112 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::msg::{Error, MessageId};
+use crate::router::Router;
+
+/// A request or notification on its way to a handler. Notifications have no
+/// `id`, and whatever they return is dropped.
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub id: Option<MessageId>,
+    pub method: String,
+    pub params: Option<Value>,
+}
+
+/// Wraps the dispatch of every call made through a `Router`. Each middleware
+/// decides whether, and how, to pass the call on to the rest of the chain.
+pub trait Middleware {
+    fn call(&self, call: Call, next: Next<'_>) -> Result<Value, Error>;
+}
+
+/// The rest of the middleware chain, ending with the method handler
+pub struct Next<'a> {
+    router: &'a Router,
+    middleware: &'a [Box<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(router: &'a Router, middleware: &'a [Box<dyn Middleware>]) -> Next<'a> {
+        Next { router, middleware }
+    }
+
+    pub fn run(self, call: Call) -> Result<Value, Error> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.call(call, Next::new(self.router, rest)),
+            None => self.router.call_handler(&call.method, call.params),
+        }
+    }
+}
+
+/// Logs every call to stderr along with how long it took
+pub struct Logger;
+
+impl Middleware for Logger {
+    fn call(&self, call: Call, next: Next<'_>) -> Result<Value, Error> {
+        let method = call.method.clone();
+        let id = call.id.clone();
+        let start = Instant::now();
+        let result = next.run(call);
+        let elapsed = start.elapsed();
+
+        let id = id.map(|id| format!(" ({id:?})")).unwrap_or_default();
+        match &result {
+            Ok(_) => eprintln!("{method}{id} ok in {elapsed:?}"),
+            Err(err) => eprintln!(
+                "{method}{id} failed in {elapsed:?}: {} {}",
+                err.code, err.message
+            ),
+        }
+        result
+    }
+}
+
+/// Reports how long each call took, e.g. to record metrics
+pub struct Timing<F: Fn(&str, Duration)> {
+    report: F,
+}
+
+impl<F: Fn(&str, Duration)> Timing<F> {
+    pub fn new(report: F) -> Timing<F> {
+        Timing { report }
+    }
+}
+
+impl<F: Fn(&str, Duration)> Middleware for Timing<F> {
+    fn call(&self, call: Call, next: Next<'_>) -> Result<Value, Error> {
+        let method = call.method.clone();
+        let start = Instant::now();
+        let result = next.run(call);
+        (self.report)(&method, start.elapsed());
+        result
+    }
+}
+
+/// Turns a panicking handler into an internal error (-32603) rather than
+/// bringing down the whole connection.
+pub struct CatchPanic;
+
+impl Middleware for CatchPanic {
+    fn call(&self, call: Call, next: Next<'_>) -> Result<Value, Error> {
+        let method = call.method.clone();
+        catch_unwind(AssertUnwindSafe(|| next.run(call))).unwrap_or_else(|panic| {
+            let message = panic_message(&panic);
+            Err(Error::internal_error(&format!(
+                "handler for {method} panicked: {message}"
+            )))
+        })
+    }
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
@@ -1,7 +1,10 @@
+use serde::de::{self, Deserializer};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Number, Value};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+const VERSION: &str = "2.0";
+
+#[derive(Serialize, Debug, Clone)]
 pub struct WireMessage {
     jsonrpc: String,
     #[serde(flatten)]
@@ -9,26 +12,140 @@ pub struct WireMessage {
 }
 
 impl WireMessage {
+    pub fn message(&self) -> &Message {
+        &self.body
+    }
+
+    pub fn into_message(self) -> Message {
+        self.body
+    }
+
     pub fn request(request: Request) -> WireMessage {
-        let jsonrpc = "2.0".to_string();
+        let jsonrpc = VERSION.to_string();
         let body = Message::Request(request);
         WireMessage { jsonrpc, body }
     }
 
     pub fn response(response: Response) -> WireMessage {
-        let jsonrpc = "2.0".to_string();
+        let jsonrpc = VERSION.to_string();
         let body = Message::Response(response);
         WireMessage { jsonrpc, body }
     }
 
     pub fn notification(notification: Notification) -> WireMessage {
-        let jsonrpc = "2.0".to_string();
+        let jsonrpc = VERSION.to_string();
         let body = Message::Notification(notification);
         WireMessage { jsonrpc, body }
     }
+
+    // The kind of message is decided by which members are present, rather
+    // than by trying each variant in turn. A request has a `method` and an
+    // `id`, a notification has a `method` but no `id`, and a response has an
+    // `id` and exactly one of `result` or `error`.
+    fn from_object(mut object: Map<String, Value>) -> Result<WireMessage, String> {
+        match object.remove("jsonrpc") {
+            Some(Value::String(version)) if version == VERSION => {}
+            Some(version) => return Err(format!("unsupported jsonrpc version {version}")),
+            None => return Err("missing jsonrpc version".to_string()),
+        }
+
+        let id = object.remove("id").map(MessageId::from_value).transpose()?;
+        let body = if let Some(method) = object.remove("method") {
+            let method = match method {
+                Value::String(method) => method,
+                _ => return Err("method must be a string".to_string()),
+            };
+            let params = object.remove("params");
+            if let Some(params) = &params {
+                if !params.is_array() && !params.is_object() {
+                    return Err("params must be an array or object".to_string());
+                }
+            }
+            match id {
+                Some(id) => Message::Request(Request { id, method, params }),
+                None => Message::Notification(Notification { method, params }),
+            }
+        } else {
+            let id = id.ok_or("message must have a method or an id")?;
+            let result = object.remove("result");
+            let error = object
+                .remove("error")
+                .map(serde_json::from_value::<Error>)
+                .transpose()
+                .map_err(|err| err.to_string())?;
+            match (&result, &error) {
+                (Some(_), None) | (None, Some(_)) => {}
+                _ => return Err("response must have exactly one of result or error".to_string()),
+            }
+            Message::Response(Response { id, result, error })
+        };
+
+        if let Some(field) = object.keys().next() {
+            return Err(format!("unexpected field `{field}`"));
+        }
+
+        let jsonrpc = VERSION.to_string();
+        Ok(WireMessage { jsonrpc, body })
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl<'de> Deserialize<'de> for WireMessage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let object = Map::deserialize(deserializer)?;
+        WireMessage::from_object(object).map_err(de::Error::custom)
+    }
+}
+
+/// Everything that can be read from one frame. A batch is a JSON array of
+/// messages, and each element may have failed to parse on its own. A batch is
+/// answered with an array of the responses to its requests.
+#[derive(Debug, Clone)]
+pub enum Packet {
+    Single(WireMessage),
+    Batch(Vec<Result<WireMessage, Response>>),
+}
+
+impl Packet {
+    /// Parse the body of a frame. Batches are parsed one element at a time so
+    /// that each invalid element can be answered individually, as required by
+    /// the specification. If the whole frame is unusable, the error response
+    /// that should be sent back is returned instead.
+    pub fn parse(text: &str) -> Result<Packet, Response> {
+        let value: Value = serde_json::from_str(text).map_err(|err| {
+            Response::error(MessageId::Null, Error::parse_error(&err.to_string()))
+        })?;
+        match value {
+            Value::Array(values) if values.is_empty() => Err(Response::error(
+                MessageId::Null,
+                Error::invalid_request("batch must not be empty"),
+            )),
+            Value::Array(values) => {
+                let messages = values.into_iter().map(parse_message).collect();
+                Ok(Packet::Batch(messages))
+            }
+            value => parse_message(value).map(Packet::Single),
+        }
+    }
+}
+
+fn parse_message(value: Value) -> Result<WireMessage, Response> {
+    let invalid = |message: &str| Response::error(MessageId::Null, Error::invalid_request(message));
+    let object = match value {
+        Value::Object(object) => object,
+        _ => return Err(invalid("message must be an object")),
+    };
+    // Echo the id back if it is usable, even if the rest of the message isn't
+    let id = object.get("id").cloned().map(MessageId::from_value);
+    WireMessage::from_object(object).map_err(|message| match id {
+        Some(Ok(id)) => Response::error(id, Error::invalid_request(&message)),
+        _ => invalid(&message),
+    })
+}
+
+#[derive(Serialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Message {
     Request(Request),
@@ -36,25 +153,56 @@ pub enum Message {
     Notification(Notification),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(untagged)]
 pub enum MessageId {
-    I32(i32),
+    /// Any integer that fits in an `i64` or a `u64`
+    Number(#[serde(deserialize_with = "integer")] Number),
     String(String),
+    Null,
 }
 
-impl From<i32> for MessageId {
-    fn from(value: i32) -> Self {
-        MessageId::I32(value)
+fn integer<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Number, D::Error> {
+    let number = Number::deserialize(deserializer)?;
+    match number.is_i64() || number.is_u64() {
+        true => Ok(number),
+        false => Err(de::Error::custom("id must be an integer")),
     }
 }
 
+impl MessageId {
+    fn from_value(value: Value) -> Result<MessageId, String> {
+        serde_json::from_value(value)
+            .map_err(|_| "id must be an integer, string or null".to_string())
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for MessageId {
+                fn from(value: $ty) -> Self {
+                    MessageId::Number(Number::from(value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
 impl From<&str> for MessageId {
     fn from(value: &str) -> Self {
         MessageId::String(value.to_owned())
     }
 }
 
+impl From<String> for MessageId {
+    fn from(value: String) -> Self {
+        MessageId::String(value)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Request {
     pub id: MessageId,
@@ -77,10 +225,12 @@ impl Request {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// Successful responses must have a `result` member even if it is null, which
+// serde would otherwise read back as `None`, so responses are only read through
+// `WireMessage`.
+#[derive(Serialize, Debug, Clone)]
 pub struct Response {
     pub id: MessageId,
-    pub method: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -88,41 +238,46 @@ pub struct Response {
 }
 
 impl Response {
-    pub fn success<I>(id: I, method: &str, result: Value) -> Response
+    pub fn success<I>(id: I, result: Value) -> Response
     where
         I: Into<MessageId>,
     {
         Response {
             id: id.into(),
-            method: method.to_owned(),
             result: Some(result),
             error: None,
         }
     }
 
-    pub fn error<I>(id: I, method: &str, error: Error) -> Response
+    pub fn error<I>(id: I, error: Error) -> Response
     where
         I: Into<MessageId>,
     {
         Response {
             id: id.into(),
-            method: method.to_owned(),
             result: None,
             error: Some(error),
         }
     }
 }
 
+// Error codes reserved by the JSON-RPC 2.0 specification
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Error {
-    pub code: u32,
+    pub code: i32,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<Value>,
 }
 
 impl Error {
-    pub fn new<V>(code: u32, message: &str, data: V) -> Error
+    pub fn new<V>(code: i32, message: &str, data: V) -> Error
     where
         V: Into<Option<Value>>,
     {
@@ -132,6 +287,29 @@ impl Error {
             data: data.into(),
         }
     }
+
+    // The reserved errors use the messages from the specification, with any
+    // details about what went wrong in `data`.
+
+    pub fn parse_error(details: &str) -> Error {
+        Error::new(PARSE_ERROR, "Parse error", Value::from(details))
+    }
+
+    pub fn invalid_request(details: &str) -> Error {
+        Error::new(INVALID_REQUEST, "Invalid Request", Value::from(details))
+    }
+
+    pub fn method_not_found(method: &str) -> Error {
+        Error::new(METHOD_NOT_FOUND, "Method not found", Value::from(method))
+    }
+
+    pub fn invalid_params(details: &str) -> Error {
+        Error::new(INVALID_PARAMS, "Invalid params", Value::from(details))
+    }
+
+    pub fn internal_error(details: &str) -> Error {
+        Error::new(INTERNAL_ERROR, "Internal error", Value::from(details))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -184,10 +362,10 @@ mod tests {
 
     #[test]
     fn test_success_response_gives_expected_json() -> TestResult {
-        let res = Response::success(1, "getFoo", json!({ "foo": 1 }));
+        let res = Response::success(1, json!({ "foo": 1 }));
         let msg = WireMessage::response(res);
 
-        let expected = r#"{"jsonrpc":"2.0","id":1,"method":"getFoo","result":{"foo":1}}"#;
+        let expected = r#"{"jsonrpc":"2.0","id":1,"result":{"foo":1}}"#;
         let actual = serde_json::to_string(&msg)?;
         assert_eq!(actual, expected);
         Ok(())
@@ -196,11 +374,10 @@ mod tests {
     #[test]
     fn test_error_response_gives_expected_json() -> TestResult {
         let error = Error::new(100, "failed", None);
-        let res = Response::error(1, "getFoo", error);
+        let res = Response::error(1, error);
         let msg = WireMessage::response(res);
 
-        let expected =
-            r#"{"jsonrpc":"2.0","id":1,"method":"getFoo","error":{"code":100,"message":"failed"}}"#;
+        let expected = r#"{"jsonrpc":"2.0","id":1,"error":{"code":100,"message":"failed"}}"#;
         let actual = serde_json::to_string(&msg)?;
         assert_eq!(actual, expected);
         Ok(())
@@ -209,10 +386,10 @@ mod tests {
     #[test]
     fn test_error_response_with_data_gives_expected_json() -> TestResult {
         let error = Error::new(100, "failed", json!({ "foo": "bar" }));
-        let res = Response::error(1, "getFoo", error);
+        let res = Response::error(1, error);
         let msg = WireMessage::response(res);
 
-        let expected = r#"{"jsonrpc":"2.0","id":1,"method":"getFoo","error":{"code":100,"message":"failed","data":{"foo":"bar"}}}"#;
+        let expected = r#"{"jsonrpc":"2.0","id":1,"error":{"code":100,"message":"failed","data":{"foo":"bar"}}}"#;
         let actual = serde_json::to_string(&msg)?;
         assert_eq!(actual, expected);
         Ok(())
@@ -239,4 +416,85 @@ mod tests {
         assert_eq!(actual, expected);
         Ok(())
     }
+
+    #[test]
+    fn test_response_deserializes_as_response() -> TestResult {
+        let json = r#"{"jsonrpc":"2.0","id":1,"result":{"foo":1}}"#;
+        let msg: WireMessage = serde_json::from_str(json)?;
+        assert!(matches!(msg.message(), Message::Response(res) if res.id == MessageId::from(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_with_method_is_rejected() {
+        let json = r#"{"jsonrpc":"2.0","id":1,"method":"getFoo","result":{"foo":1}}"#;
+        assert!(serde_json::from_str::<WireMessage>(json).is_err());
+    }
+
+    #[test]
+    fn test_response_with_null_result_keeps_result() -> TestResult {
+        let json = r#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+        let msg: WireMessage = serde_json::from_str(json)?;
+        assert_eq!(serde_json::to_string(&msg)?, json);
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_with_result_and_error_is_rejected() {
+        let json = r#"{"jsonrpc":"2.0","id":1,"result":1,"error":{"code":1,"message":"x"}}"#;
+        assert!(serde_json::from_str::<WireMessage>(json).is_err());
+    }
+
+    #[test]
+    fn test_message_without_version_is_rejected() {
+        let json = r#"{"id":1,"method":"getFoo"}"#;
+        assert!(serde_json::from_str::<WireMessage>(json).is_err());
+    }
+
+    #[test]
+    fn test_null_id_gives_expected_json() -> TestResult {
+        let res = Response::error(MessageId::Null, Error::new(100, "failed", None));
+        let msg = WireMessage::response(res);
+
+        let expected = r#"{"jsonrpc":"2.0","id":null,"error":{"code":100,"message":"failed"}}"#;
+        assert_eq!(serde_json::to_string(&msg)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_accepts_ids_larger_than_i32() -> TestResult {
+        let json = r#"{"jsonrpc":"2.0","id":8589934592,"method":"getFoo"}"#;
+        let msg: WireMessage = serde_json::from_str(json)?;
+        assert!(
+            matches!(msg.message(), Message::Request(req) if req.id == MessageId::from(1i64 << 33))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ids_round_trip_the_full_u64_range() -> TestResult {
+        let json = r#"{"jsonrpc":"2.0","id":18446744073709551615,"method":"getFoo"}"#;
+        let msg: WireMessage = serde_json::from_str(json)?;
+        assert!(
+            matches!(msg.message(), Message::Request(req) if req.id == MessageId::from(u64::MAX))
+        );
+        assert_eq!(serde_json::to_string(&msg)?, json);
+
+        assert_eq!(MessageId::from(7usize), MessageId::from(7u64));
+        assert_eq!(
+            serde_json::to_value(MessageId::from(usize::MAX))?,
+            json!(usize::MAX)
+        );
+        assert_eq!(
+            serde_json::to_value(MessageId::from(i64::MIN))?,
+            json!(i64::MIN)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fractional_ids_are_rejected() {
+        let json = r#"{"jsonrpc":"2.0","id":1.5,"method":"getFoo"}"#;
+        assert!(serde_json::from_str::<WireMessage>(json).is_err());
+    }
 }
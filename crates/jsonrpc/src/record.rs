@@ -0,0 +1,127 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Which way a recorded message travelled, from the point of view of the side
+/// doing the recording.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+/// One line of a recorded session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub direction: Direction,
+    pub message: Value,
+}
+
+/// Writes every message sent or received over a `Connection` to a JSONL file,
+/// one `Entry` per line, so the session can be replayed later.
+pub struct Recorder {
+    writer: Box<dyn Write + Send>,
+}
+
+impl Recorder {
+    pub fn new<W>(writer: W) -> Recorder
+    where
+        W: Write + Send + 'static,
+    {
+        Recorder {
+            writer: Box::new(writer),
+        }
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        let file = File::create(path)?;
+        Ok(Recorder::new(BufWriter::new(file)))
+    }
+
+    /// Record the raw body of a frame. Bodies that aren't valid JSON are kept
+    /// as a string so that they can still be replayed byte for byte.
+    pub fn record(&mut self, direction: Direction, body: &str) -> io::Result<()> {
+        let message = serde_json::from_str(body).unwrap_or_else(|_| Value::String(body.into()));
+        let entry = Entry { direction, message };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+impl Entry {
+    /// The frame body to send when replaying this entry
+    pub fn body(&self) -> String {
+        match &self.message {
+            Value::String(raw) => raw.clone(),
+            message => message.to_string(),
+        }
+    }
+}
+
+/// Read a session written by a `Recorder`. Blank lines are skipped.
+pub fn read_session<P: AsRef<Path>>(path: P) -> io::Result<Vec<Entry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_records_one_entry_per_line() -> io::Result<()> {
+        let buf = SharedBuf::default();
+        let mut recorder = Recorder::new(buf.clone());
+        recorder.record(Direction::Incoming, r#"{"jsonrpc":"2.0","method":"a"}"#)?;
+        recorder.record(Direction::Outgoing, "not json")?;
+
+        let written = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let entries: Vec<Entry> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                Entry {
+                    direction: Direction::Incoming,
+                    message: json!({"jsonrpc": "2.0", "method": "a"}),
+                },
+                Entry {
+                    direction: Direction::Outgoing,
+                    message: json!("not json"),
+                },
+            ]
+        );
+        assert_eq!(entries[1].body(), "not json");
+        Ok(())
+    }
+}
@@ -1,30 +1,45 @@
 use std::collections::HashMap;
 
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::Value;
 
-use crate::msg::Request;
-
-#[derive(Debug)]
-pub struct Server {
-    pub addr: String,
-    pub threads: u32,
-}
+use crate::method::Method;
+use crate::middleware::{Call, Middleware, Next};
+use crate::msg::{Error, Notification, Request, Response};
 
 pub struct Router {
     pub methods: HashMap<String, Box<dyn MethodHandler>>,
+    pub middleware: Vec<Box<dyn Middleware>>,
 }
 
 impl Router {
     pub fn new() -> Router {
         Router {
             methods: HashMap::new(),
+            middleware: vec![],
         }
     }
 
-    pub fn register<T>(mut self, name: &str, handler: impl Fn(T) + 'static) -> Self
+    /// Register a handler for a method declared with the `Method` trait
+    pub fn method<M>(self, handler: impl Fn(M::Params) -> M::Result + 'static) -> Self
+    where
+        M: Method,
+    {
+        self.register(M::METHOD, handler)
+    }
+
+    /// Wrap every call in `middleware`. Middleware added first is outermost,
+    /// so it sees each call before, and each result after, the rest.
+    pub fn layer(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    pub fn register<T, R>(mut self, name: &str, handler: impl Fn(T) -> R + 'static) -> Self
     where
         T: DeserializeOwned + 'static,
+        R: Serialize + 'static,
     {
         self.methods.insert(
             name.to_owned(),
@@ -35,42 +50,105 @@ impl Router {
         self
     }
 
-    pub fn run(&self, method: &str, message: Request) {
-        let handler = self.methods.get(method).unwrap();
-        handler.call(message.params.unwrap());
+    pub fn run(&self, method: &str, message: Request) -> Response {
+        let call = Call {
+            id: Some(message.id.clone()),
+            method: method.to_owned(),
+            params: message.params,
+        };
+        match self.call(call) {
+            Ok(result) => Response::success(message.id, result),
+            Err(error) => Response::error(message.id, error),
+        }
+    }
+
+    /// Notifications never get a reply, so any result or error is dropped.
+    pub fn notify(&self, message: Notification) {
+        let call = Call {
+            id: None,
+            method: message.method,
+            params: message.params,
+        };
+        let _ = self.call(call);
+    }
+
+    fn call(&self, call: Call) -> Result<Value, Error> {
+        Next::new(self, &self.middleware).run(call)
+    }
+
+    pub(crate) fn call_handler(&self, method: &str, params: Option<Value>) -> Result<Value, Error> {
+        let handler = self
+            .methods
+            .get(method)
+            .ok_or_else(|| Error::method_not_found(method))?;
+        handler.call(params.unwrap_or(Value::Null))
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router::new()
     }
 }
 
 pub trait MethodHandler {
-    fn call(&self, req: Value);
+    fn call(&self, req: Value) -> Result<Value, Error>;
 }
 
-pub struct CallbackMethodHandler<T: DeserializeOwned> {
-    pub handler: Box<dyn Fn(T)>,
+pub struct CallbackMethodHandler<T: DeserializeOwned, R: Serialize> {
+    pub handler: Box<dyn Fn(T) -> R>,
 }
 
-impl<T> MethodHandler for CallbackMethodHandler<T>
+impl<T, R> MethodHandler for CallbackMethodHandler<T, R>
 where
     T: DeserializeOwned,
+    R: Serialize,
 {
-    fn call(&self, value: Value) {
-        let value = serde_json::from_value(value).unwrap();
-        (self.handler)(value);
+    fn call(&self, value: Value) -> Result<Value, Error> {
+        let value =
+            serde_json::from_value(value).map_err(|err| Error::invalid_params(&err.to_string()))?;
+        let result = (self.handler)(value);
+        serde_json::to_value(result).map_err(|err| Error::internal_error(&err.to_string()))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde::Deserialize;
+    use crate::middleware::CatchPanic;
+    use crate::msg::{INTERNAL_ERROR, INVALID_PARAMS, METHOD_NOT_FOUND};
+    use serde::{Deserialize, Serialize};
     use serde_json::json;
-    use std::{cell::Cell, rc::Rc};
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
 
-    #[derive(Debug, Deserialize)]
+    #[derive(Debug, Serialize, Deserialize)]
     pub struct GetFooRequest {
         pub foo_id: String,
     }
 
+    pub enum GetFoo {}
+
+    impl Method for GetFoo {
+        const METHOD: &'static str = "getFoo";
+        type Params = GetFooRequest;
+        type Result = String;
+    }
+
+    // Records the order it was called in, for checking how layers nest
+    struct Record(&'static str, Rc<RefCell<Vec<String>>>);
+
+    impl Middleware for Record {
+        fn call(&self, call: Call, next: Next<'_>) -> Result<Value, Error> {
+            self.1.borrow_mut().push(format!("before {}", self.0));
+            let result = next.run(call);
+            self.1.borrow_mut().push(format!("after {}", self.0));
+            result
+        }
+    }
+
     #[test]
     fn test_calls_expected_method() {
         // This flag is set by the callback. Since we need to mutate it inside
@@ -91,4 +169,96 @@ mod tests {
 
         assert!(invoked_callback.get());
     }
+
+    #[test]
+    fn test_returns_handler_result_in_response() {
+        let router = Router::new().register("getFoo", |req: GetFooRequest| req.foo_id);
+
+        let params = json!({ "foo_id": "my-foo" });
+        let req = Request::new(1, "getFoo", params);
+        let res = router.run("getFoo", req);
+
+        assert_eq!(res.result, Some(json!("my-foo")));
+        assert!(res.error.is_none());
+    }
+
+    #[test]
+    fn test_unknown_method_gives_method_not_found_error() {
+        let router = Router::new();
+
+        let req = Request::new(1, "getFoo", None);
+        let res = router.run("getFoo", req);
+
+        assert_eq!(res.error.map(|err| err.code), Some(METHOD_NOT_FOUND));
+    }
+
+    #[test]
+    fn test_bad_params_give_invalid_params_error() {
+        let router = Router::new().register("getFoo", |_req: GetFooRequest| {});
+
+        let params = json!({ "bar_id": "my-bar" });
+        let req = Request::new(1, "getFoo", params);
+        let res = router.run("getFoo", req);
+
+        assert_eq!(res.error.map(|err| err.code), Some(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn test_calls_typed_method() {
+        let router = Router::new().method::<GetFoo>(|req| req.foo_id);
+
+        let params = json!({ "foo_id": "my-foo" });
+        let req = Request::new(1, GetFoo::METHOD, params);
+        let res = router.run(GetFoo::METHOD, req);
+
+        assert_eq!(res.result, Some(json!("my-foo")));
+    }
+
+    #[test]
+    fn test_middleware_runs_in_the_order_it_was_added() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let handler_log = log.clone();
+        let router = Router::new()
+            .layer(Record("outer", log.clone()))
+            .layer(Record("inner", log.clone()))
+            .register("getFoo", move |_: Value| {
+                handler_log.borrow_mut().push("handler".to_string());
+            });
+
+        router.run("getFoo", Request::new(1, "getFoo", None));
+
+        let expected = vec![
+            "before outer",
+            "before inner",
+            "handler",
+            "after inner",
+            "after outer",
+        ];
+        assert_eq!(*log.borrow(), expected);
+    }
+
+    #[test]
+    fn test_middleware_sees_notifications() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let router = Router::new()
+            .layer(Record("only", log.clone()))
+            .register("didFoo", |_: Value| {});
+
+        router.notify(Notification::new("didFoo", None));
+
+        assert_eq!(*log.borrow(), vec!["before only", "after only"]);
+    }
+
+    #[test]
+    fn test_catch_panic_turns_panic_into_internal_error() {
+        let router = Router::new()
+            .layer(CatchPanic)
+            .register("getFoo", |_: Value| -> () { panic!("oh no") });
+
+        let res = router.run("getFoo", Request::new(1, "getFoo", None));
+
+        let error = res.error.unwrap();
+        assert_eq!(error.code, INTERNAL_ERROR);
+        assert!(error.data.unwrap().as_str().unwrap().contains("oh no"));
+    }
 }
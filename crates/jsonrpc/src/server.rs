@@ -0,0 +1,470 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::{watch, Semaphore};
+use tokio::task::{JoinSet, LocalSet};
+
+use crate::conn::{Client, Connection};
+use crate::record::Recorder;
+use crate::router::Router;
+
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+// How long to wait after a failed accept, doubling while it keeps failing, so
+// an error that lasts like running out of file descriptors doesn't spin
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Where a `Server` accepts connections from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    /// A single connection over stdin and stdout
+    Stdio,
+    /// A TCP address like `127.0.0.1:9257`
+    Tcp(String),
+    /// The path of a Unix domain socket
+    Unix(PathBuf),
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    /// Parse `stdio`, `tcp://<addr>`, `unix://<path>` or a bare TCP address.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value == "stdio" {
+            return Ok(Listen::Stdio);
+        }
+        if let Some(path) = value.strip_prefix("unix://") {
+            return Ok(Listen::Unix(PathBuf::from(path)));
+        }
+        let addr = value.strip_prefix("tcp://").unwrap_or(value);
+        if addr.is_empty() {
+            return Err(format!("invalid address: {value}"));
+        }
+        Ok(Listen::Tcp(addr.to_owned()))
+    }
+}
+
+impl Display for Listen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Listen::Stdio => write!(f, "stdio"),
+            Listen::Tcp(addr) => write!(f, "tcp://{addr}"),
+            Listen::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// Serves the same set of handlers over stdio, TCP or a Unix domain socket.
+/// Every connection gets its own `Router`, so any state captured by the
+/// handlers is private to that connection.
+#[derive(Debug)]
+pub struct Server {
+    pub addr: Listen,
+    pub max_connections: usize,
+    pub record: Option<PathBuf>,
+}
+
+impl Server {
+    pub fn new(addr: Listen) -> Server {
+        Server {
+            addr,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            record: None,
+        }
+    }
+
+    /// Record each connection to `session-<n>.jsonl` in `dir`, numbered in the
+    /// order they were accepted.
+    pub fn record(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.record = Some(dir.into());
+        self
+    }
+
+    /// Limit the number of connections being served at once. Connections over
+    /// the limit wait to be accepted until another one closes.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+
+    pub async fn serve<F>(&self, make_router: F) -> io::Result<()>
+    where
+        F: Fn(Client) -> Router,
+    {
+        self.serve_with_shutdown(make_router, std::future::pending())
+            .await
+    }
+
+    /// Serve connections until `shutdown` resolves. The server then stops
+    /// accepting connections, asks every open connection to finish the message
+    /// it is handling, and waits for them to close.
+    pub async fn serve_with_shutdown<F, S>(&self, make_router: F, shutdown: S) -> io::Result<()>
+    where
+        F: Fn(Client) -> Router,
+        S: Future<Output = ()>,
+    {
+        // Routers aren't `Send`, so all connections are served on this thread.
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(self.accept_loop(make_router, shutdown))
+            .await
+    }
+
+    async fn accept_loop<F, S>(&self, make_router: F, shutdown: S) -> io::Result<()>
+    where
+        F: Fn(Client) -> Router,
+        S: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        if let Some(dir) = &self.record {
+            std::fs::create_dir_all(dir)?;
+        }
+        let listener = Listener::bind(&self.addr).await?;
+        let limit = Arc::new(Semaphore::new(self.max_connections));
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        let mut accepted_count = 0;
+        let mut backoff = MIN_ACCEPT_BACKOFF;
+
+        loop {
+            let permit = tokio::select! {
+                permit = limit.clone().acquire_owned() => permit.expect("semaphore is never closed"),
+                _ = &mut shutdown => break,
+            };
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                Some(_) = connections.join_next() => continue,
+                _ = &mut shutdown => break,
+            };
+            let (reader, writer) = match accepted {
+                Ok(Some(stream)) => stream,
+                // Stdio only ever has one connection, so serve it until it
+                // closes rather than asking it to stop.
+                Ok(None) => {
+                    let closed = async { while connections.join_next().await.is_some() {} };
+                    tokio::select! {
+                        _ = closed => {},
+                        _ = &mut shutdown => {},
+                    };
+                    break;
+                }
+                Err(err) => {
+                    eprintln!("Failed to accept connection: {err}");
+                    tokio::select! {
+                        _ = tokio::time::sleep(backoff) => {},
+                        _ = &mut shutdown => break,
+                    };
+                    backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                    continue;
+                }
+            };
+            backoff = MIN_ACCEPT_BACKOFF;
+
+            accepted_count += 1;
+            let mut conn = Connection::new(reader, writer);
+            if let Some(dir) = &self.record {
+                let path = dir.join(format!("session-{accepted_count}.jsonl"));
+                match Recorder::create(&path) {
+                    Ok(recorder) => conn = conn.record(recorder),
+                    Err(err) => eprintln!("Failed to record to {}: {err}", path.display()),
+                }
+            }
+            let router = make_router(conn.client());
+            let mut stop = stop_rx.clone();
+            connections.spawn_local(async move {
+                let stopped = async move {
+                    while !*stop.borrow() {
+                        if stop.changed().await.is_err() {
+                            break;
+                        }
+                    }
+                };
+                if let Err(err) = conn.run_until(&router, stopped).await {
+                    eprintln!("Connection failed: {err}");
+                }
+                drop(permit);
+            });
+        }
+
+        let _ = stop_tx.send(true);
+        while connections.join_next().await.is_some() {}
+        listener.close();
+        Ok(())
+    }
+}
+
+enum Listener {
+    Stdio(std::cell::Cell<bool>),
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    async fn bind(addr: &Listen) -> io::Result<Listener> {
+        match addr {
+            Listen::Stdio => Ok(Listener::Stdio(std::cell::Cell::new(false))),
+            Listen::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr).await?)),
+            #[cfg(unix)]
+            Listen::Unix(path) => {
+                remove_stale_socket(path);
+                Ok(Listener::Unix(UnixListener::bind(path)?, path.clone()))
+            }
+            #[cfg(not(unix))]
+            Listen::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix sockets are not supported on this platform",
+            )),
+        }
+    }
+
+    /// Wait for the next connection. Returns `None` when no more connections
+    /// will ever arrive.
+    async fn accept(&self) -> io::Result<Option<(BoxedReader, BoxedWriter)>> {
+        match self {
+            Listener::Stdio(accepted) => {
+                if accepted.replace(true) {
+                    return Ok(None);
+                }
+                Ok(Some((
+                    Box::new(tokio::io::stdin()),
+                    Box::new(tokio::io::stdout()),
+                )))
+            }
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept().await?;
+                let (reader, writer) = stream.into_split();
+                Ok(Some((Box::new(reader), Box::new(writer))))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                let (reader, writer) = stream.into_split();
+                Ok(Some((Box::new(reader), Box::new(writer))))
+            }
+        }
+    }
+
+    fn close(self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// A server that didn't shut down cleanly leaves its socket behind, which makes
+// binding the path fail. The socket is only removed when nothing accepts
+// connections on it, so a server that is still running keeps its socket and
+// binding fails as it should.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) {
+    use std::os::unix::fs::FileTypeExt;
+
+    let is_socket = std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_socket())
+        .unwrap_or(false);
+    if is_socket && std::os::unix::net::UnixStream::connect(path).is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conn::ClientError;
+    use serde_json::{json, Value};
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+    use tokio::task::spawn_local;
+
+    // Each connection counts its own calls, so the count only goes up for
+    // requests made over the same connection.
+    fn counting_router(_client: Client) -> Router {
+        let count = Rc::new(Cell::new(0));
+        Router::new().register("count", move |_: Value| {
+            count.set(count.get() + 1);
+            count.get()
+        })
+    }
+
+    fn free_tcp_addr() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    async fn connect_tcp(addr: &str) -> Client {
+        for _ in 0..50 {
+            if let Ok(stream) = TcpStream::connect(addr).await {
+                let (reader, writer) = stream.into_split();
+                let conn = Connection::new(reader, writer);
+                let client = conn.client();
+                spawn_local(async move { conn.run(&Router::new()).await });
+                return client;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("server never started listening on {addr}");
+    }
+
+    #[test]
+    fn test_parses_listen_addresses() {
+        assert_eq!("stdio".parse(), Ok(Listen::Stdio));
+        assert_eq!(
+            "tcp://127.0.0.1:9257".parse(),
+            Ok(Listen::Tcp("127.0.0.1:9257".to_string()))
+        );
+        assert_eq!(
+            "127.0.0.1:9257".parse(),
+            Ok(Listen::Tcp("127.0.0.1:9257".to_string()))
+        );
+        assert_eq!(
+            "unix:///tmp/haml.sock".parse(),
+            Ok(Listen::Unix(PathBuf::from("/tmp/haml.sock")))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_each_tcp_connection_gets_its_own_router() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let addr = free_tcp_addr();
+                let server = Server::new(Listen::Tcp(addr.clone()));
+                let (stop_tx, stop_rx) = oneshot::channel::<()>();
+                let served = spawn_local(async move {
+                    let shutdown = async move {
+                        let _ = stop_rx.await;
+                    };
+                    server.serve_with_shutdown(counting_router, shutdown).await
+                });
+
+                let first = connect_tcp(&addr).await;
+                let second = connect_tcp(&addr).await;
+                assert_eq!(first.request("count", None).await.unwrap(), json!(1));
+                assert_eq!(first.request("count", None).await.unwrap(), json!(2));
+                assert_eq!(second.request("count", None).await.unwrap(), json!(1));
+
+                stop_tx.send(()).unwrap();
+                served.await.unwrap().unwrap();
+                assert!(matches!(
+                    first.request("count", None).await,
+                    Err(ClientError::Disconnected)
+                ));
+            })
+            .await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serves_unix_socket_and_removes_it_on_shutdown() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let path =
+                    std::env::temp_dir().join(format!("jsonrpc-{}.sock", std::process::id()));
+                let server = Server::new(Listen::Unix(path.clone()));
+                let (stop_tx, stop_rx) = oneshot::channel::<()>();
+                let served = spawn_local(async move {
+                    let shutdown = async move {
+                        let _ = stop_rx.await;
+                    };
+                    server.serve_with_shutdown(counting_router, shutdown).await
+                });
+
+                let stream = loop {
+                    match tokio::net::UnixStream::connect(&path).await {
+                        Ok(stream) => break stream,
+                        Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                    }
+                };
+                let (reader, writer) = stream.into_split();
+                let conn = Connection::new(reader, writer);
+                let client = conn.client();
+                spawn_local(async move { conn.run(&Router::new()).await });
+                assert_eq!(client.request("count", None).await.unwrap(), json!(1));
+
+                stop_tx.send(()).unwrap();
+                served.await.unwrap().unwrap();
+                assert!(!path.exists());
+            })
+            .await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_replaces_a_stale_unix_socket() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let path =
+                    std::env::temp_dir().join(format!("jsonrpc-stale-{}.sock", std::process::id()));
+                let _ = std::fs::remove_file(&path);
+                // Dropping a listener leaves its socket file behind, like a
+                // server that was killed
+                drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+                assert!(path.exists());
+
+                let server = Server::new(Listen::Unix(path.clone()));
+                let (stop_tx, stop_rx) = oneshot::channel::<()>();
+                let served = spawn_local(async move {
+                    let shutdown = async move {
+                        let _ = stop_rx.await;
+                    };
+                    server.serve_with_shutdown(counting_router, shutdown).await
+                });
+                let stream = loop {
+                    match tokio::net::UnixStream::connect(&path).await {
+                        Ok(stream) => break stream,
+                        Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                    }
+                };
+
+                // A second server can't take the socket of one that's running
+                let second = Server::new(Listen::Unix(path.clone()));
+                assert!(second.serve(counting_router).await.is_err());
+
+                drop(stream);
+                stop_tx.send(()).unwrap();
+                served.await.unwrap().unwrap();
+                assert!(!path.exists());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_limits_concurrent_connections() {
+        let tasks = LocalSet::new();
+        tasks
+            .run_until(async {
+                let addr = free_tcp_addr();
+                let server = Server::new(Listen::Tcp(addr.clone())).max_connections(1);
+                spawn_local(async move { server.serve(counting_router).await });
+
+                let first = connect_tcp(&addr).await;
+                assert_eq!(first.request("count", None).await.unwrap(), json!(1));
+
+                // The second connection is only accepted once the first closes
+                let second = connect_tcp(&addr).await;
+                let waiting = second.request("count", None);
+                let result = tokio::time::timeout(Duration::from_millis(100), waiting).await;
+                assert!(result.is_err());
+            })
+            .await;
+    }
+}
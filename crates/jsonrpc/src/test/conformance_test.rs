@@ -0,0 +1,217 @@
+// These are the examples from section 7 of the JSON-RPC 2.0 specification.
+// The error `data` member is ours, so it is stripped before comparing.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{empty, sink};
+
+use crate::conn::{handle_frame, Connection};
+use crate::router::Router;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubtractParams {
+    Positional(i64, i64),
+    Named { minuend: i64, subtrahend: i64 },
+}
+
+fn router() -> Router {
+    Router::new()
+        .register("subtract", |params: SubtractParams| match params {
+            SubtractParams::Positional(minuend, subtrahend) => minuend - subtrahend,
+            SubtractParams::Named {
+                minuend,
+                subtrahend,
+            } => minuend - subtrahend,
+        })
+        .register("sum", |params: Vec<i64>| params.iter().sum::<i64>())
+        .register("update", |_: Vec<i64>| {})
+        .register("notify_hello", |_: Vec<i64>| {})
+        .register("get_data", |_: ()| json!(["hello", 5]))
+}
+
+fn call(request: &str) -> Option<Value> {
+    let client = Connection::new(empty(), sink()).client();
+    let reply = handle_frame(&router(), &client, request)?;
+    let mut reply = serde_json::to_value(reply).unwrap();
+    match &mut reply {
+        Value::Array(replies) => replies.iter_mut().for_each(strip_error_data),
+        reply => strip_error_data(reply),
+    }
+    Some(reply)
+}
+
+fn strip_error_data(reply: &mut Value) {
+    if let Some(error) = reply.get_mut("error").and_then(Value::as_object_mut) {
+        error.remove("data");
+    }
+}
+
+#[test]
+fn test_call_with_positional_parameters() {
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": 1}"#);
+    assert_eq!(
+        reply,
+        Some(json!({"jsonrpc": "2.0", "result": 19, "id": 1}))
+    );
+
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [23, 42], "id": 2}"#);
+    assert_eq!(
+        reply,
+        Some(json!({"jsonrpc": "2.0", "result": -19, "id": 2}))
+    );
+}
+
+#[test]
+fn test_call_with_named_parameters() {
+    let reply = call(
+        r#"{"jsonrpc": "2.0", "method": "subtract", "params": {"subtrahend": 23, "minuend": 42}, "id": 3}"#,
+    );
+    assert_eq!(
+        reply,
+        Some(json!({"jsonrpc": "2.0", "result": 19, "id": 3}))
+    );
+
+    let reply = call(
+        r#"{"jsonrpc": "2.0", "method": "subtract", "params": {"minuend": 42, "subtrahend": 23}, "id": 4}"#,
+    );
+    assert_eq!(
+        reply,
+        Some(json!({"jsonrpc": "2.0", "result": 19, "id": 4}))
+    );
+}
+
+#[test]
+fn test_notification() {
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "update", "params": [1,2,3,4,5]}"#);
+    assert_eq!(reply, None);
+
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "foobar"}"#);
+    assert_eq!(reply, None);
+}
+
+#[test]
+fn test_call_of_non_existent_method() {
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "foobar", "id": "1"}"#);
+    let expected = json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32601, "message": "Method not found"},
+        "id": "1"
+    });
+    assert_eq!(reply, Some(expected));
+}
+
+#[test]
+fn test_call_with_invalid_json() {
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "foobar, "params": "bar", "baz]"#);
+    let expected = json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32700, "message": "Parse error"},
+        "id": null
+    });
+    assert_eq!(reply, Some(expected));
+}
+
+#[test]
+fn test_call_with_invalid_request_object() {
+    let reply = call(r#"{"jsonrpc": "2.0", "method": 1, "params": "bar"}"#);
+    let expected = json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32600, "message": "Invalid Request"},
+        "id": null
+    });
+    assert_eq!(reply, Some(expected));
+}
+
+#[test]
+fn test_batch_with_invalid_json() {
+    let reply = call(
+        r#"[
+            {"jsonrpc": "2.0", "method": "sum", "params": [1,2,4], "id": "1"},
+            {"jsonrpc": "2.0", "method"
+        ]"#,
+    );
+    let expected = json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32700, "message": "Parse error"},
+        "id": null
+    });
+    assert_eq!(reply, Some(expected));
+}
+
+#[test]
+fn test_call_with_empty_array() {
+    let reply = call("[]");
+    let expected = json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32600, "message": "Invalid Request"},
+        "id": null
+    });
+    assert_eq!(reply, Some(expected));
+}
+
+#[test]
+fn test_call_with_invalid_batch() {
+    let invalid = json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32600, "message": "Invalid Request"},
+        "id": null
+    });
+
+    let reply = call("[1]");
+    assert_eq!(reply, Some(json!([invalid])));
+
+    let reply = call("[1,2,3]");
+    assert_eq!(reply, Some(json!([invalid, invalid, invalid])));
+}
+
+#[test]
+fn test_call_batch() {
+    let reply = call(
+        r#"[
+            {"jsonrpc": "2.0", "method": "sum", "params": [1,2,4], "id": "1"},
+            {"jsonrpc": "2.0", "method": "notify_hello", "params": [7]},
+            {"jsonrpc": "2.0", "method": "subtract", "params": [42,23], "id": "2"},
+            {"foo": "boo"},
+            {"jsonrpc": "2.0", "method": "foo.get", "params": {"name": "myself"}, "id": "5"},
+            {"jsonrpc": "2.0", "method": "get_data", "id": "9"}
+        ]"#,
+    );
+    let expected = json!([
+        {"jsonrpc": "2.0", "result": 7, "id": "1"},
+        {"jsonrpc": "2.0", "result": 19, "id": "2"},
+        {"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": null},
+        {"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": "5"},
+        {"jsonrpc": "2.0", "result": ["hello", 5], "id": "9"}
+    ]);
+    assert_eq!(reply, Some(expected));
+}
+
+#[test]
+fn test_call_batch_of_all_notifications() {
+    let reply = call(
+        r#"[
+            {"jsonrpc": "2.0", "method": "notify_sum", "params": [1,2,4]},
+            {"jsonrpc": "2.0", "method": "notify_hello", "params": [7]}
+        ]"#,
+    );
+    assert_eq!(reply, None);
+}
+
+#[test]
+fn test_call_with_null_id() {
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": null}"#);
+    assert_eq!(
+        reply,
+        Some(json!({"jsonrpc": "2.0", "result": 19, "id": null}))
+    );
+}
+
+#[test]
+fn test_result_of_null_is_kept() {
+    let reply = call(r#"{"jsonrpc": "2.0", "method": "update", "params": [1], "id": 1}"#);
+    assert_eq!(
+        reply,
+        Some(json!({"jsonrpc": "2.0", "result": null, "id": 1}))
+    );
+}
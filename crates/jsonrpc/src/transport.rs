@@ -0,0 +1,119 @@
+use std::io;
+
+use serde::Serialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const CONTENT_LENGTH: &str = "Content-Length";
+
+/// Read the body of the next message. Each message is prefixed by a set of
+/// headers, the same as the LSP base protocol:
+///
+/// ```text
+/// Content-Length: 40\r\n
+/// \r\n
+/// {"jsonrpc":"2.0","id":1,"method":"getFoo"}
+/// ```
+///
+/// Returns `None` when the stream is closed cleanly between messages.
+pub async fn read_frame<R>(reader: &mut R) -> io::Result<Option<String>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut content_length = None;
+    let mut read_any = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            if read_any {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            return Ok(None);
+        }
+        read_any = true;
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_data(&format!("malformed header: {line}")))?;
+        if name.eq_ignore_ascii_case(CONTENT_LENGTH) {
+            let len = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| invalid_data(&format!("invalid content length: {value}")))?;
+            content_length = Some(len);
+        }
+    }
+
+    let len = content_length.ok_or_else(|| invalid_data("missing Content-Length header"))?;
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await?;
+    let body = String::from_utf8(body).map_err(|err| invalid_data(&err.to_string()))?;
+    Ok(Some(body))
+}
+
+/// Write a message, or a batch of messages, with the headers expected by
+/// `read_frame`.
+pub async fn write_message<W, M>(writer: &mut W, message: &M) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    M: Serialize,
+{
+    let body = serde_json::to_string(message)?;
+    write_frame(writer, &body).await
+}
+
+pub async fn write_frame<W>(writer: &mut W, body: &str) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let header = format!("{CONTENT_LENGTH}: {}\r\n\r\n", body.len());
+    writer.write_all(header.as_bytes()).await?;
+    writer.write_all(body.as_bytes()).await?;
+    writer.flush().await
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::{Request, WireMessage};
+    use tokio::io::BufReader;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    #[tokio::test]
+    async fn test_written_message_can_be_read_back() -> TestResult {
+        let msg = WireMessage::request(Request::new(1, "getFoo", None));
+        let mut buf = vec![];
+        write_message(&mut buf, &msg).await?;
+
+        let mut reader = BufReader::new(buf.as_slice());
+        let body = read_frame(&mut reader).await?;
+        assert_eq!(body, Some(serde_json::to_string(&msg)?));
+        assert_eq!(read_frame(&mut reader).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ignores_unknown_headers() -> TestResult {
+        let input = "Content-Type: application/json\r\ncontent-length: 2\r\n\r\n{}";
+        let mut reader = BufReader::new(input.as_bytes());
+        assert_eq!(read_frame(&mut reader).await?, Some("{}".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_content_length_is_an_error() {
+        let input = "Content-Type: application/json\r\n\r\n{}";
+        let mut reader = BufReader::new(input.as_bytes());
+        let err = read_frame(&mut reader).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
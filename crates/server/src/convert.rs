@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use core::diagnostics::{Applicability, Diagnostic, Level, Suggestion};
+use core::queries::{line_index, SourceFile};
+use core::span::Span;
+use core::Db;
+use lsp_types::{
+    CodeAction, CodeActionKind, DiagnosticRelatedInformation, DiagnosticSeverity, Location,
+    NumberOrString, Position, Range, TextEdit, Url, WorkspaceEdit,
+};
+
+pub fn to_lsp_diagnostic(db: &dyn Db, diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    let range = diagnostic
+        .span
+        .map_or(Range::default(), |span| range(db, span));
+    let severity = match diagnostic.level {
+        Level::Error => DiagnosticSeverity::ERROR,
+        Level::Warning => DiagnosticSeverity::WARNING,
+        Level::Info => DiagnosticSeverity::INFORMATION,
+    };
+    lsp_types::Diagnostic {
+        range,
+        severity: Some(severity),
+        code: diagnostic
+            .code
+            .map(|code| NumberOrString::String(code.name.to_string())),
+        source: Some("haml".to_string()),
+        message: message(diagnostic),
+        related_information: related_information(db, diagnostic),
+        ..lsp_types::Diagnostic::default()
+    }
+}
+
+/// A suggestion as a quick fix for `diagnostic`, which is in the document at
+/// `uri`. Suggestions that change files without a URI can't be offered.
+pub fn to_code_action(
+    db: &dyn Db,
+    uri: &Url,
+    document: SourceFile,
+    diagnostic: &lsp_types::Diagnostic,
+    suggestion: &Suggestion,
+) -> Option<CodeAction> {
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for edit in &suggestion.edits {
+        let file = edit.span.file;
+        let edit_uri = match file == document {
+            true => uri.clone(),
+            false => Url::from_file_path(file.path(db)).ok()?,
+        };
+        let text_edit = TextEdit::new(range(db, edit.span), edit.replacement.clone());
+        changes.entry(edit_uri).or_default().push(text_edit);
+    }
+    Some(CodeAction {
+        title: suggestion.message.clone(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit::new(changes)),
+        is_preferred: Some(suggestion.applicability == Applicability::MachineApplicable),
+        ..CodeAction::default()
+    })
+}
+
+// Clients show the message as it is, so notes and help are added to it
+fn message(diagnostic: &Diagnostic) -> String {
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\nnote: {note}"));
+    }
+    for help in &diagnostic.help {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+    message
+}
+
+// Secondary labels, for the files that have a URI
+fn related_information(
+    db: &dyn Db,
+    diagnostic: &Diagnostic,
+) -> Option<Vec<DiagnosticRelatedInformation>> {
+    let related: Vec<_> = diagnostic
+        .labels
+        .iter()
+        .filter_map(|label| {
+            let uri = Url::from_file_path(label.span.file.path(db)).ok()?;
+            Some(DiagnosticRelatedInformation {
+                location: Location::new(uri, range(db, label.span)),
+                message: label.message.clone(),
+            })
+        })
+        .collect();
+    (!related.is_empty()).then_some(related)
+}
+
+fn range(db: &dyn Db, span: Span) -> Range {
+    Range::new(
+        position(db, span.file, span.start),
+        position(db, span.file, span.end),
+    )
+}
+
+/// LSP positions count columns in UTF-16 code units
+fn position(db: &dyn Db, file: SourceFile, offset: usize) -> Position {
+    let position = line_index(db, file).position(offset);
+    Position::new(position.line as u32, position.utf16 as u32)
+}
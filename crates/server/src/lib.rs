@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use jsonrpc::conn::Client;
+use jsonrpc::middleware::CatchPanic;
+use jsonrpc::router::Router;
+use jsonrpc::server::{Listen, Server, DEFAULT_MAX_CONNECTIONS};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Exit, Initialized,
+    Notification as _,
+};
+use lsp_types::request::{CodeActionRequest, Initialize, Request as _, Shutdown};
+use lsp_types::{
+    CodeActionParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, InitializeParams, InitializedParams,
+};
+
+mod convert;
+pub mod replay;
+mod session;
+
+pub use session::Session;
+
+/// How to serve the language server, as the arguments of both `haml-server`
+/// and `haml lsp`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Where to accept clients from: `stdio`, `tcp://<addr>` or `unix://<path>`
+    #[arg(short, long, default_value = "stdio")]
+    pub listen: Listen,
+
+    /// Maximum number of clients to serve at once
+    #[arg(long, default_value_t = DEFAULT_MAX_CONNECTIONS)]
+    pub max_connections: usize,
+
+    /// Record every message to `session-<n>.jsonl` in this directory, so the
+    /// session can be replayed as a regression test
+    #[arg(long, value_name = "DIR")]
+    pub record: Option<PathBuf>,
+}
+
+/// Run the language server until it is interrupted, or until the client
+/// disconnects when serving over stdio.
+pub fn run(args: Args) -> io::Result<()> {
+    let mut server = Server::new(args.listen).max_connections(args.max_connections);
+    if let Some(dir) = args.record {
+        server = server.record(dir);
+    }
+
+    // The `core` crate shadows `::core`, which the tokio macros rely on, so the
+    // runtime is built by hand.
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(async {
+        if server.addr != Listen::Stdio {
+            eprintln!("Listening on {}", server.addr);
+        }
+        let shutdown = async {
+            let _ = tokio::signal::ctrl_c().await;
+        };
+        server.serve_with_shutdown(router, shutdown).await
+    });
+    // Reading stdin blocks a thread that can't be interrupted, so the runtime
+    // doesn't wait for it once the client has exited
+    runtime.shutdown_background();
+    result
+}
+
+/// Build the router for a single client of the language server. Each client
+/// gets its own session, so open documents are never shared between clients.
+pub fn router(client: Client) -> Router {
+    let session = Rc::new(RefCell::new(Session::new(client.clone())));
+
+    let initialize = session.clone();
+    let did_open = session.clone();
+    let did_change = session.clone();
+    let did_close = session.clone();
+    let code_action = session;
+
+    Router::new()
+        .layer(CatchPanic)
+        .register(Initialize::METHOD, move |params: InitializeParams| {
+            initialize.borrow_mut().initialize(params)
+        })
+        .register(Initialized::METHOD, |_: InitializedParams| {})
+        .register(Shutdown::METHOD, |_: ()| {})
+        // The client is done with the server, so the connection is closed
+        // rather than waiting for the client to close it
+        .register(Exit::METHOD, move |_: ()| client.close())
+        .register(
+            DidOpenTextDocument::METHOD,
+            move |params: DidOpenTextDocumentParams| did_open.borrow_mut().open(params),
+        )
+        .register(
+            DidChangeTextDocument::METHOD,
+            move |params: DidChangeTextDocumentParams| did_change.borrow_mut().change(params),
+        )
+        .register(
+            DidCloseTextDocument::METHOD,
+            move |params: DidCloseTextDocumentParams| did_close.borrow_mut().close(params),
+        )
+        .register(
+            CodeActionRequest::METHOD,
+            move |params: CodeActionParams| code_action.borrow().code_action(params),
+        )
+}
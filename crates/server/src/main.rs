@@ -1,4 +1,16 @@
-#[tokio::main]
-async fn main() {
-    println!("Hello, world!");
+use clap::Parser;
+
+/// A language server for haml specs
+#[derive(Parser)]
+#[command(bin_name = "haml-server", author = "Harrison Turton", version)]
+struct Cli {
+    #[command(flatten)]
+    args: server::Args,
+}
+
+fn main() {
+    if let Err(err) = server::run(Cli::parse().args) {
+        eprintln!("Server failed: {err}");
+        std::process::exit(1);
+    }
 }
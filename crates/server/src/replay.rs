@@ -0,0 +1,146 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use jsonrpc::conn::Connection;
+use jsonrpc::record::{read_session, Direction, Entry, Recorder};
+use jsonrpc::transport::{read_frame, write_frame};
+use serde_json::Value;
+use tokio::io::{duplex, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::{spawn_local, LocalSet};
+use tokio::time::timeout;
+
+/// How long to wait for the server to send a message that the recording says
+/// it sent. A server that no longer sends it fails the comparison afterwards
+/// rather than hanging the replay.
+const WAIT_FOR_SERVER: Duration = Duration::from_secs(1);
+
+/// How long the server has to be quiet after a message before the next one is
+/// sent, when a session is recorded again
+const SETTLE: Duration = Duration::from_millis(100);
+
+// When to send the next incoming message
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Pace {
+    // Once the server has sent what the recording says it had by then
+    Recorded,
+    // Once the server has sent everything it's going to
+    Settled,
+}
+
+/// Replay the client's side of a session recorded with `--record` against a
+/// fresh, in-process server. Returns every message the server sent, in order.
+pub fn replay_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<Value>> {
+    replay(&read_session(path)?)
+}
+
+/// Replay the incoming messages of a recorded session. Each one is only sent
+/// once the server has sent as many messages as it had at that point in the
+/// recording, so replies to server-initiated requests line up with them.
+pub fn replay(entries: &[Entry]) -> io::Result<Vec<Value>> {
+    let (sent, _) = run(entries, Pace::Recorded)?;
+    Ok(sent)
+}
+
+/// Replay the incoming messages of a recorded session and record it again,
+/// as the server would send it now. Each message is sent once the server has
+/// been quiet for a while, so the recording doesn't depend on how fast the
+/// server was.
+pub fn record_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<Entry>> {
+    let (_, recorded) = run(&read_session(path)?, Pace::Settled)?;
+    Ok(recorded)
+}
+
+fn run(entries: &[Entry], pace: Pace) -> io::Result<(Vec<Value>, Vec<Entry>)> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    // Routers aren't `Send`, so the server runs on a local task set.
+    let tasks = LocalSet::new();
+    runtime.block_on(tasks.run_until(replay_entries(entries, pace)))
+}
+
+async fn replay_entries(entries: &[Entry], pace: Pace) -> io::Result<(Vec<Value>, Vec<Entry>)> {
+    let (mut to_server, server_reader) = duplex(64 * 1024);
+    let (server_writer, from_server) = duplex(64 * 1024);
+
+    let recording = Recording::default();
+    let conn =
+        Connection::new(server_reader, server_writer).record(Recorder::new(recording.clone()));
+    let router = crate::router(conn.client());
+    let server = spawn_local(async move { conn.run(&router).await });
+
+    let (sent_tx, mut sent) = mpsc::unbounded_channel();
+    spawn_local(async move {
+        let mut reader = BufReader::new(from_server);
+        while let Ok(Some(frame)) = read_frame(&mut reader).await {
+            if sent_tx.send(frame).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut received = vec![];
+    let mut expected = 0;
+    for entry in entries {
+        match entry.direction {
+            Direction::Outgoing => expected += 1,
+            Direction::Incoming => {
+                while pace == Pace::Recorded && received.len() < expected {
+                    match timeout(WAIT_FOR_SERVER, sent.recv()).await {
+                        Ok(Some(frame)) => received.push(frame),
+                        _ => break,
+                    }
+                }
+                write_frame(&mut to_server, &entry.body()).await?;
+                if pace == Pace::Settled {
+                    while let Ok(Some(frame)) = timeout(SETTLE, sent.recv()).await {
+                        received.push(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    // Closing our side lets the server flush whatever it still has queued and
+    // then close its side, which ends the stream of sent messages.
+    drop(to_server);
+    server.await.map_err(io::Error::other)??;
+    while let Some(frame) = sent.recv().await {
+        received.push(frame);
+    }
+
+    let sent = received
+        .iter()
+        .map(|frame| serde_json::from_str(frame).unwrap_or_else(|_| Value::String(frame.clone())))
+        .collect();
+    Ok((sent, recording.entries()?))
+}
+
+// What the recorder wrote, kept in memory
+#[derive(Clone, Default)]
+struct Recording(Arc<Mutex<Vec<u8>>>);
+
+impl Recording {
+    fn entries(&self) -> io::Result<Vec<Entry>> {
+        let recorded = self.0.lock().unwrap();
+        let mut entries = vec![];
+        for line in String::from_utf8_lossy(&recorded).lines() {
+            entries.push(serde_json::from_str(line)?);
+        }
+        Ok(entries)
+    }
+}
+
+impl Write for Recording {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use core::db::Database;
+use core::diagnostics::{diagnostics_for, Diagnostic};
+use core::lint::Config;
+use core::queries::{SourceFile, TrackedConfig};
+use jsonrpc::conn::Client;
+use lsp_types::notification::{Notification as _, PublishDiagnostics};
+use lsp_types::{
+    CodeActionKind, CodeActionOptions, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, InitializeParams, InitializeResult,
+    PublishDiagnosticsParams, Range, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+
+use crate::convert::{to_code_action, to_lsp_diagnostic};
+
+/// State for one client of the language server
+pub struct Session {
+    client: Client,
+    db: Database,
+    documents: HashMap<Url, Document>,
+}
+
+// An open document, and the lint config of the project it's in
+#[derive(Clone, Copy)]
+struct Document {
+    file: SourceFile,
+    config: TrackedConfig,
+}
+
+impl Session {
+    pub fn new(client: Client) -> Session {
+        Session {
+            client,
+            db: Database::default(),
+            documents: HashMap::new(),
+        }
+    }
+
+    pub fn initialize(&mut self, _params: InitializeParams) -> InitializeResult {
+        InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::FULL,
+                )),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        ..CodeActionOptions::default()
+                    },
+                )),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: "haml".to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            }),
+        }
+    }
+
+    pub fn open(&mut self, params: DidOpenTextDocumentParams) {
+        let document = params.text_document;
+        self.update(document.uri, document.text);
+    }
+
+    pub fn change(&mut self, params: DidChangeTextDocumentParams) {
+        // We only ask for full syncs, so the last change is the whole document
+        if let Some(change) = params.content_changes.into_iter().last() {
+            self.update(params.text_document.uri, change.text);
+        }
+    }
+
+    pub fn close(&mut self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.documents.remove(&uri);
+        self.publish(uri, vec![]);
+    }
+
+    fn update(&mut self, uri: Url, text: String) {
+        // Each document keeps the same inputs while it's open, so changing it
+        // only recomputes the queries that depend on what changed. A config
+        // that can't be read is reported by `haml check`, so the default
+        // levels are used until it's fixed.
+        let document = match self.documents.get(&uri) {
+            Some(&document) => {
+                document.file.set_text(&mut self.db).to(text);
+                let config = Config::find(document.file.path(&self.db)).unwrap_or_default();
+                if *document.config.config(&self.db) != config {
+                    document.config.set_config(&mut self.db).to(config);
+                }
+                document
+            }
+            None => {
+                let path = uri
+                    .to_file_path()
+                    .unwrap_or_else(|_| PathBuf::from(uri.path()));
+                let config = Config::find(&path).unwrap_or_default();
+                let document = Document {
+                    file: SourceFile::new(&self.db, path, text),
+                    config: TrackedConfig::new(&self.db, config),
+                };
+                self.documents.insert(uri.clone(), document);
+                document
+            }
+        };
+
+        let diagnostics = self
+            .diagnostics(document)
+            .iter()
+            .map(|diagnostic| to_lsp_diagnostic(&self.db, diagnostic))
+            .collect();
+        self.publish(uri, diagnostics);
+    }
+
+    /// Quick fixes for the diagnostics in the requested range
+    pub fn code_action(&self, params: CodeActionParams) -> Option<CodeActionResponse> {
+        let uri = params.text_document.uri;
+        let document = *self.documents.get(&uri)?;
+        let mut actions = vec![];
+        for diagnostic in self.diagnostics(document) {
+            let lsp_diagnostic = to_lsp_diagnostic(&self.db, &diagnostic);
+            if !overlaps(lsp_diagnostic.range, params.range) {
+                continue;
+            }
+            for suggestion in &diagnostic.suggestions {
+                let action =
+                    to_code_action(&self.db, &uri, document.file, &lsp_diagnostic, suggestion);
+                actions.extend(action.map(CodeActionOrCommand::CodeAction));
+            }
+        }
+        Some(actions)
+    }
+
+    // Everything wrong with the document itself, linted with the config of
+    // the project it's in. Diagnostics in the files it imports are left for
+    // when they're opened.
+    fn diagnostics(&self, document: Document) -> Vec<Diagnostic> {
+        diagnostics_for(&self.db, document.file, document.config)
+            .into_iter()
+            .find(|(file, _)| *file == document.file)
+            .map(|(_, diagnostics)| diagnostics)
+            .unwrap_or_default()
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<lsp_types::Diagnostic>) {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        let params = serde_json::to_value(params).expect("diagnostics are serializable");
+        let _ = self.client.notify(PublishDiagnostics::METHOD, params);
+    }
+}
+
+fn overlaps(a: Range, b: Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
@@ -0,0 +1,80 @@
+// Replays every recorded session in `tests/sessions` against the server and
+// compares what it sent with the matching `.golden.jsonl` file. Run with
+// `HAML_BLESS=1` after an intended change to record each session again and
+// update its golden file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use jsonrpc::record::Direction;
+use serde::Serialize;
+
+const SESSION_SUFFIX: &str = ".session.jsonl";
+const GOLDEN_SUFFIX: &str = ".golden.jsonl";
+
+fn sessions() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sessions");
+    let mut sessions: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(SESSION_SUFFIX))
+        .collect();
+    sessions.sort();
+    sessions
+}
+
+fn golden_path(session: &Path) -> PathBuf {
+    let session = session.to_string_lossy();
+    let name = session.strip_suffix(SESSION_SUFFIX).unwrap();
+    PathBuf::from(format!("{name}{GOLDEN_SUFFIX}"))
+}
+
+fn lines<T: Serialize>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| format!("{}\n", serde_json::to_string(value).unwrap()))
+        .collect()
+}
+
+// Record the session again, and take what the server sent as the golden output
+fn bless_session(session: &Path, golden: &Path) {
+    let entries = server::replay::record_file(session).unwrap();
+    let sent: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.direction == Direction::Outgoing)
+        .map(|entry| entry.message.clone())
+        .collect();
+    fs::write(session, lines(&entries)).unwrap();
+    fs::write(golden, lines(&sent)).unwrap();
+}
+
+#[test]
+fn test_recorded_sessions_match_golden_output() {
+    let bless = std::env::var_os("HAML_BLESS").is_some();
+    let mut failures = vec![];
+
+    for session in sessions() {
+        let golden = golden_path(&session);
+        if bless {
+            bless_session(&session, &golden);
+            continue;
+        }
+
+        let sent = server::replay::replay_file(&session).unwrap();
+        let actual = lines(&sent);
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        if actual != expected {
+            failures.push(format!(
+                "{}\n--- expected\n{expected}+++ actual\n{actual}",
+                session.display()
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "replayed sessions differ from their golden output \
+         (rerun with HAML_BLESS=1 to update):\n\n{}",
+        failures.join("\n")
+    );
+}